@@ -0,0 +1,654 @@
+//! Corpus statistics over the entity documents.
+//!
+//! Aggregations are requested as a JSON map from names to aggregations:
+//!
+//! ```json
+//! {
+//!     "types": {"terms": {"field": "@type", "size": 5}},
+//!     "authors": {"terms": {"field": "author.@id", "order": {"crates": "desc"},
+//!                 "aggs": {"crates": {"cardinality": {"field": "crate"}}}}},
+//!     "per_year": {"date_histogram": {"field": "datePublished", "interval": "year"}}
+//! }
+//! ```
+//!
+//! Fields are `@id`, `@type` (literal types), `entity_type` (with supertypes),
+//! `crate`, `datePublished`, `indexedAt`, `entityCount`, or a dotted property
+//! path into the entity JSON. Empty buckets are omitted.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use tantivy::TantivyError;
+use tantivy::aggregation::agg_req::Aggregations;
+use tantivy::aggregation::agg_result::{
+    AggregationResult as TantivyResult, AggregationResults, BucketEntries, BucketResult,
+    MetricResult,
+};
+use tantivy::aggregation::{AggregationCollector, AggregationLimitsGuard, Key};
+use tantivy::collector::Count;
+use tantivy::query::Query;
+
+use crate::error::IndexError;
+use crate::index::SearchIndex;
+
+fn default_size() -> usize {
+    10
+}
+
+/// A single aggregation over the matching entities
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Aggregation {
+    /// Most frequent values of a field
+    Terms {
+        field: String,
+        #[serde(default = "default_size")]
+        size: usize,
+        /// Bucket order, e.g. `{"count": "desc"}`, `{"key": "asc"}` or a metric sub-aggregation
+        #[serde(default)]
+        order: Option<BTreeMap<String, String>>,
+        #[serde(default)]
+        aggs: BTreeMap<String, Aggregation>,
+    },
+    /// Numeric buckets of a fixed width
+    Histogram {
+        field: String,
+        interval: f64,
+        #[serde(default)]
+        aggs: BTreeMap<String, Aggregation>,
+    },
+    /// Calendar buckets of `datePublished` or `indexedAt`
+    DateHistogram {
+        field: String,
+        interval: DateInterval,
+        #[serde(default)]
+        aggs: BTreeMap<String, Aggregation>,
+    },
+    /// Approximate number of distinct values of a field
+    Cardinality { field: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateInterval {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Aggregation {
+    /// Terms aggregation with the given number of buckets
+    pub fn terms(field: impl Into<String>, size: usize) -> Self {
+        Aggregation::Terms {
+            field: field.into(),
+            size,
+            order: None,
+            aggs: BTreeMap::new(),
+        }
+    }
+
+    /// Calendar date histogram
+    pub fn date_histogram(field: impl Into<String>, interval: DateInterval) -> Self {
+        Aggregation::DateHistogram {
+            field: field.into(),
+            interval,
+            aggs: BTreeMap::new(),
+        }
+    }
+
+    /// Distinct value count
+    pub fn cardinality(field: impl Into<String>) -> Self {
+        Aggregation::Cardinality {
+            field: field.into(),
+        }
+    }
+
+    /// Add a named sub-aggregation computed for every bucket
+    pub fn with_sub_aggregation(mut self, name: impl Into<String>, sub: Aggregation) -> Self {
+        match &mut self {
+            Aggregation::Terms { aggs, .. }
+            | Aggregation::Histogram { aggs, .. }
+            | Aggregation::DateHistogram { aggs, .. } => {
+                aggs.insert(name.into(), sub);
+            }
+            Aggregation::Cardinality { .. } => {}
+        }
+        self
+    }
+
+    /// Order terms buckets by a key (`count`, `key` or a sub-aggregation name)
+    pub fn with_order(mut self, key: impl Into<String>, descending: bool) -> Self {
+        if let Aggregation::Terms { order, .. } = &mut self {
+            let direction = if descending { "desc" } else { "asc" };
+            *order = Some(BTreeMap::from([(key.into(), direction.to_string())]));
+        }
+        self
+    }
+
+    /// Parse a JSON map of named aggregations
+    pub fn parse_map(value: &Value) -> Result<BTreeMap<String, Aggregation>, IndexError> {
+        serde_json::from_value(value.clone()).map_err(|e| invalid(e.to_string()))
+    }
+
+    fn sub_aggregations(&self) -> Option<&BTreeMap<String, Aggregation>> {
+        match self {
+            Aggregation::Terms { aggs, .. }
+            | Aggregation::Histogram { aggs, .. }
+            | Aggregation::DateHistogram { aggs, .. } => Some(aggs),
+            Aggregation::Cardinality { .. } => None,
+        }
+    }
+}
+
+/// Result of one aggregation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum AggregationResult {
+    Buckets {
+        buckets: Vec<Bucket>,
+        /// Matches not covered by the returned terms buckets
+        #[serde(skip_serializing_if = "Option::is_none")]
+        other_count: Option<u64>,
+    },
+    Value {
+        value: Option<f64>,
+    },
+}
+
+/// One bucket of a terms or histogram aggregation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    /// Term, lower bound of a histogram bucket, or calendar period (`2024`, `2024-05`, `2024-05-13`)
+    pub key: Value,
+    pub doc_count: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aggs: BTreeMap<String, AggregationResult>,
+}
+
+/// Aggregation results with the number of matching entities
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Aggregates {
+    pub total: usize,
+    pub aggs: BTreeMap<String, AggregationResult>,
+}
+
+fn invalid(reason: impl Into<String>) -> IndexError {
+    IndexError::InvalidQuery {
+        path: "aggs".to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Fast field (or JSON path) holding the values of a request field
+fn column_name(field: &str) -> String {
+    match field {
+        "@id" | "id" => "id".to_string(),
+        "@type" | "entity_type_exact" => "entity_type_exact".to_string(),
+        "entity_type" => "entity_type".to_string(),
+        "crate" | "occurs_in" => "occurs_in".to_string(),
+        "datePublished" | "date_published" => "date_published".to_string(),
+        "indexedAt" | "indexed_at" => "indexed_at".to_string(),
+        "entityCount" | "entity_count" => "entity_count".to_string(),
+        path => format!(
+            "properties.{}",
+            path.strip_prefix("properties.").unwrap_or(path)
+        ),
+    }
+}
+
+fn is_calendar(interval: DateInterval) -> bool {
+    matches!(interval, DateInterval::Month | DateInterval::Year)
+}
+
+/// Run aggregations over the documents matching `query`
+pub(crate) fn run(
+    index: &SearchIndex,
+    query: &dyn Query,
+    aggs: &BTreeMap<String, Aggregation>,
+) -> Result<Aggregates, IndexError> {
+    let searcher = index.searcher();
+
+    // Calendar histograms become range aggregations between the field's bounds
+    let mut date_fields = BTreeSet::new();
+    collect_calendar_fields(aggs, &mut date_fields)?;
+    let bounds = if date_fields.is_empty() {
+        BTreeMap::new()
+    } else {
+        let mut request = Map::new();
+        for field in &date_fields {
+            request.insert(format!("{}_min", field), json!({"min": {"field": field}}));
+            request.insert(format!("{}_max", field), json!({"max": {"field": field}}));
+        }
+        let results = execute(&searcher, query, Value::Object(request))?.1;
+        let value = |name: String| match results.0.get(&name) {
+            Some(TantivyResult::MetricResult(MetricResult::Min(m) | MetricResult::Max(m))) => {
+                m.value
+            }
+            _ => None,
+        };
+        date_fields
+            .iter()
+            .filter_map(|field| {
+                let min = value(format!("{}_min", field))?;
+                let max = value(format!("{}_max", field))?;
+                Some((field.clone(), (nanos_to_date(min), nanos_to_date(max))))
+            })
+            .collect()
+    };
+
+    let request = Value::Object(to_tantivy(aggs, &bounds));
+    let (total, results) = execute(&searcher, query, request)?;
+    Ok(Aggregates {
+        total,
+        aggs: from_tantivy(aggs, results),
+    })
+}
+
+fn execute(
+    searcher: &tantivy::Searcher,
+    query: &dyn Query,
+    request: Value,
+) -> Result<(usize, AggregationResults), IndexError> {
+    let request: Aggregations =
+        serde_json::from_value(request).map_err(|e| invalid(e.to_string()))?;
+    let collector = AggregationCollector::from_aggs(request, AggregationLimitsGuard::default());
+    searcher
+        .search(query, &(Count, collector))
+        .map_err(|e| match e {
+            TantivyError::AggregationError(_)
+            | TantivyError::InvalidArgument(_)
+            | TantivyError::FieldNotFound(_)
+            | TantivyError::SchemaError(_) => invalid(e.to_string()),
+            e => e.into(),
+        })
+}
+
+fn collect_calendar_fields(
+    aggs: &BTreeMap<String, Aggregation>,
+    fields: &mut BTreeSet<String>,
+) -> Result<(), IndexError> {
+    for agg in aggs.values() {
+        if let Aggregation::DateHistogram {
+            field, interval, ..
+        } = agg
+        {
+            let column = column_name(field);
+            if column != "date_published" && column != "indexed_at" {
+                return Err(invalid(format!(
+                    "date_histogram needs datePublished or indexedAt, not '{}'",
+                    field
+                )));
+            }
+            if is_calendar(*interval) {
+                fields.insert(column);
+            }
+        }
+        if let Some(sub) = agg.sub_aggregations() {
+            collect_calendar_fields(sub, fields)?;
+        }
+    }
+    Ok(())
+}
+
+fn nanos_to_date(nanos: f64) -> NaiveDate {
+    DateTime::<Utc>::from_timestamp((nanos / 1e9).floor() as i64, 0)
+        .unwrap_or_default()
+        .date_naive()
+}
+
+fn date_to_nanos(date: NaiveDate) -> f64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as f64 * 1e9
+}
+
+/// Calendar periods covering `[min, max]`, as (key, start, end)
+fn calendar_ranges(
+    (min, max): (NaiveDate, NaiveDate),
+    interval: DateInterval,
+) -> Vec<(String, NaiveDate, NaiveDate)> {
+    let next = |date: NaiveDate| match interval {
+        DateInterval::Year => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+        _ if date.month() == 12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+        _ => NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1),
+    };
+    let mut start = match interval {
+        DateInterval::Year => NaiveDate::from_ymd_opt(min.year(), 1, 1),
+        _ => NaiveDate::from_ymd_opt(min.year(), min.month(), 1),
+    };
+
+    let mut ranges = Vec::new();
+    while let Some(from) = start
+        && from <= max
+        && let Some(to) = next(from)
+    {
+        let key = match interval {
+            DateInterval::Year => format!("{}", from.year()),
+            _ => format!("{}-{:02}", from.year(), from.month()),
+        };
+        ranges.push((key, from, to));
+        start = Some(to);
+    }
+    ranges
+}
+
+fn to_tantivy(
+    aggs: &BTreeMap<String, Aggregation>,
+    bounds: &BTreeMap<String, (NaiveDate, NaiveDate)>,
+) -> Map<String, Value> {
+    let mut request = Map::new();
+    for (name, agg) in aggs {
+        let mut entry = match agg {
+            Aggregation::Terms {
+                field, size, order, ..
+            } => {
+                let mut terms = json!({"field": column_name(field), "size": size});
+                if let Some(order) = order {
+                    let order: Map<String, Value> = order
+                        .iter()
+                        .map(|(key, direction)| {
+                            let key = match key.as_str() {
+                                "count" => "_count",
+                                "key" => "_key",
+                                other => other,
+                            };
+                            (key.to_string(), Value::from(direction.as_str()))
+                        })
+                        .collect();
+                    terms["order"] = Value::Object(order);
+                }
+                json!({ "terms": terms })
+            }
+            Aggregation::Histogram {
+                field, interval, ..
+            } => json!({"histogram": {
+                "field": column_name(field),
+                "interval": interval,
+                "min_doc_count": 1
+            }}),
+            Aggregation::DateHistogram {
+                field, interval, ..
+            } => {
+                let column = column_name(field);
+                match interval {
+                    DateInterval::Day | DateInterval::Week => json!({"date_histogram": {
+                        "field": column,
+                        "fixed_interval": if *interval == DateInterval::Day { "1d" } else { "7d" },
+                        "min_doc_count": 1
+                    }}),
+                    _ => {
+                        let ranges: Vec<Value> = bounds
+                            .get(&column)
+                            .map(|b| calendar_ranges(*b, *interval))
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|(key, from, to)| {
+                                json!({
+                                    "key": key,
+                                    "from": date_to_nanos(from),
+                                    "to": date_to_nanos(to)
+                                })
+                            })
+                            .collect();
+                        if ranges.is_empty() {
+                            // No dated documents: nothing to bucket
+                            continue;
+                        }
+                        json!({"range": {"field": column, "ranges": ranges}})
+                    }
+                }
+            }
+            Aggregation::Cardinality { field } => {
+                json!({"cardinality": {"field": column_name(field)}})
+            }
+        };
+        if let Some(sub) = agg.sub_aggregations()
+            && !sub.is_empty()
+        {
+            entry["aggs"] = Value::Object(to_tantivy(sub, bounds));
+        }
+        request.insert(name.clone(), entry);
+    }
+    request
+}
+
+fn key_value(key: Key) -> Value {
+    match key {
+        Key::Str(s) => Value::from(s),
+        Key::I64(i) => Value::from(i),
+        Key::U64(u) => Value::from(u),
+        Key::F64(f) => Value::from(f),
+    }
+}
+
+fn from_tantivy(
+    aggs: &BTreeMap<String, Aggregation>,
+    mut results: AggregationResults,
+) -> BTreeMap<String, AggregationResult> {
+    let no_subs = BTreeMap::new();
+    aggs.iter()
+        .map(|(name, agg)| {
+            let subs = agg.sub_aggregations().unwrap_or(&no_subs);
+            let result = match results.0.remove(name) {
+                Some(TantivyResult::MetricResult(metric)) => AggregationResult::Value {
+                    value: match metric {
+                        MetricResult::Cardinality(m) => m.value,
+                        _ => None,
+                    },
+                },
+                Some(TantivyResult::BucketResult(BucketResult::Terms {
+                    buckets,
+                    sum_other_doc_count,
+                    ..
+                })) => AggregationResult::Buckets {
+                    buckets: buckets
+                        .into_iter()
+                        .map(|b| Bucket {
+                            key: key_value(b.key),
+                            doc_count: b.doc_count,
+                            aggs: from_tantivy(subs, b.sub_aggregation),
+                        })
+                        .collect(),
+                    other_count: Some(sum_other_doc_count),
+                },
+                Some(TantivyResult::BucketResult(BucketResult::Histogram { buckets })) => {
+                    let is_date = matches!(agg, Aggregation::DateHistogram { .. });
+                    AggregationResult::Buckets {
+                        buckets: entries(buckets)
+                            .into_iter()
+                            .map(|b| Bucket {
+                                key: match (is_date, b.key_as_string) {
+                                    // RFC 3339 timestamp; keep the date
+                                    (true, Some(date)) => Value::from(&date[..10.min(date.len())]),
+                                    _ => key_value(b.key),
+                                },
+                                doc_count: b.doc_count,
+                                aggs: from_tantivy(subs, b.sub_aggregation),
+                            })
+                            .collect(),
+                        other_count: None,
+                    }
+                }
+                Some(TantivyResult::BucketResult(BucketResult::Range { buckets })) => {
+                    let mut buckets: Vec<Bucket> = entries(buckets)
+                        .into_iter()
+                        // Drop the open-ended buckets tantivy adds around the ranges
+                        .filter(|b| b.doc_count > 0 && b.from.is_some() && b.to.is_some())
+                        .map(|b| Bucket {
+                            key: key_value(b.key),
+                            doc_count: b.doc_count,
+                            aggs: from_tantivy(subs, b.sub_aggregation),
+                        })
+                        .collect();
+                    buckets.sort_by(|a, b| a.key.as_str().cmp(&b.key.as_str()));
+                    AggregationResult::Buckets {
+                        buckets,
+                        other_count: None,
+                    }
+                }
+                None => match agg {
+                    Aggregation::Cardinality { .. } => AggregationResult::Value { value: None },
+                    _ => AggregationResult::Buckets {
+                        buckets: Vec::new(),
+                        other_count: None,
+                    },
+                },
+            };
+            (name.clone(), result)
+        })
+        .collect()
+}
+
+fn entries<T>(buckets: BucketEntries<T>) -> Vec<T> {
+    match buckets {
+        BucketEntries::Vec(vec) => vec,
+        BucketEntries::HashMap(map) => map.into_values().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tantivy::query::AllQuery;
+
+    fn test_index() -> SearchIndex {
+        let alice = json!({"@id": "https://orcid.org/alice"});
+        let crates: Vec<_> = [
+            ("crate-a", "2023-03-01", "CC-BY-4.0"),
+            ("crate-b", "2023-11-20", "CC-BY-4.0"),
+            ("crate-c", "2024-06-15", "MIT"),
+        ]
+        .into_iter()
+        .map(|(crate_id, date, license)| {
+            let entities = vec![
+                json!({
+                    "@id": "./",
+                    "@type": "Dataset",
+                    "datePublished": date,
+                    "license": {"@id": license},
+                    "author": alice,
+                    "keywords": ["ecology", crate_id]
+                }),
+                json!({"@id": "https://orcid.org/alice", "@type": "Person"}),
+            ];
+            (crate_id, entities)
+        })
+        .collect();
+        SearchIndex::with_entities(&crates)
+    }
+
+    #[test]
+    fn test_terms_and_cardinality() {
+        let index = test_index();
+        let aggs = Aggregation::parse_map(&json!({
+            "types": {"terms": {"field": "@type"}},
+            "licenses": {"terms": {"field": "license.@id", "size": 1}},
+            "keywords": {"terms": {"field": "keywords", "size": 1}},
+            "crates": {"cardinality": {"field": "crate"}}
+        }))
+        .unwrap();
+        let result = run(&index, &AllQuery, &aggs).unwrap();
+        assert_eq!(result.total, 6);
+
+        let AggregationResult::Buckets { buckets, .. } = &result.aggs["types"] else {
+            panic!("expected buckets");
+        };
+        let types: Vec<_> = buckets
+            .iter()
+            .map(|b| (b.key.clone(), b.doc_count))
+            .collect();
+        assert!(types.contains(&(json!("Dataset"), 3)));
+        assert!(types.contains(&(json!("Person"), 3)));
+
+        let AggregationResult::Buckets {
+            buckets,
+            other_count,
+        } = &result.aggs["licenses"]
+        else {
+            panic!("expected buckets");
+        };
+        assert_eq!(buckets[0].key, json!("CC-BY-4.0"));
+        assert_eq!(buckets[0].doc_count, 2);
+        assert_eq!(*other_count, Some(1));
+
+        let AggregationResult::Buckets { buckets, .. } = &result.aggs["keywords"] else {
+            panic!("expected buckets");
+        };
+        assert_eq!(buckets[0].key, json!("ecology"));
+
+        let AggregationResult::Value { value } = result.aggs["crates"] else {
+            panic!("expected a value");
+        };
+        assert_eq!(value.map(f64::round), Some(3.0));
+
+        // Authors with the most crates
+        let aggs = BTreeMap::from([(
+            "authors".to_string(),
+            Aggregation::terms("author.@id", 5)
+                .with_sub_aggregation("crates", Aggregation::cardinality("crate"))
+                .with_order("crates", true),
+        )]);
+        let result = run(&index, &AllQuery, &aggs).unwrap();
+        let AggregationResult::Buckets { buckets, .. } = &result.aggs["authors"] else {
+            panic!("expected buckets");
+        };
+        assert_eq!(buckets[0].key, json!("https://orcid.org/alice"));
+        assert!(matches!(
+            buckets[0].aggs["crates"],
+            AggregationResult::Value { value: Some(v) } if v.round() == 3.0
+        ));
+    }
+
+    #[test]
+    fn test_calendar_histogram_with_sub_aggregation() {
+        let index = test_index();
+        let aggs = BTreeMap::from([(
+            "per_year".to_string(),
+            Aggregation::date_histogram("datePublished", DateInterval::Year)
+                .with_sub_aggregation("licenses", Aggregation::terms("license.@id", 5)),
+        )]);
+        let result = run(&index, &AllQuery, &aggs).unwrap();
+        let AggregationResult::Buckets { buckets, .. } = &result.aggs["per_year"] else {
+            panic!("expected buckets");
+        };
+        let years: Vec<_> = buckets
+            .iter()
+            .map(|b| (b.key.clone(), b.doc_count))
+            .collect();
+        assert_eq!(years, vec![(json!("2023"), 2), (json!("2024"), 1)]);
+        assert!(buckets[0].aggs.contains_key("licenses"));
+
+        let months = BTreeMap::from([(
+            "per_month".to_string(),
+            Aggregation::date_histogram("datePublished", DateInterval::Month),
+        )]);
+        let result = run(&index, &AllQuery, &months).unwrap();
+        let AggregationResult::Buckets { buckets, .. } = &result.aggs["per_month"] else {
+            panic!("expected buckets");
+        };
+        let keys: Vec<_> = buckets.iter().map(|b| b.key.clone()).collect();
+        assert_eq!(
+            keys,
+            vec![json!("2023-03"), json!("2023-11"), json!("2024-06")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let index = test_index();
+        assert!(matches!(
+            Aggregation::parse_map(&json!({"x": {"term": {"field": "@type"}}})),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+        let aggs = Aggregation::parse_map(&json!({
+            "x": {"date_histogram": {"field": "name", "interval": "year"}}
+        }))
+        .unwrap();
+        assert!(matches!(
+            run(&index, &AllQuery, &aggs),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+    }
+}
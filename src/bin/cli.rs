@@ -1,8 +1,13 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
-use rocrate_indexer::{AddResult, CrateIndex, CrateSource};
+use rocrate_indexer::{
+    AddResult, Aggregation, AggregationResult, Bucket, CrateFormat, CrateIndex, CrateScope,
+    CrateSource, DateInterval, ExplanationNode, GraphFormat, HashingEmbedder, IndexConfig,
+    ScoreExplanation, SearchHit, SearchOptions, SortKey,
+};
 
 #[derive(Parser)]
 #[command(name = "rocrate-idx")]
@@ -14,10 +19,11 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Add an RO-Crate from a path or URL
+    /// Add one or more RO-Crates from paths or URLs
     Add {
-        /// Path to directory/zip or URL to ro-crate-metadata.json
-        source: String,
+        /// Paths to directories/zips or URLs to ro-crate-metadata.json
+        #[arg(required = true)]
+        sources: Vec<String>,
     },
     /// Search for crates matching a query (Tantivy query syntax)
     Search {
@@ -32,9 +38,45 @@ enum Commands {
         /// Maximum number of results
         #[arg(short, long, default_value = "10")]
         limit: usize,
+        /// Number of results to skip
+        #[arg(long, default_value = "0")]
+        offset: usize,
+        /// Continue from the cursor printed by a previous search
+        #[arg(long)]
+        cursor: Option<String>,
+        /// Show highlighted snippets of the matching content
+        #[arg(long)]
+        snippets: bool,
+        /// Only search inside this crate ID and its subcrates
+        #[arg(long)]
+        within: Option<String>,
+        /// With --within, skip the crate's subcrates
+        #[arg(long, requires = "within")]
+        no_descendants: bool,
+        /// Rank crates instead of entities, showing the best matches per crate
+        #[arg(long, conflicts_with = "crates_only")]
+        by_crate: bool,
         /// Show only unique crate IDs (deduplicate by crate)
         #[arg(long)]
         crates_only: bool,
+        /// Sort by fields instead of relevance, e.g. "datePublished:desc,name"
+        /// (score, datePublished, name, indexedAt, entityCount)
+        #[arg(long, conflicts_with = "by_crate")]
+        sort: Option<String>,
+        /// Match content terms within this many typos (0 to 2)
+        #[arg(long, default_value = "0")]
+        fuzzy: u8,
+        /// Don't suggest a corrected query when few results match
+        #[arg(long)]
+        no_spellcheck: bool,
+        /// Blend in semantic similarity with this weight, 0 to 1
+        /// (needs ROCRATE_EMBEDDER, e.g. "hashing")
+        #[arg(long, default_value = "0", conflicts_with_all = ["by_crate", "sort", "cursor"])]
+        semantic: f32,
+        /// Explain the score of each hit: matched fields, term scores and
+        /// the parsed query
+        #[arg(long, conflicts_with_all = ["by_crate", "crates_only", "semantic"])]
+        explain: bool,
     },
     /// List all indexed crate IDs
     List {
@@ -45,10 +87,14 @@ enum Commands {
         #[arg(long)]
         json: bool,
     },
-    /// Show full metadata JSON for a crate
+    /// Show full metadata for a crate, as stored or converted to RDF
     Show {
         /// Crate ID to show
         crate_id: String,
+        /// Output format: json (as stored), expanded (JSON-LD), turtle,
+        /// ntriples or nquads
+        #[arg(long, default_value = "json")]
+        format: String,
     },
     /// Show short info (name, description, ancestry path) for a crate
     Info {
@@ -63,42 +109,218 @@ enum Commands {
         /// Crate ID to remove
         crate_id: String,
     },
+    /// Find every occurrence of an entity by its exact @id
+    ById {
+        /// Entity @id (e.g. an ORCID URL)
+        entity_id: String,
+        /// Only search inside this crate ID and its subcrates
+        #[arg(long)]
+        within: Option<String>,
+        /// With --within, skip the crate's subcrates
+        #[arg(long, requires = "within")]
+        no_descendants: bool,
+    },
+    /// Find entities of a type, optionally matching a query
+    ByType {
+        /// @type value, e.g. Person or Dataset
+        entity_type: String,
+        /// Optional query the entities must also match
+        query: Option<String>,
+        /// Maximum number of results
+        #[arg(short, long, default_value = "10")]
+        limit: usize,
+        /// Match the literal @type only, not its schema.org subtypes
+        #[arg(long)]
+        exact: bool,
+        /// Only search inside this crate ID and its subcrates
+        #[arg(long)]
+        within: Option<String>,
+        /// With --within, skip the crate's subcrates
+        #[arg(long, requires = "within")]
+        no_descendants: bool,
+    },
+    /// List all crates that mention an entity @id
+    CratesByEntity {
+        /// Entity @id (e.g. https://orcid.org/0000-0002-1825-0097)
+        entity_id: String,
+        /// Only search inside this crate ID and its subcrates
+        #[arg(long)]
+        within: Option<String>,
+        /// With --within, skip the crate's subcrates
+        #[arg(long, requires = "within")]
+        no_descendants: bool,
+    },
+    /// Run a SPARQL 1.1 query over the RDF graphs of all crates
+    ///
+    /// Prints SPARQL JSON results, or N-Triples for CONSTRUCT and DESCRIBE.
+    Sparql {
+        /// Query, e.g. "SELECT ?d WHERE { ?d a <http://schema.org/Dataset> }"
+        query: String,
+    },
+    /// Export the links between crates (isBasedOn, citation, shared DOIs, ...)
+    /// as a graph for lineage visualisation
+    Graph {
+        /// Output format: dot or graphml
+        #[arg(long, default_value = "dot")]
+        format: String,
+    },
+    /// Show corpus statistics: types, licenses, keywords, authors and datasets per year
+    Stats {
+        /// Only count entities matching this query
+        query: Option<String>,
+        /// Number of values shown per statistic
+        #[arg(short, long, default_value = "10")]
+        size: usize,
+        /// Only count entities inside this crate ID and its subcrates
+        #[arg(long)]
+        within: Option<String>,
+        /// Custom aggregations as JSON (same format as POST /aggregate), printed as JSON
+        #[arg(long)]
+        aggs: Option<String>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    let mut index = CrateIndex::open_or_create()?;
+    let config = IndexConfig::from_current_dir()?.with_embedder(HashingEmbedder::from_env());
+    let mut index = CrateIndex::open_with_config(config)?;
 
     match cli.command {
-        Commands::Add { source } => {
-            let crate_source = parse_source(&source);
-            let result = index.add_from_source(&crate_source)?;
-            print_add_result(&result, 0);
+        Commands::Add { sources } => {
+            // Commit once for all sources instead of once per crate
+            index.begin_batch();
+            let mut failure = None;
+            for source in sources {
+                let crate_source = parse_source(&source);
+                match index.add_from_source(&crate_source) {
+                    Ok(result) => print_add_result(&result, 0),
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                }
+            }
+            // Commit the crates added before a failure so their metadata isn't orphaned
+            index.commit_batch()?;
+            if let Some(e) = failure {
+                return Err(e.into());
+            }
         }
         Commands::Search {
             query,
             limit,
+            offset,
+            cursor,
+            snippets,
+            within,
+            no_descendants,
+            by_crate,
             crates_only,
+            sort,
+            fuzzy,
+            no_spellcheck,
+            semantic,
+            explain,
         } => {
-            let hits = index.search(&query, limit)?;
-            if hits.is_empty() {
-                println!("No results found.");
-            } else if crates_only {
-                // Deduplicate by crate_id
-                let mut seen = std::collections::HashSet::new();
-                for hit in hits {
-                    if seen.insert(hit.crate_id.clone()) {
-                        println!("{}", hit.crate_id);
+            let sort = SortKey::parse_list(sort.as_deref().unwrap_or_default())?;
+            let scope = crate_scope(within, no_descendants);
+            let spellcheck_below = if no_spellcheck {
+                0
+            } else {
+                SearchOptions::SPELLCHECK_BELOW
+            };
+            let base = SearchOptions::default()
+                .with_fuzziness(fuzzy)
+                .with_spellcheck_below(spellcheck_below);
+            if by_crate {
+                let options = base
+                    .with_limit(limit)
+                    .with_offset(offset)
+                    .with_cursor(cursor)
+                    .with_snippets(snippets)
+                    .with_scope(scope.clone());
+                let page = index.search_crates(&query, &options)?;
+                if page.hits.is_empty() {
+                    println!("No results found.");
+                }
+                for hit in &page.hits {
+                    println!("Crate: {}", hit.crate_id);
+                    if let Some(name) = &hit.name {
+                        println!("  Name: {}", name);
                     }
+                    println!(
+                        "  Score: {:.4} ({} matching entities)",
+                        hit.score, hit.matches
+                    );
+                    for entity in &hit.entities {
+                        println!("  - {} ({:.4})", entity.entity_id, entity.score);
+                        if let Some(snippet) = &entity.snippet {
+                            println!("    {}", snippet);
+                        }
+                    }
+                    println!();
+                }
+                if page.total > 0 {
+                    println!(
+                        "Showing {} of {} matching crates",
+                        page.hits.len(),
+                        page.total
+                    );
+                }
+                if let Some(next) = page.next_cursor {
+                    eprintln!("Next page: --cursor {}", next);
+                }
+                if let Some(correction) = page.did_you_mean {
+                    eprintln!("Did you mean: {}", correction);
                 }
             } else {
-                // Show entity and crate for each hit
-                for hit in hits {
-                    println!("Entity: {}", hit.entity_id);
-                    println!("  Crate: {}", hit.crate_id);
-                    println!("  Score: {:.4}", hit.score);
-                    println!();
+                let options = base
+                    .with_limit(limit)
+                    .with_offset(offset)
+                    .with_cursor(cursor)
+                    .with_snippets(snippets)
+                    .with_scope(scope.clone())
+                    .with_sort(sort)
+                    .with_semantic_weight(semantic);
+                let page = index.search_page(&query, &options)?;
+                let hits = page.hits;
+                let hits_len = hits.len();
+                if hits.is_empty() {
+                    println!("No results found.");
+                } else if crates_only {
+                    // Deduplicate by crate_id
+                    let mut seen = std::collections::HashSet::new();
+                    for hit in hits {
+                        if seen.insert(hit.crate_id.clone()) {
+                            println!("{}", hit.crate_id);
+                        }
+                    }
+                } else {
+                    // Show entity and crate for each hit
+                    for hit in &hits {
+                        print_hit(hit);
+                        if explain {
+                            print_explanation(&index.explain(
+                                &query,
+                                &hit.entity_id,
+                                &hit.crate_id,
+                                &options,
+                            )?);
+                        }
+                    }
+                }
+                if !crates_only && page.total > 0 {
+                    println!("Showing {} of {} matching entities", hits_len, page.total);
+                }
+                if let Some(next) = page.next_cursor {
+                    eprintln!("Next page: --cursor {}", next);
+                }
+                if let Some(correction) = page.did_you_mean {
+                    eprintln!("Did you mean: {}", correction);
                 }
             }
         }
@@ -157,13 +379,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }
-        Commands::Show { crate_id } => match index.get_crate_json(&crate_id)? {
-            Some(json) => println!("{}", json),
-            None => {
-                eprintln!("Crate not found: {}", crate_id);
-                std::process::exit(1);
+        Commands::Show { crate_id, format } => {
+            match index.get_crate_as(&crate_id, CrateFormat::parse(&format)?)? {
+                Some(metadata) => println!("{}", metadata),
+                None => {
+                    eprintln!("Crate not found: {}", crate_id);
+                    std::process::exit(1);
+                }
             }
-        },
+        }
         Commands::Info { crate_id, json } => match index.get_crate_info(&crate_id) {
             Some(entry) => {
                 if json {
@@ -212,11 +436,144 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             index.remove(&crate_id)?;
             println!("Removed crate: {}", crate_id);
         }
+        Commands::ById {
+            entity_id,
+            within,
+            no_descendants,
+        } => {
+            let hits = index.search_by_id(&entity_id, crate_scope(within, no_descendants))?;
+            if hits.is_empty() {
+                println!("No results found.");
+            }
+            for hit in &hits {
+                print_hit(hit);
+            }
+        }
+        Commands::ByType {
+            entity_type,
+            query,
+            limit,
+            exact,
+            within,
+            no_descendants,
+        } => {
+            let scope = crate_scope(within, no_descendants);
+            let hits = match (query, exact) {
+                (Some(query), false) => index.search_typed(&entity_type, &query, limit, scope)?,
+                (Some(query), true) => {
+                    index.search_typed_exact(&entity_type, &query, limit, scope)?
+                }
+                (None, false) => index.search_by_type(&entity_type, limit, scope)?,
+                (None, true) => index.search_by_type_exact(&entity_type, limit, scope)?,
+            };
+            if hits.is_empty() {
+                println!("No results found.");
+            }
+            for hit in &hits {
+                print_hit(hit);
+            }
+        }
+        Commands::CratesByEntity {
+            entity_id,
+            within,
+            no_descendants,
+        } => {
+            let mut crate_ids: Vec<_> = index
+                .find_crates_by_entity(&entity_id, crate_scope(within, no_descendants))?
+                .into_iter()
+                .collect();
+            crate_ids.sort();
+            if crate_ids.is_empty() {
+                println!("No crates found.");
+            }
+            for crate_id in crate_ids {
+                println!("{}", crate_id);
+            }
+        }
+        Commands::Sparql { query } => {
+            println!("{}", index.sparql(&query)?.body);
+        }
+        Commands::Graph { format } => {
+            print!("{}", index.relation_graph(GraphFormat::parse(&format)?));
+        }
+        Commands::Stats {
+            query,
+            size,
+            within,
+            aggs,
+            json,
+        } => {
+            let scope = within.map(CrateScope::new);
+            if let Some(aggs) = aggs {
+                let aggs = Aggregation::parse_map(&serde_json::from_str(&aggs)?)?;
+                let result = index.aggregate(query.as_deref(), &aggs, scope)?;
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                return Ok(());
+            }
+
+            let overview = BTreeMap::from([
+                ("types".to_string(), Aggregation::terms("@type", size)),
+                (
+                    "licenses".to_string(),
+                    Aggregation::terms("license.@id", size),
+                ),
+                ("keywords".to_string(), Aggregation::terms("keywords", size)),
+                (
+                    "authors".to_string(),
+                    Aggregation::terms("author.@id", size)
+                        .with_sub_aggregation("crates", Aggregation::cardinality("crate"))
+                        .with_order("crates", true),
+                ),
+                ("crates".to_string(), Aggregation::cardinality("crate")),
+            ]);
+            let mut result = index.aggregate(query.as_deref(), &overview, scope.clone())?;
+
+            let datasets_query = match &query {
+                Some(q) => format!("entity_type:Dataset AND ({})", q),
+                None => "entity_type:Dataset".to_string(),
+            };
+            let per_year = BTreeMap::from([(
+                "datasets_per_year".to_string(),
+                Aggregation::date_histogram("datePublished", DateInterval::Year),
+            )]);
+            result.aggs.extend(
+                index
+                    .aggregate(Some(&datasets_query), &per_year, scope)?
+                    .aggs,
+            );
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                return Ok(());
+            }
+
+            let crates = match result.aggs.get("crates") {
+                Some(AggregationResult::Value { value: Some(v) }) => *v as u64,
+                _ => 0,
+            };
+            println!("Entities: {} in ~{} crates", result.total, crates);
+            for (name, title) in [
+                ("types", "Types"),
+                ("licenses", "Licenses"),
+                ("keywords", "Keywords"),
+                ("authors", "Authors (by crates)"),
+                ("datasets_per_year", "Datasets per year"),
+            ] {
+                if let Some(AggregationResult::Buckets { buckets, .. }) = result.aggs.get(name) {
+                    print_buckets(title, buckets);
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Build the crate scope of `--within` and `--no-descendants`
+fn crate_scope(within: Option<String>, no_descendants: bool) -> Option<CrateScope> {
+    within.map(|id| CrateScope::new(id).with_descendants(!no_descendants))
+}
+
 fn parse_source(source: &str) -> CrateSource {
     if source.starts_with("http://") || source.starts_with("https://") {
         CrateSource::Url(source.to_string())
@@ -231,6 +588,78 @@ fn parse_source(source: &str) -> CrateSource {
     }
 }
 
+fn print_hit(hit: &SearchHit) {
+    println!("Entity: {}", hit.entity_id);
+    if let Some(entity) = &hit.entity {
+        if let Some(name) = &entity.name {
+            println!("  Name: {}", name);
+        }
+        if !entity.types.is_empty() {
+            println!("  Type: {}", entity.types.join(", "));
+        }
+    }
+    println!("  Crate: {}", hit.crate_id);
+    println!("  Score: {:.4}", hit.score);
+    if let Some(snippet) = &hit.snippet {
+        println!("  Match: {}", snippet);
+    }
+    println!();
+}
+
+fn print_explanation(explanation: &ScoreExplanation) {
+    println!("  Query: {}", explanation.parsed_query);
+    println!(
+        "  Matched fields: {}",
+        explanation.matched_fields.join(", ")
+    );
+    for term in &explanation.terms {
+        println!("    {:>8.4}  {}:{}", term.score, term.field, term.term);
+    }
+    if let Some(details) = &explanation.details {
+        println!("  Score breakdown:");
+        print_explanation_node(details, 2);
+    }
+    println!();
+}
+
+fn print_explanation_node(node: &ExplanationNode, depth: usize) {
+    let context = if node.context.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", node.context.join("; "))
+    };
+    println!(
+        "{}{:.4}  {}{}",
+        "  ".repeat(depth),
+        node.value,
+        node.description,
+        context
+    );
+    for detail in &node.details {
+        print_explanation_node(detail, depth + 1);
+    }
+}
+
+fn print_buckets(title: &str, buckets: &[Bucket]) {
+    println!();
+    println!("{}:", title);
+    if buckets.is_empty() {
+        println!("  (none)");
+    }
+    for bucket in buckets {
+        let key = match &bucket.key {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        // Sub-aggregation value (e.g. crates per author) takes precedence over the entity count
+        let count = match bucket.aggs.get("crates") {
+            Some(AggregationResult::Value { value: Some(v) }) => *v as u64,
+            _ => bucket.doc_count,
+        };
+        println!("  {:>6}  {}", count, key);
+    }
+}
+
 fn print_add_result(result: &AddResult, indent: usize) {
     let prefix = "  ".repeat(indent);
     println!("{}Added: {}", prefix, result.crate_id);
@@ -1,11 +1,14 @@
+use std::collections::BTreeMap;
+use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+
 use axum::{
     Json, Router,
-    extract::{Multipart, Path, Query, State},
-    http::StatusCode,
+    extract::{FromRequest, FromRequestParts, Multipart, Path, Query, Request, State},
+    http::{HeaderMap, StatusCode, header, request::Parts},
     response::{IntoResponse, Redirect},
     routing::{delete, get, post},
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing::info;
@@ -13,7 +16,12 @@ use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitEx
 use utoipa::{OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
-use rocrate_indexer::{CrateIndex, CrateSource, SharedCrateIndex};
+use rocrate_indexer::error::IndexError;
+use rocrate_indexer::{
+    Aggregation, AggregationResult, CrateFormat, CrateIndex, CrateScope, CrateSource, EntityRef,
+    HashingEmbedder, IndexConfig, PartTree, QueryDsl, S3Config, S3Storage, SearchOptions,
+    SharedCrateIndex, SortKey, SuggestField,
+};
 
 // === API Documentation ===
 
@@ -25,8 +33,23 @@ use rocrate_indexer::{CrateIndex, CrateSource, SharedCrateIndex};
         list_crates,
         get_crate,
         get_crate_info,
+        similar_crates,
+        related_crates,
+        crate_entity,
         remove_crate,
         search,
+        search_structured,
+        search_crates,
+        explain,
+        entities_by_id,
+        registered_entity,
+        entities_by_type,
+        similar_entities,
+        crates_by_entity,
+        aggregate,
+        suggest,
+        sparql_get,
+        sparql_post,
     ),
     components(
         schemas(
@@ -34,16 +57,46 @@ use rocrate_indexer::{CrateIndex, CrateSource, SharedCrateIndex};
             AddCrateResponse,
             CrateAddedInfo,
             ListCratesResponse,
+            CrateFormatParams,
             CrateInfoResponse,
+            CrateEntityParams,
+            CrateEntityResponse,
+            EntityRefResponse,
+            PartTreeResponse,
             SearchParams,
+            QuerySearchRequest,
             SearchResponse,
             SearchHitResponse,
+            EntitySummaryResponse,
+            CrateSearchParams,
+            CrateSearchResponse,
+            CrateHitResponse,
+            ExplainParams,
+            ExplainResponse,
+            TermContributionResponse,
+            ExplanationNodeResponse,
+            EntityIdParams,
+            EntityTypeParams,
+            SimilarParams,
+            SimilarEntitiesParams,
+            CrateIdsResponse,
+            RelatedCratesResponse,
+            CrateRelationResponse,
+            RegisteredEntityResponse,
+            EntityOccurrenceResponse,
+            AggregateRequest,
+            AggregateResponse,
+            SuggestParams,
+            SuggestResponse,
+            SuggestionResponse,
+            SparqlParams,
             ErrorResponse,
         )
     ),
     tags(
         (name = "crates", description = "RO-Crate management endpoints"),
-        (name = "search", description = "Search endpoints")
+        (name = "search", description = "Search endpoints"),
+        (name = "stats", description = "Corpus statistics")
     )
 )]
 struct ApiDoc;
@@ -113,6 +166,13 @@ struct ListCratesParams {
     full: bool,
 }
 
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct CrateFormatParams {
+    /// Format overriding the `Accept` header: json (as stored), expanded
+    /// (JSON-LD), turtle, ntriples or nquads
+    format: Option<String>,
+}
+
 #[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
 struct SearchParams {
     /// Tantivy query string
@@ -126,18 +186,404 @@ struct SearchParams {
     /// Maximum number of results (default: 10)
     #[serde(default = "default_limit")]
     limit: usize,
+    /// Number of results to skip (default: 0)
+    #[serde(default)]
+    offset: usize,
+    /// Cursor from a previous response's `next_cursor` to fetch the next page
+    cursor: Option<String>,
+    /// Include highlighted snippets of the matching content (default: false)
+    #[serde(default)]
+    snippets: bool,
+    /// Only search inside this crate ID
+    within: Option<String>,
+    /// With `within`, also search the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+    /// Sort keys instead of relevance, e.g. `datePublished:desc,name`
+    ///
+    /// Fields: score, datePublished, name, indexedAt, entityCount; order asc
+    /// or desc (default: desc for score, asc otherwise).
+    sort: Option<String>,
+    /// Match content terms within this many typos, 0 to 2 (default: 0)
+    #[serde(default)]
+    fuzzy: u8,
+    /// Suggest a spelling correction when few entities match (default: true)
+    #[serde(default = "default_true")]
+    did_you_mean: bool,
+    /// Weight of semantic similarity in the ranking, 0 to 1 (default: 0)
+    ///
+    /// Above 0, keyword and vector hits are fused and paged by `offset`;
+    /// requires the server to run with `ROCRATE_EMBEDDER` set.
+    #[serde(default)]
+    semantic: f32,
 }
 
 fn default_limit() -> usize {
     10
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+struct QuerySearchRequest {
+    /// Structured query, e.g.
+    /// `{"bool": {"must": [{"term": {"field": "author.@id", "value": "https://orcid.org/..."}}]}}`
+    ///
+    /// Clauses: bool (must/should/must_not), term, match, phrase, range
+    /// (gt/gte/lt/lte), exists, prefix, fuzzy, all. Fields are `@id`, `@type`,
+    /// `crate`, `content` or a property path (`"author.name"` or `["author", "name"]`).
+    #[schema(value_type = Object)]
+    query: serde_json::Value,
+    /// Maximum number of results (default: 10)
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Number of results to skip (default: 0)
+    #[serde(default)]
+    offset: usize,
+    /// Cursor from a previous response's `next_cursor` to fetch the next page
+    cursor: Option<String>,
+    /// Include highlighted snippets of the matching content (default: false)
+    #[serde(default)]
+    snippets: bool,
+    /// Only search inside this crate ID
+    within: Option<String>,
+    /// With `within`, also search the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+    /// Sort keys instead of relevance, e.g. `datePublished:desc,name`
+    ///
+    /// Fields: score, datePublished, name, indexedAt, entityCount; order asc
+    /// or desc (default: desc for score, asc otherwise).
+    sort: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct SuggestParams {
+    /// Text typed so far; matches the start of a value or of any later word
+    #[serde(default)]
+    q: String,
+    /// Suggester: name, keyword, author or type (default: all)
+    field: Option<String>,
+    /// Maximum number of suggestions (default: 10)
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Only suggest values from this crate ID
+    within: Option<String>,
+    /// With `within`, also include the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct SuggestResponse {
+    /// Suggestions, most frequent first
+    suggestions: Vec<SuggestionResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct SuggestionResponse {
+    /// Completed value
+    text: String,
+    /// Suggester the value comes from
+    field: String,
+    /// Number of entities with this value
+    count: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct AggregateRequest {
+    /// Tantivy query string selecting the entities (default: all entities)
+    q: Option<String>,
+    /// Structured query selecting the entities, as accepted by `POST /search`
+    #[schema(value_type = Option<Object>)]
+    query: Option<serde_json::Value>,
+    /// Named aggregations, e.g.
+    /// `{"types": {"terms": {"field": "@type", "size": 10}},
+    ///   "per_year": {"date_histogram": {"field": "datePublished", "interval": "year"}}}`
+    ///
+    /// Kinds: terms (size, order, aggs), histogram (interval, aggs),
+    /// date_histogram (interval: day, week, month or year; aggs) and
+    /// cardinality. Fields are `@id`, `@type`, `entity_type` (with
+    /// supertypes), `crate`, `datePublished`, `indexedAt`, `entityCount` or a
+    /// property path such as `license.@id`.
+    #[schema(value_type = Object)]
+    aggs: serde_json::Value,
+    /// Only aggregate inside this crate ID
+    within: Option<String>,
+    /// With `within`, also include the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct AggregateResponse {
+    /// Number of entities matching the query
+    total: usize,
+    /// Results by aggregation name: `{"buckets": [{"key", "doc_count", "aggs"?}], "other_count"?}`
+    /// for bucket aggregations, `{"value"}` for cardinality
+    #[schema(value_type = Object)]
+    aggs: BTreeMap<String, AggregationResult>,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct CrateSearchParams {
+    /// Tantivy query string, matched against entities
+    q: String,
+    /// Maximum number of crates (default: 10)
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Number of crates to skip (default: 0)
+    #[serde(default)]
+    offset: usize,
+    /// Cursor from a previous response's `next_cursor` to fetch the next page
+    cursor: Option<String>,
+    /// Matching entities returned per crate (default: 3)
+    #[serde(default = "default_entities_per_crate")]
+    entities: usize,
+    /// Include highlighted snippets of the matching content (default: false)
+    #[serde(default)]
+    snippets: bool,
+    /// Only search inside this crate ID
+    within: Option<String>,
+    /// With `within`, also search the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+    /// Match content terms within this many typos, 0 to 2 (default: 0)
+    #[serde(default)]
+    fuzzy: u8,
+    /// Suggest a spelling correction when few crates match (default: true)
+    #[serde(default = "default_true")]
+    did_you_mean: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct ExplainParams {
+    /// Tantivy query string, as passed to `/search`
+    q: String,
+    /// Exact @id of the entity to explain
+    id: String,
+    /// Crate ID of the occurrence to explain
+    #[serde(rename = "crate")]
+    crate_id: String,
+    /// Only search inside this crate ID
+    within: Option<String>,
+    /// With `within`, also search the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+    /// Match content terms within this many typos, 0 to 2 (default: 0)
+    #[serde(default)]
+    fuzzy: u8,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_entities_per_crate() -> usize {
+    3
+}
+
+fn spellcheck_below(did_you_mean: bool) -> usize {
+    if did_you_mean {
+        SearchOptions::SPELLCHECK_BELOW
+    } else {
+        0
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct EntityIdParams {
+    /// Exact entity @id, e.g. "https://orcid.org/0000-0002-1825-0097"
+    id: String,
+    /// Only search inside this crate ID
+    within: Option<String>,
+    /// With `within`, also search the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct SimilarParams {
+    /// Maximum number of crates (default: 10)
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct SimilarEntitiesParams {
+    /// Exact @id of the entity to find similar entities for
+    id: String,
+    /// Maximum number of entities (default: 10)
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct EntityTypeParams {
+    /// Optional Tantivy query the entities must also match
+    q: Option<String>,
+    /// Maximum number of results (default: 10)
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Match the literal @type only, not its schema.org subtypes (default: false)
+    #[serde(default)]
+    exact: bool,
+    /// Only search inside this crate ID
+    within: Option<String>,
+    /// With `within`, also search the crate's subcrates (default: true)
+    #[serde(default = "default_true")]
+    descendants: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct CrateEntityParams {
+    /// Levels of referenced entities to inline into the entity, 0 to 5 (default: 0)
+    #[serde(default)]
+    expand: usize,
+    /// Levels of the hasPart tree below the entity to return, 0 to 5 (default: 0, no tree)
+    #[serde(default)]
+    parts: usize,
+}
+
+/// An entity of a crate with its references both ways
+#[derive(Debug, Serialize, ToSchema)]
+struct CrateEntityResponse {
+    /// The entity, with references inlined up to the `expand` depth
+    #[schema(value_type = Object)]
+    entity: serde_json::Value,
+    /// References from the entity to others in the crate or outside it
+    outgoing: Vec<EntityRefResponse>,
+    /// References to the entity from others in the crate (backlinks)
+    incoming: Vec<EntityRefResponse>,
+    /// hasPart tree below the entity, when `parts` is above 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parts: Option<PartTreeResponse>,
+}
+
+/// A reference between two entities of a crate
+#[derive(Debug, Serialize, ToSchema)]
+struct EntityRefResponse {
+    /// Property holding the reference, e.g. author
+    property: String,
+    /// @id of the entity at the other end
+    entity_id: String,
+    /// Name of that entity, if the crate describes it
+    name: Option<String>,
+}
+
+/// An entity with its hasPart children
+#[derive(Debug, Serialize, ToSchema)]
+struct PartTreeResponse {
+    entity_id: String,
+    name: Option<String>,
+    #[schema(no_recursion)]
+    parts: Vec<PartTreeResponse>,
+}
+
+/// Everything known about an entity across the indexed crates
+#[derive(Debug, Serialize, ToSchema)]
+struct RegisteredEntityResponse {
+    /// The entity's absolute @id
+    entity_id: String,
+    /// Names it is given in any crate
+    names: Vec<String>,
+    /// @type values it has in any crate
+    types: Vec<String>,
+    /// Number of crates it occurs in
+    crate_count: usize,
+    /// Crates it occurs in, sorted by crate ID
+    crates: Vec<EntityOccurrenceResponse>,
+}
+
+/// Occurrence of an entity in one crate
+#[derive(Debug, Serialize, ToSchema)]
+struct EntityOccurrenceResponse {
+    crate_id: String,
+    /// Name of the crate
+    crate_name: Option<String>,
+    /// Properties referencing the entity in this crate, e.g. author or funder
+    roles: Vec<String>,
+    /// Names the crate gives the entity
+    names: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CrateIdsResponse {
+    /// Matching crate IDs, sorted
+    crate_ids: Vec<String>,
+    /// Number of crate IDs
+    count: usize,
+}
+
+/// Links between a crate and other indexed crates
+#[derive(Debug, Serialize, ToSchema)]
+struct RelatedCratesResponse {
+    crate_id: String,
+    /// Links both ways, sorted by source and target crate
+    relations: Vec<CrateRelationResponse>,
+}
+
+/// A typed link between two crates
+#[derive(Debug, Serialize, ToSchema)]
+struct CrateRelationResponse {
+    /// isBasedOn, citation, isPartOf, mentions, hasPart or sharedDoi
+    kind: String,
+    /// Crate holding the link
+    source: String,
+    /// Crate the link resolves to
+    target: String,
+    /// IRI or DOI the link goes through
+    via: String,
+    /// Name of the crate at the other end
+    related_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CrateSearchResponse {
+    /// Matching crates, best first
+    hits: Vec<CrateHitResponse>,
+    /// Number of crates returned
+    count: usize,
+    /// Total number of crates matching the query
+    total: usize,
+    /// Cursor to pass as `cursor` to fetch the next page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+    /// Corrected query that matches more results, when few matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    did_you_mean: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CrateHitResponse {
+    /// The crate ID
+    crate_id: String,
+    /// Crate name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Crate description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Aggregated relevance score
+    score: f32,
+    /// Number of matching entities in the crate
+    matches: usize,
+    /// Best matching entities
+    entities: Vec<SearchHitResponse>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 struct SearchResponse {
     /// Search results
     hits: Vec<SearchHitResponse>,
     /// Total number of hits returned
     count: usize,
+    /// Total number of entities matching the query
+    total: usize,
+    /// Cursor to pass as `cursor` to fetch the next page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+    /// Corrected query that matches more results, when few matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    did_you_mean: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -148,16 +594,272 @@ struct SearchHitResponse {
     crate_id: String,
     /// Relevance score
     score: f32,
+    /// Name, types and description of the entity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity: Option<EntitySummaryResponse>,
+    /// Matching content with terms wrapped in `<b>` tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ExplainResponse {
+    /// The @id of the explained entity
+    entity_id: String,
+    /// The crate ID of the explained occurrence
+    crate_id: String,
+    /// The parsed query tree
+    parsed_query: String,
+    /// Whether the entity matches the query
+    matched: bool,
+    /// Relevance score, 0 if the entity does not match
+    score: f32,
+    /// Fields with at least one matching query term
+    matched_fields: Vec<String>,
+    /// Matching query terms, each scored on its own, highest first
+    terms: Vec<TermContributionResponse>,
+    /// Tantivy's score explanation tree, absent if the entity does not match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<ExplanationNodeResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct TermContributionResponse {
+    /// Field, with the JSON path for properties, e.g. `properties.name`
+    field: String,
+    /// Indexed term
+    term: String,
+    /// Score of the term alone
+    score: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ExplanationNodeResponse {
+    /// What this part of the score is
+    description: String,
+    /// Value of this part of the score
+    value: f32,
+    /// Parts this value is computed from
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(no_recursion)]
+    details: Vec<ExplanationNodeResponse>,
+    /// Extra information, e.g. the term a node scores
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    context: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct EntitySummaryResponse {
+    /// Entity name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// @type values
+    types: Vec<String>,
+    /// Short description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
 }
 
+/// Body of every error response
 #[derive(Debug, Serialize, ToSchema)]
 struct ErrorResponse {
-    /// Error message
+    /// Machine-readable error code, e.g. `query_parse`, `invalid_query`,
+    /// `invalid_cursor`, `crate_not_found` or `internal_error`
+    code: String,
+    /// Human-readable error message
     error: String,
+    /// For `query_parse`: byte offset in the query string where parsing failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+    /// For `invalid_query`: path of the offending clause or parameter, e.g. `$.must[1]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+}
+
+// === Errors ===
+
+/// Error returned by a handler, rendered as an `ErrorResponse`
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    body: ErrorResponse,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &str, error: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: ErrorResponse {
+                code: code.to_string(),
+                error: error.into(),
+                position: None,
+                path: None,
+            },
+        }
+    }
+
+    fn bad_request(code: &str, error: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, error)
+    }
+
+    fn internal(error: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            error.to_string(),
+        )
+    }
+}
+
+/// Invalid queries and cursors are client errors, unknown crates and entities
+/// are not found, crates that fail to load are unprocessable, anything else is
+/// a server error
+impl From<IndexError> for ApiError {
+    fn from(error: IndexError) -> Self {
+        let status = match &error {
+            IndexError::QueryParse { .. }
+            | IndexError::InvalidQuery { .. }
+            | IndexError::InvalidCursor(_)
+            | IndexError::InvalidPath(_) => StatusCode::BAD_REQUEST,
+            IndexError::CrateNotFound(_) | IndexError::EntityNotFound(_) => StatusCode::NOT_FOUND,
+            IndexError::LoadError { .. } | IndexError::InvalidCrateFormat(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let mut api_error = ApiError::new(status, error.code(), error.to_string());
+        match error {
+            IndexError::QueryParse { position, .. } => api_error.body.position = position,
+            IndexError::InvalidQuery { path, .. } => api_error.body.path = Some(path),
+            _ => {}
+        }
+        api_error
+    }
+}
+
+impl From<tokio::task::JoinError> for ApiError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        ApiError::internal(format!("Task join error: {}", error))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+/// `Query` extractor rejecting malformed parameters with an `ErrorResponse`
+struct ApiQuery<T>(T);
+
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| ApiError::new(e.status(), "invalid_parameters", e.body_text()))?;
+        Ok(ApiQuery(value))
+    }
+}
+
+/// `Json` extractor rejecting malformed bodies with an `ErrorResponse`
+struct ApiJson<T>(T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|e| ApiError::new(e.status(), "invalid_body", e.body_text()))?;
+        Ok(ApiJson(value))
+    }
 }
 
 // === Helper Functions ===
 
+/// Run blocking index work on the blocking thread pool
+async fn blocking<T, F>(work: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work).await?
+}
+
+fn read_index(index: &SharedCrateIndex) -> Result<RwLockReadGuard<'_, CrateIndex>, ApiError> {
+    index
+        .read()
+        .map_err(|e| ApiError::internal(format!("Lock error: {}", e)))
+}
+
+fn write_index(index: &SharedCrateIndex) -> Result<RwLockWriteGuard<'_, CrateIndex>, ApiError> {
+    index
+        .write()
+        .map_err(|e| ApiError::internal(format!("Lock error: {}", e)))
+}
+
+fn crate_scope(within: Option<String>, descendants: bool) -> Option<CrateScope> {
+    within.map(|crate_id| CrateScope::new(crate_id).with_descendants(descendants))
+}
+
+fn convert_hits(hits: Vec<rocrate_indexer::SearchHit>) -> SearchResponse {
+    SearchResponse {
+        count: hits.len(),
+        total: hits.len(),
+        next_cursor: None,
+        did_you_mean: None,
+        hits: hits.into_iter().map(convert_search_hit).collect(),
+    }
+}
+
+fn convert_explanation_node(node: rocrate_indexer::ExplanationNode) -> ExplanationNodeResponse {
+    ExplanationNodeResponse {
+        description: node.description,
+        value: node.value,
+        details: node
+            .details
+            .into_iter()
+            .map(convert_explanation_node)
+            .collect(),
+        context: node.context,
+    }
+}
+
+fn convert_crate_hit(hit: rocrate_indexer::CrateHit) -> CrateHitResponse {
+    CrateHitResponse {
+        crate_id: hit.crate_id,
+        name: hit.name,
+        description: hit.description,
+        score: hit.score,
+        matches: hit.matches,
+        entities: hit.entities.into_iter().map(convert_search_hit).collect(),
+    }
+}
+
+fn convert_search_hit(hit: rocrate_indexer::SearchHit) -> SearchHitResponse {
+    SearchHitResponse {
+        entity_id: hit.entity_id,
+        crate_id: hit.crate_id,
+        score: hit.score,
+        entity: hit.entity.map(|e| EntitySummaryResponse {
+            name: e.name,
+            types: e.types,
+            description: e.description,
+        }),
+        snippet: hit.snippet,
+    }
+}
+
 /// Flatten the recursive AddResult into a flat response
 fn convert_add_result(result: rocrate_indexer::AddResult) -> AddCrateResponse {
     let mut subcrates = Vec::new();
@@ -200,65 +902,28 @@ fn collect_subcrates(results: &[rocrate_indexer::AddResult], out: &mut Vec<Crate
         (status = 201, description = "Crate added successfully", body = AddCrateResponse),
         (status = 400, description = "Invalid request", body = ErrorResponse),
         (status = 409, description = "Crate already indexed", body = ErrorResponse),
+        (status = 422, description = "Crate could not be loaded", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse),
     )
 )]
 async fn add_crate_by_url(
     State(index): State<SharedCrateIndex>,
-    Json(req): Json<AddCrateByUrlRequest>,
-) -> impl IntoResponse {
+    ApiJson(req): ApiJson<AddCrateByUrlRequest>,
+) -> Result<(StatusCode, Json<AddCrateResponse>), ApiError> {
     let source = CrateSource::Url(req.url.clone());
     let crate_id = source.to_crate_id();
 
     // Check if already indexed
-    {
-        let idx = match index.read() {
-            Ok(idx) => idx,
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        error: format!("Lock error: {}", e),
-                    }),
-                )
-                    .into_response();
-            }
-        };
-        if idx.is_indexed(&crate_id) {
-            return (
-                StatusCode::CONFLICT,
-                Json(ErrorResponse {
-                    error: format!("Crate already indexed: {}", crate_id),
-                }),
-            )
-                .into_response();
-        }
+    if read_index(&index)?.is_indexed(&crate_id) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "crate_already_indexed",
+            format!("Crate already indexed: {}", crate_id),
+        ));
     }
 
-    let result = tokio::task::spawn_blocking(move || {
-        let mut idx = index.write().map_err(|e| format!("Lock error: {}", e))?;
-        idx.add_from_source(&source)
-            .map_err(|e| format!("Failed to add crate: {}", e))
-    })
-    .await;
-
-    match result {
-        Ok(Ok(add_result)) => {
-            (StatusCode::CREATED, Json(convert_add_result(add_result))).into_response()
-        }
-        Ok(Err(e)) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
-    }
+    let add_result = blocking(move || Ok(write_index(&index)?.add_from_source(&source)?)).await?;
+    Ok((StatusCode::CREATED, Json(convert_add_result(add_result))))
 }
 
 /// Add an RO-Crate by uploading a file (zip archive or ro-crate-metadata.json)
@@ -277,20 +942,16 @@ async fn add_crate_by_url(
     responses(
         (status = 201, description = "Crate added successfully", body = AddCrateResponse),
         (status = 400, description = "Invalid request or file", body = ErrorResponse),
+        (status = 422, description = "Crate could not be loaded", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse),
     )
 )]
 async fn add_crate_by_upload(
     State(index): State<SharedCrateIndex>,
     mut multipart: Multipart,
-) -> impl IntoResponse {
+) -> Result<(StatusCode, Json<AddCrateResponse>), ApiError> {
     // Extract file from multipart
-    let (filename, data) = match extract_file_from_multipart(&mut multipart).await {
-        Ok(result) => result,
-        Err(e) => {
-            return (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response();
-        }
-    };
+    let (filename, data) = extract_file_from_multipart(&mut multipart).await?;
 
     // Determine file type and process
     let is_zip = filename.ends_with(".zip")
@@ -300,50 +961,33 @@ async fn add_crate_by_upload(
     // Extract a clean name hint from the original filename
     let name_hint = extract_name_hint(&filename);
 
-    let result = tokio::task::spawn_blocking(move || {
-        let mut idx = index.write().map_err(|e| format!("Lock error: {}", e))?;
+    let add_result = blocking(move || {
+        let mut idx = write_index(&index)?;
 
         if is_zip {
             // Write to temp file and load
             let temp_path =
                 std::env::temp_dir().join(format!("rocrate_{}.zip", uuid::Uuid::new_v4()));
             std::fs::write(&temp_path, &data)
-                .map_err(|e| format!("Failed to write temp file: {}", e))?;
+                .map_err(|e| ApiError::internal(format!("Failed to write temp file: {}", e)))?;
 
             let result = idx.add_from_zip_with_name(&temp_path, name_hint.as_deref());
 
             // Clean up temp file
             let _ = std::fs::remove_file(&temp_path);
 
-            result.map_err(|e| format!("Failed to add crate: {}", e))
+            Ok(result?)
         } else {
             // Assume JSON metadata
-            let json_str =
-                String::from_utf8(data).map_err(|e| format!("Invalid UTF-8 in file: {}", e))?;
+            let json_str = String::from_utf8(data).map_err(|e| {
+                ApiError::bad_request("invalid_upload", format!("Invalid UTF-8 in file: {}", e))
+            })?;
 
-            idx.add_from_json(&json_str, name_hint.as_deref())
-                .map_err(|e| format!("Failed to add crate: {}", e))
+            Ok(idx.add_from_json(&json_str, name_hint.as_deref())?)
         }
     })
-    .await;
-
-    match result {
-        Ok(Ok(add_result)) => {
-            (StatusCode::CREATED, Json(convert_add_result(add_result))).into_response()
-        }
-        Ok(Err(e)) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
-    }
+    .await?;
+    Ok((StatusCode::CREATED, Json(convert_add_result(add_result))))
 }
 
 /// Extract a clean name hint from a filename
@@ -377,11 +1021,12 @@ fn extract_name_hint(filename: &str) -> Option<String> {
 /// Extract filename and data from multipart upload
 async fn extract_file_from_multipart(
     multipart: &mut Multipart,
-) -> Result<(String, Vec<u8>), String> {
+) -> Result<(String, Vec<u8>), ApiError> {
+    let invalid = |error: String| ApiError::bad_request("invalid_upload", error);
     while let Some(field) = multipart
         .next_field()
         .await
-        .map_err(|e| format!("Failed to read multipart field: {}", e))?
+        .map_err(|e| invalid(format!("Failed to read multipart field: {}", e)))?
     {
         let name = field.name().unwrap_or("").to_string();
         if name == "file" {
@@ -390,13 +1035,15 @@ async fn extract_file_from_multipart(
             let data = field
                 .bytes()
                 .await
-                .map_err(|e| format!("Failed to read file data: {}", e))?;
+                .map_err(|e| invalid(format!("Failed to read file data: {}", e)))?;
 
             return Ok((filename, data.to_vec()));
         }
     }
 
-    Err("No file field found in multipart request".to_string())
+    Err(invalid(
+        "No file field found in multipart request".to_string(),
+    ))
 }
 
 /// List all indexed crate IDs
@@ -412,10 +1059,10 @@ async fn extract_file_from_multipart(
 )]
 async fn list_crates(
     State(index): State<SharedCrateIndex>,
-    Query(params): Query<ListCratesParams>,
-) -> impl IntoResponse {
-    let result = tokio::task::spawn_blocking(move || {
-        let idx = index.read().map_err(|e| format!("Lock error: {}", e))?;
+    ApiQuery(params): ApiQuery<ListCratesParams>,
+) -> Result<Json<ListCratesResponse>, ApiError> {
+    let response = blocking(move || {
+        let idx = read_index(&index)?;
         if params.full {
             let entries: Vec<CrateInfoResponse> = idx
                 .list_crate_entries()
@@ -430,7 +1077,7 @@ async fn list_crates(
                 })
                 .collect();
             let count = entries.len();
-            Ok::<_, String>(ListCratesResponse {
+            Ok(ListCratesResponse {
                 crates: None,
                 entries: Some(entries),
                 count,
@@ -445,75 +1092,76 @@ async fn list_crates(
             })
         }
     })
-    .await;
-
-    match result {
-        Ok(Ok(response)) => (StatusCode::OK, Json(response)).into_response(),
-        Ok(Err(e)) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
-    }
+    .await?;
+    Ok(Json(response))
 }
 
-/// Get full metadata JSON for a crate
+/// Get full metadata for a crate, as stored or converted to RDF
+///
+/// The format is negotiated with the `Accept` header: `application/json` or
+/// `application/ld+json` (as stored), `application/ld+json` with the
+/// expanded profile, `text/turtle`, `application/n-triples` or
+/// `application/n-quads`. The `format` parameter overrides it.
 #[utoipa::path(
     get,
     path = "/crates/{crate_id}",
     tag = "crates",
     params(
-        ("crate_id" = String, Path, description = "The crate ID (URL-encoded if necessary)")
+        ("crate_id" = String, Path, description = "The crate ID (URL-encoded if necessary)"),
+        CrateFormatParams
     ),
     responses(
-        (status = 200, description = "Crate metadata JSON", content_type = "application/json"),
+        (status = 200, description = "Crate metadata", content(("application/json"), ("application/ld+json"), ("text/turtle"), ("application/n-triples"), ("application/n-quads"))),
+        (status = 400, description = "Unknown format", body = ErrorResponse),
         (status = 404, description = "Crate not found", body = ErrorResponse),
+        (status = 406, description = "No acceptable format", body = ErrorResponse),
+        (status = 422, description = "Crate metadata can't be converted to RDF", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse),
     )
 )]
 async fn get_crate(
     State(index): State<SharedCrateIndex>,
     Path(crate_id): Path<String>,
-) -> impl IntoResponse {
-    let result = tokio::task::spawn_blocking(move || {
-        let idx = index.read().map_err(|e| format!("Lock error: {}", e))?;
-        idx.get_crate_json(&crate_id)
-            .map_err(|e| format!("Failed to get crate: {}", e))
+    headers: HeaderMap,
+    ApiQuery(params): ApiQuery<CrateFormatParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    let format = match (&params.format, accept) {
+        (Some(name), _) => CrateFormat::parse(name)?,
+        (None, Some(accept)) => CrateFormat::from_accept(accept).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_ACCEPTABLE,
+                "not_acceptable",
+                format!("No supported format in Accept: {}", accept),
+            )
+        })?,
+        (None, None) => CrateFormat::JsonLd,
+    };
+    // Stored metadata stays plain JSON unless JSON-LD was asked for
+    let content_type = match format {
+        CrateFormat::JsonLd if !accept.is_some_and(|a| a.contains("application/ld+json")) => {
+            "application/json"
+        }
+        _ => format.media_type(),
+    };
+
+    let metadata = blocking(move || {
+        read_index(&index)?
+            .get_crate_as(&crate_id, format)?
+            .ok_or_else(|| IndexError::CrateNotFound(crate_id).into())
     })
-    .await;
-
-    match result {
-        Ok(Ok(Some(json))) => {
-            (StatusCode::OK, [("content-type", "application/json")], json).into_response()
-        }
-        Ok(Ok(None)) => (
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Crate not found".to_string(),
-            }),
-        )
-            .into_response(),
-        Ok(Err(e)) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
-    }
-}
+    .await?;
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::VARY, "Accept"),
+        ],
+        metadata,
+    ))
+}
 
 /// Get short info (name, description, ancestry path) for a crate
 #[utoipa::path(
@@ -532,46 +1180,105 @@ async fn get_crate(
 async fn get_crate_info(
     State(index): State<SharedCrateIndex>,
     Path(crate_id): Path<String>,
-) -> impl IntoResponse {
-    let result = tokio::task::spawn_blocking(move || {
-        let idx = index.read().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<Json<CrateInfoResponse>, ApiError> {
+    let info = blocking(move || {
+        let idx = read_index(&index)?;
 
         match idx.get_crate_info(&crate_id) {
-            Some(entry) => Ok(Some(CrateInfoResponse {
+            Some(entry) => Ok(CrateInfoResponse {
                 crate_id: entry.crate_id.clone(),
                 full_path: entry.full_path.clone(),
                 name: entry.name.clone(),
                 description: entry.description.clone(),
                 is_root: entry.is_root(),
                 parent_id: entry.parent_id().map(String::from),
-            })),
-            None => Ok(None),
+            }),
+            None => Err(IndexError::CrateNotFound(crate_id).into()),
         }
     })
-    .await;
-
-    match result {
-        Ok(Ok(Some(info))) => (StatusCode::OK, Json(info)).into_response(),
-        Ok(Ok(None)) => (
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Crate not found".to_string(),
-            }),
-        )
-            .into_response(),
-        Ok(Err(e)) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
-    }
+    .await?;
+    Ok(Json(info))
+}
+
+/// Find crates with content similar to a crate, outside its own subtree
+#[utoipa::path(
+    get,
+    path = "/crates/{crate_id}/similar",
+    tag = "search",
+    params(
+        ("crate_id" = String, Path, description = "The crate ID (URL-encoded if necessary)"),
+        SimilarParams
+    ),
+    responses(
+        (status = 200, description = "Similar crates, most similar first", body = CrateSearchResponse),
+        (status = 404, description = "Crate not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn similar_crates(
+    State(index): State<SharedCrateIndex>,
+    Path(crate_id): Path<String>,
+    ApiQuery(params): ApiQuery<SimilarParams>,
+) -> Result<Json<CrateSearchResponse>, ApiError> {
+    let hits =
+        blocking(move || Ok(read_index(&index)?.similar_crates(&crate_id, params.limit)?)).await?;
+    Ok(Json(CrateSearchResponse {
+        count: hits.len(),
+        total: hits.len(),
+        next_cursor: None,
+        did_you_mean: None,
+        hits: hits.into_iter().map(convert_crate_hit).collect(),
+    }))
+}
+
+/// Crates linked to a crate by isBasedOn, citation, isPartOf, mentions,
+/// hasPart or a shared DOI, both ways
+#[utoipa::path(
+    get,
+    path = "/crates/{crate_id}/related",
+    tag = "crates",
+    params(
+        ("crate_id" = String, Path, description = "The crate ID (URL-encoded if necessary)")
+    ),
+    responses(
+        (status = 200, description = "Links to and from other crates", body = RelatedCratesResponse),
+        (status = 404, description = "Crate not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn related_crates(
+    State(index): State<SharedCrateIndex>,
+    Path(crate_id): Path<String>,
+) -> Result<Json<RelatedCratesResponse>, ApiError> {
+    let response = blocking(move || {
+        let idx = read_index(&index)?;
+        let relations = idx
+            .related_crates(&crate_id)?
+            .into_iter()
+            .map(|relation| {
+                let other = if relation.source == crate_id {
+                    &relation.target
+                } else {
+                    &relation.source
+                };
+                CrateRelationResponse {
+                    related_name: idx
+                        .get_crate_info(other)
+                        .and_then(|entry| entry.name.clone()),
+                    kind: relation.kind.as_str().to_string(),
+                    source: relation.source,
+                    target: relation.target,
+                    via: relation.via,
+                }
+            })
+            .collect();
+        Ok(RelatedCratesResponse {
+            crate_id,
+            relations,
+        })
+    })
+    .await?;
+    Ok(Json(response))
 }
 
 /// Remove a crate from the index
@@ -591,37 +1298,18 @@ async fn get_crate_info(
 async fn remove_crate(
     State(index): State<SharedCrateIndex>,
     Path(crate_id): Path<String>,
-) -> impl IntoResponse {
-    let result = tokio::task::spawn_blocking(move || {
-        let mut idx = index.write().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<StatusCode, ApiError> {
+    blocking(move || {
+        let mut idx = write_index(&index)?;
 
         if !idx.list_crates().contains(&crate_id) {
-            return Err("Crate not found".to_string());
+            return Err(IndexError::CrateNotFound(crate_id).into());
         }
 
-        idx.remove(&crate_id)
-            .map_err(|e| format!("Failed to remove crate: {}", e))
+        Ok(idx.remove(&crate_id)?)
     })
-    .await;
-
-    match result {
-        Ok(Ok(())) => StatusCode::NO_CONTENT.into_response(),
-        Ok(Err(e)) if e == "Crate not found" => {
-            (StatusCode::NOT_FOUND, Json(ErrorResponse { error: e })).into_response()
-        }
-        Ok(Err(e)) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
-    }
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// Search for entities matching a query
@@ -634,54 +1322,534 @@ async fn remove_crate(
     ),
     responses(
         (status = 200, description = "Search results", body = SearchResponse),
-        (status = 400, description = "Invalid query", body = ErrorResponse),
+        (status = 400, description = "Invalid query, with the error position for syntax errors", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse),
     )
 )]
 async fn search(
     State(index): State<SharedCrateIndex>,
-    Query(params): Query<SearchParams>,
-) -> impl IntoResponse {
-    let result = tokio::task::spawn_blocking(move || {
-        let idx = index.read().map_err(|e| format!("Lock error: {}", e))?;
-        idx.search(&params.q, params.limit)
-            .map_err(|e| format!("Search failed: {}", e))
+    ApiQuery(params): ApiQuery<SearchParams>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let page = blocking(move || {
+        let sort = SortKey::parse_list(params.sort.as_deref().unwrap_or_default())?;
+        let idx = read_index(&index)?;
+        let options = SearchOptions::default()
+            .with_limit(params.limit)
+            .with_offset(params.offset)
+            .with_cursor(params.cursor)
+            .with_snippets(params.snippets)
+            .with_scope(crate_scope(params.within, params.descendants))
+            .with_sort(sort)
+            .with_fuzziness(params.fuzzy)
+            .with_spellcheck_below(spellcheck_below(params.did_you_mean))
+            .with_semantic_weight(params.semantic);
+        Ok(idx.search_page(&params.q, &options)?)
     })
-    .await;
-
-    match result {
-        Ok(Ok(hits)) => {
-            let response = SearchResponse {
-                count: hits.len(),
-                hits: hits
-                    .into_iter()
-                    .map(|h| SearchHitResponse {
-                        entity_id: h.entity_id,
-                        crate_id: h.crate_id,
-                        score: h.score,
-                    })
-                    .collect(),
-            };
-            (StatusCode::OK, Json(response)).into_response()
-        }
-        Ok(Err(e)) => {
-            if e.contains("parse") || e.contains("Parse") {
-                (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response()
-            } else {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse { error: e }),
-                )
-                    .into_response()
+    .await?;
+    Ok(Json(SearchResponse {
+        count: page.hits.len(),
+        total: page.total,
+        next_cursor: page.next_cursor,
+        did_you_mean: page.did_you_mean,
+        hits: page.hits.into_iter().map(convert_search_hit).collect(),
+    }))
+}
+
+/// Search for entities with a structured JSON query
+#[utoipa::path(
+    post,
+    path = "/search",
+    tag = "search",
+    request_body = QuerySearchRequest,
+    responses(
+        (status = 200, description = "Search results", body = SearchResponse),
+        (status = 400, description = "Invalid query, with the path of the offending clause", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn search_structured(
+    State(index): State<SharedCrateIndex>,
+    ApiJson(request): ApiJson<QuerySearchRequest>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let page = blocking(move || {
+        let query = QueryDsl::from_json(&request.query)?;
+        let sort = SortKey::parse_list(request.sort.as_deref().unwrap_or_default())?;
+        let idx = read_index(&index)?;
+        let options = SearchOptions::default()
+            .with_limit(request.limit)
+            .with_offset(request.offset)
+            .with_cursor(request.cursor)
+            .with_snippets(request.snippets)
+            .with_scope(crate_scope(request.within, request.descendants))
+            .with_sort(sort);
+        Ok(idx.search_dsl(&query, &options)?)
+    })
+    .await?;
+    Ok(Json(SearchResponse {
+        count: page.hits.len(),
+        total: page.total,
+        next_cursor: page.next_cursor,
+        did_you_mean: page.did_you_mean,
+        hits: page.hits.into_iter().map(convert_search_hit).collect(),
+    }))
+}
+
+/// Search for crates, ranked by their matching entities
+#[utoipa::path(
+    get,
+    path = "/search/crates",
+    tag = "search",
+    params(
+        CrateSearchParams
+    ),
+    responses(
+        (status = 200, description = "Matching crates", body = CrateSearchResponse),
+        (status = 400, description = "Invalid query, with the error position for syntax errors", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn search_crates(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<CrateSearchParams>,
+) -> Result<Json<CrateSearchResponse>, ApiError> {
+    let page = blocking(move || {
+        let idx = read_index(&index)?;
+        let options = SearchOptions::default()
+            .with_limit(params.limit)
+            .with_offset(params.offset)
+            .with_cursor(params.cursor)
+            .with_entities_per_crate(params.entities)
+            .with_snippets(params.snippets)
+            .with_scope(crate_scope(params.within, params.descendants))
+            .with_fuzziness(params.fuzzy)
+            .with_spellcheck_below(spellcheck_below(params.did_you_mean));
+        Ok(idx.search_crates(&params.q, &options)?)
+    })
+    .await?;
+    Ok(Json(CrateSearchResponse {
+        count: page.hits.len(),
+        total: page.total,
+        next_cursor: page.next_cursor,
+        did_you_mean: page.did_you_mean,
+        hits: page.hits.into_iter().map(convert_crate_hit).collect(),
+    }))
+}
+
+/// Explain the score of one entity occurrence for a query
+#[utoipa::path(
+    get,
+    path = "/search/explain",
+    tag = "search",
+    params(
+        ExplainParams
+    ),
+    responses(
+        (status = 200, description = "Score explanation", body = ExplainResponse),
+        (status = 400, description = "Invalid query, with the error position for syntax errors", body = ErrorResponse),
+        (status = 404, description = "Entity not found in the crate", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn explain(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<ExplainParams>,
+) -> Result<Json<ExplainResponse>, ApiError> {
+    let explanation = blocking(move || {
+        let idx = read_index(&index)?;
+        let options = SearchOptions::default()
+            .with_scope(crate_scope(params.within, params.descendants))
+            .with_fuzziness(params.fuzzy);
+        Ok(idx.explain(&params.q, &params.id, &params.crate_id, &options)?)
+    })
+    .await?;
+    Ok(Json(ExplainResponse {
+        entity_id: explanation.entity_id,
+        crate_id: explanation.crate_id,
+        parsed_query: explanation.parsed_query,
+        matched: explanation.matched,
+        score: explanation.score,
+        matched_fields: explanation.matched_fields,
+        terms: explanation
+            .terms
+            .into_iter()
+            .map(|t| TermContributionResponse {
+                field: t.field,
+                term: t.term,
+                score: t.score,
+            })
+            .collect(),
+        details: explanation.details.map(convert_explanation_node),
+    }))
+}
+
+/// Find every occurrence of an entity by its exact @id
+#[utoipa::path(
+    get,
+    path = "/entities/by-id",
+    tag = "search",
+    params(
+        EntityIdParams
+    ),
+    responses(
+        (status = 200, description = "Occurrences of the entity", body = SearchResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn entities_by_id(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<EntityIdParams>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let scope = crate_scope(params.within, params.descendants);
+    let hits = blocking(move || Ok(read_index(&index)?.search_by_id(&params.id, scope)?)).await?;
+    Ok(Json(convert_hits(hits)))
+}
+
+/// An entity of a crate with its outgoing references, backlinks and hasPart tree
+#[utoipa::path(
+    get,
+    path = "/crates/{crate_id}/entities/{entity_id}",
+    tag = "crates",
+    params(
+        ("crate_id" = String, Path, description = "Crate ID"),
+        ("entity_id" = String, Path, description = "@id of the entity in the crate, URL-encoded"),
+        CrateEntityParams
+    ),
+    responses(
+        (status = 200, description = "The entity and its references", body = CrateEntityResponse),
+        (status = 400, description = "Depth above 5", body = ErrorResponse),
+        (status = 404, description = "Crate or entity not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn crate_entity(
+    State(index): State<SharedCrateIndex>,
+    Path((crate_id, entity_id)): Path<(String, String)>,
+    ApiQuery(params): ApiQuery<CrateEntityParams>,
+) -> Result<Json<CrateEntityResponse>, ApiError> {
+    let response = blocking(move || {
+        let index = read_index(&index)?;
+        let graph = index.entity_graph(&crate_id)?;
+        let parts = match params.parts {
+            0 => None,
+            depth => Some(convert_part_tree(graph.part_tree(&entity_id, depth)?)),
+        };
+        Ok(CrateEntityResponse {
+            entity: graph.expand(&entity_id, params.expand)?,
+            outgoing: convert_refs(graph.outgoing(&entity_id)?),
+            incoming: convert_refs(graph.incoming(&entity_id)?),
+            parts,
+        })
+    })
+    .await?;
+    Ok(Json(response))
+}
+
+fn convert_refs(refs: Vec<EntityRef>) -> Vec<EntityRefResponse> {
+    refs.into_iter()
+        .map(|r| EntityRefResponse {
+            property: r.property,
+            entity_id: r.entity_id,
+            name: r.name,
+        })
+        .collect()
+}
+
+fn convert_part_tree(tree: PartTree) -> PartTreeResponse {
+    PartTreeResponse {
+        entity_id: tree.entity_id,
+        name: tree.name,
+        parts: tree.parts.into_iter().map(convert_part_tree).collect(),
+    }
+}
+
+/// Every crate an entity with an absolute @id (e.g. an ORCID) occurs in
+#[utoipa::path(
+    get,
+    path = "/entities/{entity_id}",
+    tag = "search",
+    params(
+        ("entity_id" = String, Path, description = "Absolute @id of the entity, URL-encoded")
+    ),
+    responses(
+        (status = 200, description = "The entity's occurrences, roles and names", body = RegisteredEntityResponse),
+        (status = 404, description = "No crate mentions the entity", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn registered_entity(
+    State(index): State<SharedCrateIndex>,
+    Path(entity_id): Path<String>,
+) -> Result<Json<RegisteredEntityResponse>, ApiError> {
+    let response = blocking(move || {
+        let idx = read_index(&index)?;
+        let entity = idx
+            .registered_entity(&entity_id)
+            .ok_or(IndexError::EntityNotFound(entity_id))?;
+        let crates: Vec<_> = entity
+            .occurrences
+            .into_iter()
+            .map(|occurrence| EntityOccurrenceResponse {
+                crate_name: idx
+                    .get_crate_info(&occurrence.crate_id)
+                    .and_then(|entry| entry.name.clone()),
+                crate_id: occurrence.crate_id,
+                roles: occurrence.roles.into_iter().collect(),
+                names: occurrence.names.into_iter().collect(),
+            })
+            .collect();
+        Ok(RegisteredEntityResponse {
+            entity_id: entity.entity_id,
+            names: entity.names,
+            types: entity.types,
+            crate_count: crates.len(),
+            crates,
+        })
+    })
+    .await?;
+    Ok(Json(response))
+}
+
+/// Find entities with content similar to an entity, outside the crates it occurs in
+#[utoipa::path(
+    get,
+    path = "/entities/similar",
+    tag = "search",
+    params(
+        SimilarEntitiesParams
+    ),
+    responses(
+        (status = 200, description = "Similar entities, most similar first", body = SearchResponse),
+        (status = 404, description = "Entity not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn similar_entities(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<SimilarEntitiesParams>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let hits =
+        blocking(move || Ok(read_index(&index)?.similar_entities(&params.id, params.limit)?))
+            .await?;
+    Ok(Json(convert_hits(hits)))
+}
+
+/// Find entities of a @type (including subtypes unless `exact`), optionally matching a query
+#[utoipa::path(
+    get,
+    path = "/entities/by-type/{entity_type}",
+    tag = "search",
+    params(
+        ("entity_type" = String, Path, description = "@type value, e.g. Person"),
+        EntityTypeParams
+    ),
+    responses(
+        (status = 200, description = "Entities of the type", body = SearchResponse),
+        (status = 400, description = "Invalid query, with the error position for syntax errors", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn entities_by_type(
+    State(index): State<SharedCrateIndex>,
+    Path(entity_type): Path<String>,
+    ApiQuery(params): ApiQuery<EntityTypeParams>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let hits = blocking(move || {
+        let idx = read_index(&index)?;
+        let scope = crate_scope(params.within, params.descendants);
+        let hits = match (&params.q, params.exact) {
+            (Some(q), false) => idx.search_typed(&entity_type, q, params.limit, scope),
+            (Some(q), true) => idx.search_typed_exact(&entity_type, q, params.limit, scope),
+            (None, false) => idx.search_by_type(&entity_type, params.limit, scope),
+            (None, true) => idx.search_by_type_exact(&entity_type, params.limit, scope),
+        }?;
+        Ok(hits)
+    })
+    .await?;
+    Ok(Json(convert_hits(hits)))
+}
+
+/// List all crates that mention an entity @id
+#[utoipa::path(
+    get,
+    path = "/crates/by-entity",
+    tag = "search",
+    params(
+        EntityIdParams
+    ),
+    responses(
+        (status = 200, description = "Crates mentioning the entity", body = CrateIdsResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn crates_by_entity(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<EntityIdParams>,
+) -> Result<Json<CrateIdsResponse>, ApiError> {
+    let scope = crate_scope(params.within, params.descendants);
+    let crate_ids =
+        blocking(move || Ok(read_index(&index)?.find_crates_by_entity(&params.id, scope)?)).await?;
+    let mut crate_ids: Vec<String> = crate_ids.into_iter().collect();
+    crate_ids.sort();
+    Ok(Json(CrateIdsResponse {
+        count: crate_ids.len(),
+        crate_ids,
+    }))
+}
+
+/// Compute corpus statistics over the entities matching an optional query
+#[utoipa::path(
+    post,
+    path = "/aggregate",
+    tag = "stats",
+    request_body = AggregateRequest,
+    responses(
+        (status = 200, description = "Aggregation results", body = AggregateResponse),
+        (status = 400, description = "Invalid query or aggregation", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn aggregate(
+    State(index): State<SharedCrateIndex>,
+    ApiJson(request): ApiJson<AggregateRequest>,
+) -> Result<Json<AggregateResponse>, ApiError> {
+    let result = blocking(move || {
+        let aggs = Aggregation::parse_map(&request.aggs)?;
+        let scope = crate_scope(request.within, request.descendants);
+        let idx = read_index(&index)?;
+        let result = match (&request.q, &request.query) {
+            (Some(_), Some(_)) => Err(IndexError::InvalidQuery {
+                path: "query".to_string(),
+                reason: "give either q or query, not both".to_string(),
+            }),
+            (_, Some(query)) => {
+                QueryDsl::from_json(query).and_then(|query| idx.aggregate_dsl(&query, &aggs, scope))
             }
+            (q, None) => idx.aggregate(q.as_deref(), &aggs, scope),
+        }?;
+        Ok(result)
+    })
+    .await?;
+    Ok(Json(AggregateResponse {
+        total: result.total,
+        aggs: result.aggs,
+    }))
+}
+
+/// Type-ahead suggestions for entity names, keywords, authors and types
+#[utoipa::path(
+    get,
+    path = "/suggest",
+    tag = "search",
+    params(SuggestParams),
+    responses(
+        (status = 200, description = "Suggestions, most frequent first", body = SuggestResponse),
+        (status = 400, description = "Unknown suggestion field", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn suggest(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<SuggestParams>,
+) -> Result<Json<SuggestResponse>, ApiError> {
+    let suggestions = blocking(move || {
+        let field = params
+            .field
+            .as_deref()
+            .map(SuggestField::parse)
+            .transpose()?;
+        let scope = crate_scope(params.within, params.descendants);
+        Ok(read_index(&index)?.suggest(&params.q, field, params.limit, scope)?)
+    })
+    .await?;
+    Ok(Json(SuggestResponse {
+        suggestions: suggestions
+            .into_iter()
+            .map(|s| SuggestionResponse {
+                text: s.text,
+                field: s.field.as_str().to_string(),
+                count: s.count,
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct SparqlParams {
+    /// SPARQL 1.1 query; the default graph is the union of all crates, each
+    /// crate is also a named graph
+    query: String,
+}
+
+/// Run a SPARQL 1.1 query over the RDF graphs of all crates
+#[utoipa::path(
+    get,
+    path = "/sparql",
+    tag = "search",
+    params(SparqlParams),
+    responses(
+        (status = 200, description = "SPARQL JSON results for SELECT and ASK, N-Triples for CONSTRUCT and DESCRIBE", content(("application/sparql-results+json"), ("application/n-triples"))),
+        (status = 400, description = "Invalid SPARQL query", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn sparql_get(
+    State(index): State<SharedCrateIndex>,
+    ApiQuery(params): ApiQuery<SparqlParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    run_sparql(index, params.query).await
+}
+
+/// Run a SPARQL 1.1 query sent as `application/sparql-query` or as a form
+#[utoipa::path(
+    post,
+    path = "/sparql",
+    tag = "search",
+    request_body(content = String, content_type = "application/sparql-query"),
+    responses(
+        (status = 200, description = "SPARQL JSON results for SELECT and ASK, N-Triples for CONSTRUCT and DESCRIBE", content(("application/sparql-results+json"), ("application/n-triples"))),
+        (status = 400, description = "Invalid SPARQL query", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+    )
+)]
+async fn sparql_post(
+    State(index): State<SharedCrateIndex>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, ApiError> {
+    let is_form = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    let query = if is_form {
+        url::form_urlencoded::parse(body.as_bytes())
+            .find(|(key, _)| key == "query")
+            .map(|(_, value)| value.into_owned())
+            .ok_or_else(|| ApiError::bad_request("invalid_body", "missing form field: query"))?
+    } else {
+        body
+    };
+    run_sparql(index, query).await
+}
+
+async fn run_sparql(index: SharedCrateIndex, query: String) -> Result<impl IntoResponse, ApiError> {
+    let results = blocking(move || Ok(read_index(&index)?.sparql(&query)?)).await?;
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, results.content_type)],
+        results.body,
+    ))
+}
+
+/// Open the index, keeping metadata in an S3-compatible store if `S3_ENDPOINT`
+/// is set and computing entity vectors if `ROCRATE_EMBEDDER` is set
+fn open_index() -> Result<CrateIndex, IndexError> {
+    let config = IndexConfig::from_current_dir()?.with_embedder(HashingEmbedder::from_env());
+    match S3Config::from_env() {
+        Some(s3) => {
+            info!(endpoint = %s3.endpoint, bucket = %s3.bucket, "Using S3 metadata storage");
+            CrateIndex::open_with_storage(config, Box::new(S3Storage::new(s3)))
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Task join error: {}", e),
-            }),
-        )
-            .into_response(),
+        None => CrateIndex::open_with_config(config),
     }
 }
 
@@ -709,7 +1877,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let bind_addr = std::env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1".to_string());
 
     info!("Initializing RO-Crate index...");
-    let index = CrateIndex::open_or_create()?;
+    // Opening may perform blocking HTTP requests against the metadata store
+    let index = tokio::task::spawn_blocking(open_index).await??;
     let crate_count = index.crate_count();
     info!(crate_count, "Loaded crates from index");
 
@@ -723,10 +1892,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/crates", get(list_crates))
         .route("/crates/url", post(add_crate_by_url))
         .route("/crates/upload", post(add_crate_by_upload))
+        .route("/crates/by-entity", get(crates_by_entity))
         .route("/crates/{crate_id}", get(get_crate))
         .route("/crates/{crate_id}", delete(remove_crate))
         .route("/crates/{crate_id}/info", get(get_crate_info))
-        .route("/search", get(search))
+        .route("/crates/{crate_id}/similar", get(similar_crates))
+        .route("/crates/{crate_id}/related", get(related_crates))
+        .route("/crates/{crate_id}/entities/{entity_id}", get(crate_entity))
+        .route("/search", get(search).post(search_structured))
+        .route("/search/crates", get(search_crates))
+        .route("/search/explain", get(explain))
+        .route("/entities/by-id", get(entities_by_id))
+        .route("/entities/{entity_id}", get(registered_entity))
+        .route("/entities/by-type/{entity_type}", get(entities_by_type))
+        .route("/entities/similar", get(similar_entities))
+        .route("/aggregate", post(aggregate))
+        .route("/suggest", get(suggest))
+        .route("/sparql", get(sparql_get).post(sparql_post))
         .with_state(shared_index)
         .layer(
             CorsLayer::new()
@@ -767,3 +1949,123 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_invalid_dsl_is_bad_request() {
+        let error = QueryDsl::from_json(&json!({"range": {"field": "size", "gte": "a", "lt": 3}}))
+            .unwrap_err();
+        let error = ApiError::from(error);
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.body.code, "invalid_query");
+        assert_eq!(error.body.path.as_deref(), Some("$.range"));
+    }
+
+    #[test]
+    fn test_unknown_crate_is_not_found() {
+        let error = IndexError::CrateNotFound("01ABC/parse-results".to_string());
+        let error = ApiError::from(error);
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+        assert_eq!(error.body.code, "crate_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_crate_content_negotiation() {
+        let index = CrateIndex::new_in_memory().unwrap().into_shared();
+        let metadata = json!({
+            "@context": "https://w3id.org/ro/crate/1.2/context",
+            "@graph": [
+                {"@id": "ro-crate-metadata.json", "@type": "CreativeWork", "about": {"@id": "./"}},
+                {"@id": "./", "@type": "Dataset", "name": "Soil survey"}
+            ]
+        });
+        let crate_id = write_index(&index)
+            .unwrap()
+            .add_from_json(&metadata.to_string(), None)
+            .unwrap()
+            .crate_id;
+
+        let get = |accept: Option<&str>, format: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(accept) = accept {
+                headers.insert(header::ACCEPT, accept.parse().unwrap());
+            }
+            let params = CrateFormatParams {
+                format: format.map(str::to_string),
+            };
+            get_crate(
+                State(index.clone()),
+                Path(crate_id.clone()),
+                headers,
+                ApiQuery(params),
+            )
+        };
+        for (accept, format, content_type) in [
+            (None, None, "application/json"),
+            (Some("text/turtle, */*;q=0.1"), None, "text/turtle"),
+            (Some("text/turtle"), Some("nquads"), "application/n-quads"),
+            (Some("application/ld+json"), None, "application/ld+json"),
+        ] {
+            let response = get(accept, format).await.unwrap().into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], content_type);
+        }
+
+        let error = get(Some("text/html"), None).await.err().unwrap();
+        assert_eq!(error.status, StatusCode::NOT_ACCEPTABLE);
+        let error = get(None, Some("rdfxml")).await.err().unwrap();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_sparql_form_and_query_body() {
+        let index = CrateIndex::new_in_memory().unwrap().into_shared();
+        let metadata = json!({
+            "@context": "https://w3id.org/ro/crate/1.2/context",
+            "@graph": [
+                {"@id": "ro-crate-metadata.json", "@type": "CreativeWork", "about": {"@id": "./"}},
+                {"@id": "./", "@type": "Dataset", "name": "Soil survey"}
+            ]
+        });
+        write_index(&index)
+            .unwrap()
+            .add_from_json(&metadata.to_string(), None)
+            .unwrap();
+
+        let query = "ASK { ?d <http://schema.org/name> \"Soil survey\" }";
+        let mut form = HeaderMap::new();
+        form.insert(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        let body: String = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("query", query)
+            .finish();
+        for (headers, body) in [(form, body), (HeaderMap::new(), query.to_string())] {
+            let response = sparql_post(State(index.clone()), headers, body)
+                .await
+                .unwrap()
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "application/sparql-results+json"
+            );
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, r#"{"head":{},"boolean":true}"#);
+        }
+
+        let error = sparql_post(State(index), HeaderMap::new(), "ASK {".to_string())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.body.path.as_deref(), Some("query"));
+    }
+}
@@ -1,22 +1,33 @@
-use std::collections::HashMap;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
+use crate::embed::Embedder;
 use crate::error::IndexError;
+use crate::extract::extract_root_metadata;
+use crate::index::WriterSettings;
 
 const INDEX_DIR_NAME: &str = ".rocrate-index";
-const METADATA_DIR_NAME: &str = "metadata";
+pub(crate) const METADATA_DIR_NAME: &str = "metadata";
 const INDEX_SUBDIR_NAME: &str = "index";
-const MANIFEST_FILE_NAME: &str = "manifest.json";
+pub(crate) const MANIFEST_FILE_NAME: &str = "manifest.json";
+const MANIFEST_DB_FILE_NAME: &str = "manifest.redb";
+const MIGRATED_MANIFEST_FILE_NAME: &str = "manifest.json.migrated";
+const METADATA_EXTENSION: &str = "json";
+const SIDECAR_EXTENSION: &str = "id";
 
-/// Configuration for index paths and directories
+/// Configuration for index paths, directories, writer settings and the embedder
 #[derive(Debug, Clone)]
 pub struct Config {
     base_dir: PathBuf,
+    writer_settings: WriterSettings,
+    embedder: Option<Arc<dyn Embedder>>,
 }
 
 /// Information about an indexed crate
@@ -31,6 +42,12 @@ pub struct CrateEntry {
     pub name: Option<String>,
     /// Description extracted from the crate metadata
     pub description: Option<String>,
+    /// Location the crate was loaded from (URL, path, zip entry), if known
+    #[serde(default)]
+    pub source: Option<String>,
+    /// When the crate was indexed (Unix seconds), if known
+    #[serde(default)]
+    pub indexed_at: Option<i64>,
 }
 
 impl CrateEntry {
@@ -42,6 +59,8 @@ impl CrateEntry {
             full_path,
             name: None,
             description: None,
+            source: None,
+            indexed_at: None,
         }
     }
 
@@ -54,6 +73,8 @@ impl CrateEntry {
             full_path,
             name: None,
             description: None,
+            source: None,
+            indexed_at: None,
         }
     }
 
@@ -69,6 +90,18 @@ impl CrateEntry {
         self
     }
 
+    /// Set the source location
+    pub fn with_source(mut self, source: Option<String>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Set the index time (Unix seconds)
+    pub fn with_indexed_at(mut self, indexed_at: Option<i64>) -> Self {
+        self.indexed_at = indexed_at;
+        self
+    }
+
     /// Check if this is a root-level crate (no parents)
     pub fn is_root(&self) -> bool {
         self.full_path.len() <= 1
@@ -84,6 +117,14 @@ impl CrateEntry {
             None
         }
     }
+
+    /// IDs of the enclosing crates, root first (empty for root crates)
+    pub fn ancestors(&self) -> &[String] {
+        match self.full_path.split_last() {
+            Some((_, ancestors)) => ancestors,
+            None => &[],
+        }
+    }
 }
 
 /// Manifest tracking all indexed crates with their metadata
@@ -91,12 +132,41 @@ impl CrateEntry {
 pub struct Manifest {
     /// Map of crate_id to CrateEntry
     pub crates: HashMap<String, CrateEntry>,
+    /// Crate IDs added or removed since the last save
+    #[serde(skip)]
+    changed: HashSet<String>,
 }
 
 impl Config {
     /// Create config with a specific base directory
     pub fn new(base_dir: PathBuf) -> Self {
-        Self { base_dir }
+        Self {
+            base_dir,
+            writer_settings: WriterSettings::default(),
+            embedder: None,
+        }
+    }
+
+    /// Set the index writer settings
+    pub fn with_writer_settings(mut self, writer_settings: WriterSettings) -> Self {
+        self.writer_settings = writer_settings;
+        self
+    }
+
+    /// Get the index writer settings
+    pub fn writer_settings(&self) -> &WriterSettings {
+        &self.writer_settings
+    }
+
+    /// Set the embedder computing entity vectors for semantic search
+    pub fn with_embedder(mut self, embedder: Option<Arc<dyn Embedder>>) -> Self {
+        self.embedder = embedder;
+        self
+    }
+
+    /// Get the embedder, if semantic search is enabled
+    pub fn embedder(&self) -> Option<&Arc<dyn Embedder>> {
+        self.embedder.as_ref()
     }
 
     /// Create config using the current working directory
@@ -120,11 +190,21 @@ impl Config {
         self.base_dir.join(INDEX_SUBDIR_NAME)
     }
 
-    /// Get the manifest file path
+    /// Get the legacy JSON manifest file path
     pub fn manifest_path(&self) -> PathBuf {
         self.base_dir.join(MANIFEST_FILE_NAME)
     }
 
+    /// Get the manifest database path
+    pub fn manifest_db_path(&self) -> PathBuf {
+        self.base_dir.join(MANIFEST_DB_FILE_NAME)
+    }
+
+    /// Get the path the JSON manifest is moved to after migration
+    pub fn migrated_manifest_path(&self) -> PathBuf {
+        self.base_dir.join(MIGRATED_MANIFEST_FILE_NAME)
+    }
+
     /// Create all necessary directories if they don't exist
     pub fn ensure_directories(&self) -> Result<(), IndexError> {
         fs::create_dir_all(&self.base_dir)?;
@@ -134,10 +214,11 @@ impl Config {
 
     /// Check if the index has been initialized
     pub fn is_initialized(&self) -> bool {
-        self.base_dir.exists() && self.manifest_path().exists()
+        self.base_dir.exists()
+            && (self.manifest_db_path().exists() || self.manifest_path().exists())
     }
 
-    /// Load manifest from disk, or return empty manifest if not exists
+    /// Load JSON manifest from disk, or return empty manifest if not exists
     pub fn load_manifest(&self) -> Result<Manifest, IndexError> {
         let path = self.manifest_path();
         if !path.exists() {
@@ -148,7 +229,7 @@ impl Config {
         Ok(manifest)
     }
 
-    /// Save manifest to disk
+    /// Save JSON manifest to disk
     pub fn save_manifest(&self, manifest: &Manifest) -> Result<(), IndexError> {
         let path = self.manifest_path();
         let content = serde_json::to_string_pretty(manifest)?;
@@ -160,21 +241,181 @@ impl Config {
     /// Uses a hash of the crate_id for the filename to handle URLs safely
     pub fn metadata_path_for_crate(&self, crate_id: &str) -> PathBuf {
         let hash = hash_crate_id(crate_id);
-        self.metadata_dir().join(format!("{}.json", hash))
+        self.metadata_dir()
+            .join(format!("{}.{}", hash, METADATA_EXTENSION))
+    }
+
+    /// Get the path of the sidecar file recording the crate ID of a metadata file
+    pub fn sidecar_path_for_crate(&self, crate_id: &str) -> PathBuf {
+        let hash = hash_crate_id(crate_id);
+        self.metadata_dir()
+            .join(format!("{}.{}", hash, SIDECAR_EXTENSION))
+    }
+
+    /// Write a crate's metadata JSON together with its crate ID sidecar
+    pub fn write_metadata(&self, crate_id: &str, json: &str) -> Result<(), IndexError> {
+        fs::write(self.metadata_path_for_crate(crate_id), json)?;
+        fs::write(self.sidecar_path_for_crate(crate_id), crate_id)?;
+        Ok(())
+    }
+
+    /// Read a crate's metadata JSON, if stored
+    pub fn read_metadata(&self, crate_id: &str) -> Result<Option<String>, IndexError> {
+        let path = self.metadata_path_for_crate(crate_id);
+        if path.exists() {
+            Ok(Some(fs::read_to_string(path)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Remove a crate's metadata JSON and sidecar
+    pub fn remove_metadata(&self, crate_id: &str) -> Result<(), IndexError> {
+        for path in [
+            self.metadata_path_for_crate(crate_id),
+            self.sidecar_path_for_crate(crate_id),
+        ] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rename metadata files written with the legacy `DefaultHasher` naming
+    /// to the SHA-256 naming and add missing crate ID sidecars.
+    ///
+    /// Crates whose legacy name doesn't match (e.g. written by another
+    /// toolchain) are matched to leftover legacy files by their root name and
+    /// description; crates that remain unresolved are reported as warnings.
+    ///
+    /// Returns the number of renamed metadata files.
+    pub fn migrate_metadata_files(&self, manifest: &Manifest) -> Result<usize, IndexError> {
+        let mut migrated = 0;
+        let mut unresolved = Vec::new();
+
+        for crate_id in manifest.crates.keys() {
+            let path = self.metadata_path_for_crate(crate_id);
+
+            if !path.exists() {
+                let legacy_path = self.metadata_dir().join(format!(
+                    "{}.{}",
+                    legacy_hash_crate_id(crate_id),
+                    METADATA_EXTENSION
+                ));
+                if !legacy_path.exists() {
+                    unresolved.push(crate_id.as_str());
+                    continue;
+                }
+                fs::rename(&legacy_path, &path)?;
+                migrated += 1;
+            }
+
+            let sidecar = self.sidecar_path_for_crate(crate_id);
+            if !sidecar.exists() {
+                fs::write(sidecar, crate_id)?;
+            }
+        }
+
+        if !unresolved.is_empty() {
+            migrated += self.migrate_by_content(manifest, &mut unresolved)?;
+        }
+        for crate_id in unresolved {
+            eprintln!("Warning: No metadata file found for crate {}", crate_id);
+        }
+
+        Ok(migrated)
+    }
+
+    /// Match legacy metadata files (non-SHA-256 names without a sidecar) to
+    /// unresolved crates by the name and description of their root entity
+    fn migrate_by_content(
+        &self,
+        manifest: &Manifest,
+        unresolved: &mut Vec<&str>,
+    ) -> Result<usize, IndexError> {
+        let mut migrated = 0;
+
+        for dir_entry in fs::read_dir(self.metadata_dir())? {
+            let path = dir_entry?.path();
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let is_metadata = path
+                .extension()
+                .is_some_and(|ext| ext == METADATA_EXTENSION);
+            let has_sidecar = path.with_extension(SIDECAR_EXTENSION).exists();
+            if !is_metadata || is_sha256_name(stem) || has_sidecar {
+                continue;
+            }
+
+            let content = fs::read_to_string(&path)?;
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) else {
+                eprintln!(
+                    "Warning: Skipping unreadable metadata file {}",
+                    path.display()
+                );
+                continue;
+            };
+            let graph = json.get("@graph").and_then(|g| g.as_array());
+            let root = extract_root_metadata(graph.map(Vec::as_slice).unwrap_or_default());
+            if root.name.is_none() && root.description.is_none() {
+                continue;
+            }
+
+            let matches: Vec<usize> = unresolved
+                .iter()
+                .enumerate()
+                .filter(|(_, crate_id)| {
+                    let entry = &manifest.crates[**crate_id];
+                    entry.name == root.name && entry.description == root.description
+                })
+                .map(|(i, _)| i)
+                .collect();
+
+            match matches[..] {
+                [i] => {
+                    let crate_id = unresolved.remove(i);
+                    fs::rename(&path, self.metadata_path_for_crate(crate_id))?;
+                    fs::write(self.sidecar_path_for_crate(crate_id), crate_id)?;
+                    migrated += 1;
+                }
+                [] => {}
+                _ => eprintln!(
+                    "Warning: Metadata file {} matches {} crates, leaving it in place",
+                    path.display(),
+                    matches.len()
+                ),
+            }
+        }
+
+        Ok(migrated)
     }
 }
 
 impl Manifest {
     /// Add a crate entry to the manifest
     pub fn add_crate(&mut self, entry: CrateEntry) {
+        self.changed.insert(entry.crate_id.clone());
         self.crates.insert(entry.crate_id.clone(), entry);
     }
 
     /// Remove a crate from the manifest
     pub fn remove_crate(&mut self, crate_id: &str) {
+        self.changed.insert(crate_id.to_string());
         self.crates.remove(crate_id);
     }
 
+    /// Crate IDs added or removed since the last `clear_changes`
+    pub fn changes(&self) -> &HashSet<String> {
+        &self.changed
+    }
+
+    /// Forget recorded changes (after they have been persisted)
+    pub fn clear_changes(&mut self) {
+        self.changed.clear();
+    }
+
     /// Check if a crate ID exists in the manifest
     pub fn contains(&self, crate_id: &str) -> bool {
         self.crates.contains_key(crate_id)
@@ -202,7 +443,23 @@ impl Manifest {
 }
 
 /// Generate a stable hash string for a crate ID (used for filenames)
-fn hash_crate_id(s: &str) -> String {
+///
+/// Hex-encoded SHA-256 of the crate ID, so names survive toolchain upgrades.
+pub(crate) fn hash_crate_id(s: &str) -> String {
+    Sha256::digest(s.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Check if a file stem is a hex-encoded SHA-256 digest
+fn is_sha256_name(stem: &str) -> bool {
+    stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Legacy filename hash based on `DefaultHasher`, only used for migration.
+/// Its output is not stable across Rust releases.
+fn legacy_hash_crate_id(s: &str) -> String {
     let mut hasher = DefaultHasher::new();
     s.hash(&mut hasher);
     format!("{:016x}", hasher.finish())
@@ -218,6 +475,80 @@ mod tests {
         let hash1 = hash_crate_id(id);
         let hash2 = hash_crate_id(id);
         assert_eq!(hash1, hash2);
+
+        // Fixed SHA-256 digest, independent of the toolchain
+        assert_eq!(
+            hash_crate_id("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_migrate_legacy_metadata_files() {
+        let base = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        let config = Config::new(base.clone());
+        config.ensure_directories().unwrap();
+
+        let crate_id = "https://example.org/crate";
+        let legacy_path = config
+            .metadata_dir()
+            .join(format!("{}.json", legacy_hash_crate_id(crate_id)));
+        fs::write(&legacy_path, "{}").unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest.add_crate(CrateEntry::new(crate_id.to_string()));
+
+        assert_eq!(config.migrate_metadata_files(&manifest).unwrap(), 1);
+        assert!(!legacy_path.exists());
+        assert_eq!(
+            config.read_metadata(crate_id).unwrap(),
+            Some("{}".to_string())
+        );
+        assert_eq!(
+            fs::read_to_string(config.sidecar_path_for_crate(crate_id)).unwrap(),
+            crate_id
+        );
+
+        // Second run is a no-op
+        assert_eq!(config.migrate_metadata_files(&manifest).unwrap(), 0);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_unmatched_legacy_file_by_content() {
+        let base = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        let config = Config::new(base.clone());
+        config.ensure_directories().unwrap();
+
+        // A legacy file whose name came from a different DefaultHasher
+        let json = r#"{"@graph": [
+            {"@id": "ro-crate-metadata.json", "about": {"@id": "./"}},
+            {"@id": "./", "@type": "Dataset", "name": "Growth curves"}
+        ]}"#;
+        let orphan = config.metadata_dir().join("0123456789abcdef.json");
+        fs::write(&orphan, json).unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest.add_crate(
+            CrateEntry::new("growth".to_string()).with_name(Some("Growth curves".to_string())),
+        );
+        manifest
+            .add_crate(CrateEntry::new("missing".to_string()).with_name(Some("Other".to_string())));
+
+        assert_eq!(config.migrate_metadata_files(&manifest).unwrap(), 1);
+        assert!(!orphan.exists());
+        assert_eq!(
+            config.read_metadata("growth").unwrap(),
+            Some(json.to_string())
+        );
+        assert_eq!(
+            fs::read_to_string(config.sidecar_path_for_crate("growth")).unwrap(),
+            "growth"
+        );
+        assert_eq!(config.read_metadata("missing").unwrap(), None);
+
+        fs::remove_dir_all(base).unwrap();
     }
 
     #[test]
@@ -0,0 +1,768 @@
+//! Structured JSON query DSL compiled directly to tantivy queries.
+//!
+//! Every clause is an object with a single key naming its kind:
+//!
+//! ```json
+//! {"bool": {
+//!     "must": [{"term": {"field": "author.@id", "value": "https://orcid.org/0000-0002-1825-0097"}}],
+//!     "should": [{"match": {"field": "name", "query": "growth curves"}}],
+//!     "must_not": [{"exists": {"field": "license"}}]
+//! }}
+//! ```
+//!
+//! Fields are `@id`, `@type`, `crate`, `content`, or a property path into the
+//! entity JSON, either dotted (`author.name`, dots in keys escaped as `\.`)
+//! or as an array of keys (`["author", "name"]`). `@type` also matches
+//! subtypes; `entity_type_exact` matches literal types only.
+
+use std::ops::Bound;
+
+use serde_json::{Map, Value};
+use tantivy::Term;
+use tantivy::query::{
+    AllQuery, BooleanQuery, ExistsQuery, FuzzyTermQuery, Occur, PhrasePrefixQuery, PhraseQuery,
+    Query, RangeQuery, TermQuery,
+};
+use tantivy::schema::{Field, IndexRecordOption};
+
+use crate::error::IndexError;
+use crate::index::SearchIndex;
+
+/// Maximum number of terms a prefix clause expands to
+const PREFIX_MAX_EXPANSIONS: u32 = 1000;
+
+/// Largest edit distance accepted by fuzzy clauses
+const MAX_FUZZY_DISTANCE: u8 = 2;
+
+/// A literal value in a term or range clause
+#[derive(Debug, Clone, PartialEq)]
+pub enum DslValue {
+    Str(String),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+}
+
+/// A parsed query clause
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryDsl {
+    /// Combine clauses; at least one `should` must match if there is no `must`
+    Bool {
+        must: Vec<QueryDsl>,
+        should: Vec<QueryDsl>,
+        must_not: Vec<QueryDsl>,
+    },
+    /// Exact value (property strings are matched untokenized)
+    Term { field: String, value: DslValue },
+    /// Analyzed full-text match, any term (or all terms with `all_terms`)
+    Match {
+        field: Option<String>,
+        query: String,
+        all_terms: bool,
+    },
+    /// Analyzed phrase, with optional slop
+    Phrase {
+        field: Option<String>,
+        query: String,
+        slop: u32,
+    },
+    /// Values between two bounds
+    Range {
+        field: String,
+        lower: Bound<DslValue>,
+        upper: Bound<DslValue>,
+    },
+    /// Field or property has any value
+    Exists { field: String },
+    /// Terms starting with a prefix
+    Prefix { field: String, value: String },
+    /// Terms within an edit distance
+    Fuzzy {
+        field: String,
+        value: String,
+        distance: u8,
+    },
+    /// Every entity
+    All,
+}
+
+/// What a DSL field name refers to in the schema
+enum Target<'a> {
+    /// Untokenized top-level field (`@id`, `@type`, `crate`)
+    Raw(Field),
+    /// Tokenized top-level field (`content`)
+    Text(Field),
+    /// Path into the `properties` JSON field
+    Property(&'a str),
+}
+
+impl QueryDsl {
+    /// Parse a JSON query, reporting the path of the offending clause on error
+    pub fn from_json(value: &Value) -> Result<Self, IndexError> {
+        parse_clause(value, "$")
+    }
+
+    /// Compile to a tantivy query against the given index
+    pub fn compile(&self, index: &SearchIndex) -> Result<Box<dyn Query>, IndexError> {
+        self.compile_at(index, "$")
+    }
+
+    fn compile_at(&self, index: &SearchIndex, path: &str) -> Result<Box<dyn Query>, IndexError> {
+        match self {
+            QueryDsl::Bool {
+                must,
+                should,
+                must_not,
+            } => {
+                let path = format!("{}.bool", path);
+                let mut clauses = Vec::new();
+                for (occur, name, list) in [
+                    (Occur::Must, "must", must),
+                    (Occur::Should, "should", should),
+                    (Occur::MustNot, "must_not", must_not),
+                ] {
+                    for (i, clause) in list.iter().enumerate() {
+                        let query =
+                            clause.compile_at(index, &format!("{}.{}[{}]", path, name, i))?;
+                        clauses.push((occur, query));
+                    }
+                }
+                // A boolean query with only exclusions matches nothing in tantivy
+                if must.is_empty() && should.is_empty() {
+                    clauses.push((Occur::Must, Box::new(AllQuery)));
+                }
+                Ok(Box::new(BooleanQuery::new(clauses)))
+            }
+            QueryDsl::Term { field, value } => {
+                let path = format!("{}.term", path);
+                match resolve(index, field) {
+                    Target::Raw(f) | Target::Text(f) => {
+                        let DslValue::Str(s) = value else {
+                            return Err(invalid(&path, format!("{} only holds strings", field)));
+                        };
+                        Ok(Box::new(TermQuery::new(
+                            Term::from_field_text(f, s),
+                            IndexRecordOption::WithFreqs,
+                        )))
+                    }
+                    Target::Property(p) => {
+                        let term = property_term(index, p, value);
+                        match value {
+                            // Strings are tokenized in the inverted index, so exact
+                            // matches go through the untokenized fast column
+                            DslValue::Str(_) => Ok(Box::new(RangeQuery::new(
+                                Bound::Included(term.clone()),
+                                Bound::Included(term),
+                            ))),
+                            _ => Ok(Box::new(TermQuery::new(term, IndexRecordOption::Basic))),
+                        }
+                    }
+                }
+            }
+            QueryDsl::Match {
+                field,
+                query,
+                all_terms,
+            } => {
+                let path = format!("{}.match", path);
+                let target = match field {
+                    Some(field) => resolve(index, field),
+                    None => Target::Text(index.content_field),
+                };
+                if let Target::Raw(f) = target {
+                    return Ok(Box::new(TermQuery::new(
+                        Term::from_field_text(f, query),
+                        IndexRecordOption::Basic,
+                    )));
+                }
+
+                let terms = analyze(index, &target, query)?;
+                if terms.is_empty() {
+                    return Err(invalid(&path, "query has no searchable terms"));
+                }
+                let occur = if *all_terms {
+                    Occur::Must
+                } else {
+                    Occur::Should
+                };
+                let clauses: Vec<(Occur, Box<dyn Query>)> = terms
+                    .into_iter()
+                    .map(|(_, term)| {
+                        let query: Box<dyn Query> =
+                            Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+                        (occur, query)
+                    })
+                    .collect();
+                Ok(Box::new(BooleanQuery::new(clauses)))
+            }
+            QueryDsl::Phrase { field, query, slop } => {
+                let path = format!("{}.phrase", path);
+                let target = match field {
+                    Some(field) => resolve(index, field),
+                    None => Target::Text(index.content_field),
+                };
+                if let Target::Raw(_) = target {
+                    return Err(invalid(&path, "phrases need a text field or property"));
+                }
+
+                let mut terms = analyze(index, &target, query)?;
+                match terms.len() {
+                    0 => Err(invalid(&path, "query has no searchable terms")),
+                    1 => Ok(Box::new(TermQuery::new(
+                        terms.remove(0).1,
+                        IndexRecordOption::WithFreqs,
+                    ))),
+                    _ => Ok(Box::new(PhraseQuery::new_with_offset_and_slop(
+                        terms, *slop,
+                    ))),
+                }
+            }
+            QueryDsl::Range {
+                field,
+                lower,
+                upper,
+            } => {
+                let path = format!("{}.range", path);
+                let kind = range_kind(&path, lower, upper)?;
+                let to_term = |value: &DslValue| -> Result<Term, IndexError> {
+                    let value = coerce(value, &kind);
+                    match resolve(index, field) {
+                        Target::Raw(f) => match &value {
+                            DslValue::Str(s) => Ok(Term::from_field_text(f, s)),
+                            _ => Err(invalid(&path, format!("{} only holds strings", field))),
+                        },
+                        Target::Text(_) => Err(invalid(
+                            &path,
+                            "ranges need @id, @type, crate or a property",
+                        )),
+                        Target::Property(p) => Ok(property_term(index, p, &value)),
+                    }
+                };
+                Ok(Box::new(RangeQuery::new(
+                    map_bound(lower, &to_term)?,
+                    map_bound(upper, &to_term)?,
+                )))
+            }
+            QueryDsl::Exists { field } => {
+                let path = format!("{}.exists", path);
+                match resolve(index, field) {
+                    Target::Raw(f) => Ok(Box::new(ExistsQuery::new(
+                        index.schema().get_field_name(f).to_string(),
+                        false,
+                    ))),
+                    Target::Text(_) => Err(invalid(
+                        &path,
+                        "exists needs @id, @type, crate or a property",
+                    )),
+                    Target::Property(p) => Ok(Box::new(ExistsQuery::new(
+                        format!("properties.{}", p),
+                        true,
+                    ))),
+                }
+            }
+            QueryDsl::Prefix { field, value } => {
+                let term = single_term(index, &resolve(index, field), value);
+                let mut query = PhrasePrefixQuery::new(vec![term]);
+                query.set_max_expansions(PREFIX_MAX_EXPANSIONS);
+                Ok(Box::new(query))
+            }
+            QueryDsl::Fuzzy {
+                field,
+                value,
+                distance,
+            } => {
+                let term = single_term(index, &resolve(index, field), value);
+                Ok(Box::new(FuzzyTermQuery::new(term, *distance, true)))
+            }
+            QueryDsl::All => Ok(Box::new(AllQuery)),
+        }
+    }
+}
+
+fn invalid(path: &str, reason: impl Into<String>) -> IndexError {
+    IndexError::InvalidQuery {
+        path: path.to_string(),
+        reason: reason.into(),
+    }
+}
+
+fn resolve<'a>(index: &SearchIndex, field: &'a str) -> Target<'a> {
+    match field {
+        "@id" | "id" => Target::Raw(index.id_field),
+        "@type" | "entity_type" => Target::Raw(index.entity_type_field),
+        "entity_type_exact" => Target::Raw(index.entity_type_exact_field),
+        "crate" | "occurs_in" => Target::Raw(index.occurs_in_field),
+        "content" => Target::Text(index.content_field),
+        _ => Target::Property(field.strip_prefix("properties.").unwrap_or(field)),
+    }
+}
+
+/// Term for a typed value at a property path
+fn property_term(index: &SearchIndex, path: &str, value: &DslValue) -> Term {
+    let mut term = Term::from_field_json_path(index.properties_field, path, false);
+    match value {
+        DslValue::Str(s) => term.append_type_and_str(s),
+        DslValue::I64(n) => term.append_type_and_fast_value(*n),
+        DslValue::F64(n) => term.append_type_and_fast_value(*n),
+        DslValue::Bool(b) => term.append_type_and_fast_value(*b),
+    }
+    term
+}
+
+/// Single term for prefix and fuzzy clauses, lowercased on tokenized fields
+fn single_term(index: &SearchIndex, target: &Target, value: &str) -> Term {
+    match target {
+        Target::Raw(f) => Term::from_field_text(*f, value),
+        Target::Text(f) => Term::from_field_text(*f, &value.to_lowercase()),
+        Target::Property(p) => property_term(index, p, &DslValue::Str(value.to_lowercase())),
+    }
+}
+
+/// Tokenize text the way the target field was indexed
+fn analyze(
+    index: &SearchIndex,
+    target: &Target,
+    text: &str,
+) -> Result<Vec<(usize, Term)>, IndexError> {
+    let field = match target {
+        Target::Raw(f) | Target::Text(f) => *f,
+        Target::Property(_) => index.properties_field,
+    };
+    let mut analyzer = index.index.tokenizer_for_field(field)?;
+    let mut stream = analyzer.token_stream(text);
+
+    let mut terms = Vec::new();
+    while let Some(token) = stream.next() {
+        let term = match target {
+            Target::Property(p) => property_term(index, p, &DslValue::Str(token.text.clone())),
+            _ => Term::from_field_text(field, &token.text),
+        };
+        terms.push((token.position, term));
+    }
+    Ok(terms)
+}
+
+/// Value type shared by both range bounds
+enum RangeKind {
+    Str,
+    I64,
+    F64,
+}
+
+fn range_kind(
+    path: &str,
+    lower: &Bound<DslValue>,
+    upper: &Bound<DslValue>,
+) -> Result<RangeKind, IndexError> {
+    let mut kind = None;
+    for bound in [lower, upper] {
+        let value = match bound {
+            Bound::Included(v) | Bound::Excluded(v) => v,
+            Bound::Unbounded => continue,
+        };
+        kind = match (kind, value) {
+            (None, DslValue::Str(_)) => Some(RangeKind::Str),
+            (None, DslValue::I64(_)) => Some(RangeKind::I64),
+            (None, DslValue::F64(_)) => Some(RangeKind::F64),
+            (Some(RangeKind::Str), DslValue::Str(_)) => Some(RangeKind::Str),
+            (Some(RangeKind::I64), DslValue::I64(_)) => Some(RangeKind::I64),
+            (Some(RangeKind::I64 | RangeKind::F64), DslValue::I64(_) | DslValue::F64(_)) => {
+                Some(RangeKind::F64)
+            }
+            (_, DslValue::Bool(_)) => return Err(invalid(path, "booleans have no range")),
+            _ => return Err(invalid(path, "bounds must both be strings or numbers")),
+        };
+    }
+    kind.ok_or_else(|| invalid(path, "expected at least one of gt, gte, lt, lte"))
+}
+
+fn coerce(value: &DslValue, kind: &RangeKind) -> DslValue {
+    match (value, kind) {
+        (DslValue::I64(n), RangeKind::F64) => DslValue::F64(*n as f64),
+        _ => value.clone(),
+    }
+}
+
+fn map_bound(
+    bound: &Bound<DslValue>,
+    to_term: &impl Fn(&DslValue) -> Result<Term, IndexError>,
+) -> Result<Bound<Term>, IndexError> {
+    Ok(match bound {
+        Bound::Included(v) => Bound::Included(to_term(v)?),
+        Bound::Excluded(v) => Bound::Excluded(to_term(v)?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
+}
+
+// === Parsing ===
+
+fn parse_clause(value: &Value, path: &str) -> Result<QueryDsl, IndexError> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| invalid(path, "expected a clause object"))?;
+    let mut entries = obj.iter();
+    let (kind, body) = match (entries.next(), entries.next()) {
+        (Some(entry), None) => entry,
+        _ => {
+            return Err(invalid(
+                path,
+                "a clause must have exactly one key, e.g. {\"match\": {...}}",
+            ));
+        }
+    };
+
+    let path = format!("{}.{}", path, kind);
+    let path = path.as_str();
+    let body = body
+        .as_object()
+        .ok_or_else(|| invalid(path, "expected an object"))?;
+
+    match kind.as_str() {
+        "bool" => {
+            check_keys(body, path, &["must", "should", "must_not"])?;
+            let must = parse_clauses(body, path, "must")?;
+            let should = parse_clauses(body, path, "should")?;
+            let must_not = parse_clauses(body, path, "must_not")?;
+            if must.is_empty() && should.is_empty() && must_not.is_empty() {
+                return Err(invalid(path, "expected at least one clause"));
+            }
+            Ok(QueryDsl::Bool {
+                must,
+                should,
+                must_not,
+            })
+        }
+        "term" => {
+            check_keys(body, path, &["field", "value"])?;
+            Ok(QueryDsl::Term {
+                field: parse_field(body, path)?,
+                value: parse_value(required(body, path, "value")?, &format!("{}.value", path))?,
+            })
+        }
+        "match" => {
+            check_keys(body, path, &["field", "query", "operator"])?;
+            let all_terms = match body.get("operator").map(|v| v.as_str()) {
+                None | Some(Some("or")) => false,
+                Some(Some("and")) => true,
+                Some(_) => {
+                    return Err(invalid(
+                        &format!("{}.operator", path),
+                        "expected \"and\" or \"or\"",
+                    ));
+                }
+            };
+            Ok(QueryDsl::Match {
+                field: optional_field(body, path)?,
+                query: parse_string(body, path, "query")?,
+                all_terms,
+            })
+        }
+        "phrase" => {
+            check_keys(body, path, &["field", "query", "slop"])?;
+            let slop = match body.get("slop") {
+                None => 0,
+                Some(v) => v
+                    .as_u64()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or_else(|| invalid(&format!("{}.slop", path), "expected an integer"))?,
+            };
+            Ok(QueryDsl::Phrase {
+                field: optional_field(body, path)?,
+                query: parse_string(body, path, "query")?,
+                slop,
+            })
+        }
+        "range" => {
+            check_keys(body, path, &["field", "gt", "gte", "lt", "lte"])?;
+            let bound = |inclusive: &str, exclusive: &str| -> Result<Bound<DslValue>, IndexError> {
+                match (body.get(inclusive), body.get(exclusive)) {
+                    (Some(_), Some(_)) => Err(invalid(
+                        path,
+                        format!("{} and {} are mutually exclusive", inclusive, exclusive),
+                    )),
+                    (Some(v), None) => Ok(Bound::Included(parse_value(
+                        v,
+                        &format!("{}.{}", path, inclusive),
+                    )?)),
+                    (None, Some(v)) => Ok(Bound::Excluded(parse_value(
+                        v,
+                        &format!("{}.{}", path, exclusive),
+                    )?)),
+                    (None, None) => Ok(Bound::Unbounded),
+                }
+            };
+            let lower = bound("gte", "gt")?;
+            let upper = bound("lte", "lt")?;
+            range_kind(path, &lower, &upper)?;
+            Ok(QueryDsl::Range {
+                field: parse_field(body, path)?,
+                lower,
+                upper,
+            })
+        }
+        "exists" => {
+            check_keys(body, path, &["field"])?;
+            Ok(QueryDsl::Exists {
+                field: parse_field(body, path)?,
+            })
+        }
+        "prefix" => {
+            check_keys(body, path, &["field", "value"])?;
+            Ok(QueryDsl::Prefix {
+                field: parse_field(body, path)?,
+                value: parse_string(body, path, "value")?,
+            })
+        }
+        "fuzzy" => {
+            check_keys(body, path, &["field", "value", "distance"])?;
+            let distance = match body.get("distance") {
+                None => 1,
+                Some(v) => v
+                    .as_u64()
+                    .filter(|d| *d <= MAX_FUZZY_DISTANCE as u64)
+                    .ok_or_else(|| {
+                        invalid(
+                            &format!("{}.distance", path),
+                            format!("expected an integer from 0 to {}", MAX_FUZZY_DISTANCE),
+                        )
+                    })? as u8,
+            };
+            Ok(QueryDsl::Fuzzy {
+                field: parse_field(body, path)?,
+                value: parse_string(body, path, "value")?,
+                distance,
+            })
+        }
+        "all" => {
+            check_keys(body, path, &[])?;
+            Ok(QueryDsl::All)
+        }
+        other => Err(invalid(
+            path,
+            format!(
+                "unknown clause '{}', expected one of bool, term, match, phrase, range, \
+                 exists, prefix, fuzzy, all",
+                other
+            ),
+        )),
+    }
+}
+
+fn parse_clauses(
+    body: &Map<String, Value>,
+    path: &str,
+    key: &str,
+) -> Result<Vec<QueryDsl>, IndexError> {
+    match body.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| parse_clause(item, &format!("{}.{}[{}]", path, key, i)))
+            .collect(),
+        // A single clause is accepted in place of a one-element list
+        Some(item) => Ok(vec![parse_clause(item, &format!("{}.{}", path, key))?]),
+    }
+}
+
+fn check_keys(body: &Map<String, Value>, path: &str, allowed: &[&str]) -> Result<(), IndexError> {
+    match body.keys().find(|k| !allowed.contains(&k.as_str())) {
+        Some(key) => Err(invalid(
+            &format!("{}.{}", path, key),
+            format!("unknown key, expected one of: {}", allowed.join(", ")),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn required<'a>(
+    body: &'a Map<String, Value>,
+    path: &str,
+    key: &str,
+) -> Result<&'a Value, IndexError> {
+    body.get(key)
+        .ok_or_else(|| invalid(path, format!("missing '{}'", key)))
+}
+
+fn parse_string(body: &Map<String, Value>, path: &str, key: &str) -> Result<String, IndexError> {
+    required(body, path, key)?
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| invalid(&format!("{}.{}", path, key), "expected a string"))
+}
+
+fn parse_field(body: &Map<String, Value>, path: &str) -> Result<String, IndexError> {
+    required(body, path, "field")?;
+    optional_field(body, path).map(|f| f.unwrap_or_default())
+}
+
+/// A field is a dotted path or an array of keys, which needs no escaping
+fn optional_field(body: &Map<String, Value>, path: &str) -> Result<Option<String>, IndexError> {
+    let path = format!("{}.field", path);
+    match body.get("field") {
+        None => Ok(None),
+        Some(Value::String(s)) if !s.is_empty() => Ok(Some(s.clone())),
+        Some(Value::Array(keys)) if !keys.is_empty() => {
+            let keys: Option<Vec<String>> = keys
+                .iter()
+                .map(|k| k.as_str().map(|k| k.replace('.', "\\.")))
+                .collect();
+            keys.map(|k| Some(k.join(".")))
+                .ok_or_else(|| invalid(&path, "expected an array of strings"))
+        }
+        Some(_) => Err(invalid(
+            &path,
+            "expected a field name or an array of property keys",
+        )),
+    }
+}
+
+fn parse_value(value: &Value, path: &str) -> Result<DslValue, IndexError> {
+    match value {
+        Value::String(s) => Ok(DslValue::Str(s.clone())),
+        Value::Bool(b) => Ok(DslValue::Bool(*b)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(DslValue::I64(i)),
+            None => n
+                .as_f64()
+                .map(DslValue::F64)
+                .ok_or_else(|| invalid(path, "number out of range")),
+        },
+        _ => Err(invalid(path, "expected a string, number or boolean")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tantivy::collector::Count;
+
+    fn test_index() -> SearchIndex {
+        SearchIndex::with_entities(&[(
+            "crate-a",
+            vec![
+                json!({
+                    "@id": "./",
+                    "@type": "Dataset",
+                    "name": "Escherichia coli growth curves",
+                    "description": "Optical density measurements",
+                    "author": {"@id": "https://orcid.org/0000-0002-1825-0097"},
+                    "contentSize": 2048
+                }),
+                json!({
+                    "@id": "https://orcid.org/0000-0002-1825-0097",
+                    "@type": "Person",
+                    "name": "Josiah Carberry"
+                }),
+                json!({
+                    "@id": "data.csv",
+                    "@type": "File",
+                    "name": "Raw optical density",
+                    "contentSize": 512
+                }),
+            ],
+        )])
+    }
+
+    fn count(index: &SearchIndex, query: Value) -> usize {
+        let query = QueryDsl::from_json(&query).unwrap().compile(index).unwrap();
+        index.searcher().search(&query, &Count).unwrap()
+    }
+
+    #[test]
+    fn test_clauses() {
+        let index = test_index();
+
+        let orcid = "https://orcid.org/0000-0002-1825-0097";
+        assert_eq!(
+            count(
+                &index,
+                json!({"term": {"field": "author.@id", "value": orcid}})
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &index,
+                json!({"term": {"field": ["author", "@id"], "value": orcid}})
+            ),
+            1
+        );
+        assert_eq!(
+            count(&index, json!({"term": {"field": "@id", "value": orcid}})),
+            1
+        );
+        assert_eq!(
+            count(&index, json!({"match": {"query": "optical growth"}})),
+            2
+        );
+        assert_eq!(
+            count(
+                &index,
+                json!({"match": {"query": "optical growth", "operator": "and"}})
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &index,
+                json!({"phrase": {"field": "name", "query": "optical density"}})
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &index,
+                json!({"range": {"field": "contentSize", "gte": 1000}})
+            ),
+            1
+        );
+        assert_eq!(count(&index, json!({"exists": {"field": "author"}})), 1);
+        assert_eq!(
+            count(
+                &index,
+                json!({"prefix": {"field": "name", "value": "Escher"}})
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &index,
+                json!({"fuzzy": {"field": "name", "value": "carbery"}})
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &index,
+                json!({"bool": {"must_not": [{"term": {"field": "@type", "value": "Person"}}]}})
+            ),
+            2
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_clause() {
+        let err = QueryDsl::from_json(&json!({"bool": {"must": [
+            {"match": {"query": "x"}},
+            {"range": {"field": "size", "gte": "a", "lt": 3}}
+        ]}}))
+        .unwrap_err();
+        match err {
+            IndexError::InvalidQuery { path, .. } => assert_eq!(path, "$.bool.must[1].range"),
+            other => panic!("unexpected error: {}", other),
+        }
+
+        let err = QueryDsl::from_json(&json!({"bool": {"should": {"tern": {}}}})).unwrap_err();
+        assert!(err.to_string().contains("$.bool.should.tern"));
+
+        let index = SearchIndex::new_in_memory().unwrap();
+        let err = QueryDsl::from_json(&json!({"phrase": {"field": "@type", "query": "a b"}}))
+            .unwrap()
+            .compile(&index)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("$.phrase"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Text embeddings for semantic search.
+//!
+//! Any model can be plugged in by implementing [`Embedder`]; the bundled
+//! [`HashingEmbedder`] needs no model files or network access.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Default number of dimensions of a `HashingEmbedder`
+const DEFAULT_HASHING_DIMENSIONS: usize = 256;
+
+/// Turns text into a fixed-size vector
+///
+/// Vectors are compared by cosine similarity. Vectors stored by an embedder
+/// with a different `id` or dimension count are discarded and recomputed.
+pub trait Embedder: Send + Sync + Debug {
+    /// Identifies the model and its settings, e.g. `hashing-ngram-v1/256`
+    fn id(&self) -> String;
+
+    /// Length of every vector returned by `embed`
+    fn dimensions(&self) -> usize;
+
+    /// Embed a text; an all-zero vector means nothing to embed
+    fn embed(&self, text: &str) -> Vec<f32>;
+}
+
+/// Deterministic embedder hashing words and character trigrams into buckets
+///
+/// Related word forms ("transcriptome", "transcriptomics") share most of
+/// their trigrams and end up close; it does not know synonyms.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASHING_DIMENSIONS)
+    }
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    /// Embedder selected by `ROCRATE_EMBEDDER`, if any
+    ///
+    /// Accepts `hashing` or `hashing:<dimensions>`; unset or `none` disables
+    /// semantic search.
+    pub fn from_env() -> Option<Arc<dyn Embedder>> {
+        let value = std::env::var("ROCRATE_EMBEDDER").ok()?;
+        let dimensions = match value.as_str() {
+            "" | "none" => return None,
+            "hashing" => DEFAULT_HASHING_DIMENSIONS,
+            other => match other.strip_prefix("hashing:").and_then(|d| d.parse().ok()) {
+                Some(dimensions) if dimensions > 0 => dimensions,
+                _ => {
+                    eprintln!(
+                        "Warning: unknown ROCRATE_EMBEDDER '{}', semantic search disabled",
+                        value
+                    );
+                    return None;
+                }
+            },
+        };
+        Some(Arc::new(Self::new(dimensions)))
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let hash = fnv1a(feature.as_bytes());
+        let bucket = (hash % self.dimensions as u64) as usize;
+        // The sign bit keeps collisions from only ever adding up
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign * weight;
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn id(&self) -> String {
+        format!("hashing-ngram-v1/{}", self.dimensions)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase);
+        for word in words {
+            self.add_feature(&mut vector, &word, 1.0);
+
+            let padded: Vec<char> = format!("^{}$", word).chars().collect();
+            for trigram in padded.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                self.add_feature(&mut vector, &format!("#{}", trigram), 0.5);
+            }
+        }
+
+        normalize(&mut vector);
+        vector
+    }
+}
+
+/// Scale a vector to unit length (zero vectors are left alone)
+pub(crate) fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Cosine similarity of two vectors
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms =
+        a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms > 0.0 { dot / norms } else { 0.0 }
+}
+
+/// 64-bit FNV-1a, stable across platforms and releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashing_embedder() {
+        let embedder = HashingEmbedder::new(128);
+        let a = embedder.embed("Transcriptome of yeast");
+        assert_eq!(a.len(), 128);
+        assert_eq!(a, embedder.embed("transcriptome  OF yeast"));
+        assert!((cosine(&a, &a) - 1.0).abs() < 1e-5);
+
+        let related = embedder.embed("yeast transcriptomics");
+        let unrelated = embedder.embed("soil nitrogen survey");
+        assert!(cosine(&a, &related) > 0.5);
+        assert!(cosine(&a, &related) > cosine(&a, &unrelated) + 0.3);
+
+        assert!(embedder.embed(" - ").iter().all(|&x| x == 0.0));
+    }
+}
@@ -0,0 +1,299 @@
+//! Navigation of the entity graph within one crate.
+//!
+//! Entities reference each other with `{"@id": ...}` property values. The
+//! graph indexes these references both ways, so clients can follow an
+//! entity's outgoing references, find the entities pointing at it
+//! (backlinks) and walk `hasPart` trees. Entities that are only referenced,
+//! e.g. an ORCID the crate doesn't describe, can be navigated as well: they
+//! have backlinks but no properties of their own.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::error::IndexError;
+use crate::extract::{extract_id, extract_references};
+
+/// Deepest expansion or `hasPart` traversal allowed
+pub const MAX_DEPTH: usize = 5;
+
+/// A reference between two entities of a crate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityRef {
+    /// Property holding the reference, e.g. `author`
+    pub property: String,
+    /// The entity at the other end
+    pub entity_id: String,
+    /// Name of that entity, if the crate describes it
+    pub name: Option<String>,
+}
+
+/// An entity with its `hasPart` children
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartTree {
+    pub entity_id: String,
+    pub name: Option<String>,
+    /// Parts below the traversal depth, or seen higher up the tree, are left out
+    pub parts: Vec<PartTree>,
+}
+
+/// Entities of one crate with their references indexed both ways
+pub struct EntityGraph {
+    entities: HashMap<String, Value>,
+    /// (property, target) per source entity, by property
+    outgoing: HashMap<String, Vec<(String, String)>>,
+    /// (property, source) per target entity, in @graph order of the sources
+    incoming: HashMap<String, Vec<(String, String)>>,
+}
+
+impl EntityGraph {
+    /// Build the graph from a crate's `@graph` entities
+    pub fn new(entities: Vec<Value>) -> Self {
+        let mut outgoing: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut incoming: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for entity in &entities {
+            let (Some(source), Some(properties)) = (extract_id(entity), entity.as_object()) else {
+                continue;
+            };
+            for (property, value) in properties.iter().filter(|(p, _)| !p.starts_with('@')) {
+                for target in extract_references(value) {
+                    outgoing
+                        .entry(source.to_string())
+                        .or_default()
+                        .push((property.clone(), target.to_string()));
+                    incoming
+                        .entry(target.to_string())
+                        .or_default()
+                        .push((property.clone(), source.to_string()));
+                }
+            }
+        }
+        let entities = entities
+            .into_iter()
+            .filter_map(|entity| Some((extract_id(&entity)?.to_string(), entity)))
+            .collect();
+        Self {
+            entities,
+            outgoing,
+            incoming,
+        }
+    }
+
+    /// An entity as stored
+    pub fn get(&self, entity_id: &str) -> Option<&Value> {
+        self.entities.get(entity_id)
+    }
+
+    /// An entity with references replaced by the entities they point at,
+    /// `depth` levels deep
+    ///
+    /// References to entities the crate doesn't describe, and references
+    /// back to an entity being expanded, are kept as they are. An entity
+    /// that is only referenced is returned as such a reference.
+    pub fn expand(&self, entity_id: &str, depth: usize) -> Result<Value, IndexError> {
+        check_depth("expand", depth)?;
+        match self.require(entity_id)? {
+            Some(entity) => Ok(self.expand_entity(entity, depth, &mut vec![entity_id])),
+            None => Ok(json!({ "@id": entity_id })),
+        }
+    }
+
+    /// References from an entity to others
+    pub fn outgoing(&self, entity_id: &str) -> Result<Vec<EntityRef>, IndexError> {
+        self.require(entity_id)?;
+        Ok(self.refs(self.outgoing.get(entity_id)))
+    }
+
+    /// References to an entity from others (backlinks)
+    pub fn incoming(&self, entity_id: &str) -> Result<Vec<EntityRef>, IndexError> {
+        self.require(entity_id)?;
+        Ok(self.refs(self.incoming.get(entity_id)))
+    }
+
+    /// The `hasPart` tree below an entity, `depth` levels deep
+    pub fn part_tree(&self, entity_id: &str, depth: usize) -> Result<PartTree, IndexError> {
+        check_depth("parts", depth)?;
+        self.require(entity_id)?;
+        Ok(self.parts_of(entity_id, depth, &mut vec![entity_id]))
+    }
+
+    /// The entity if the crate describes it, `None` if it is only referenced
+    fn require(&self, entity_id: &str) -> Result<Option<&Value>, IndexError> {
+        match self.get(entity_id) {
+            Some(entity) => Ok(Some(entity)),
+            None if self.incoming.contains_key(entity_id) => Ok(None),
+            None => Err(IndexError::EntityNotFound(entity_id.to_string())),
+        }
+    }
+
+    fn name(&self, entity_id: &str) -> Option<String> {
+        self.get(entity_id)?.get("name")?.as_str().map(String::from)
+    }
+
+    fn refs(&self, edges: Option<&Vec<(String, String)>>) -> Vec<EntityRef> {
+        edges
+            .into_iter()
+            .flatten()
+            .map(|(property, entity_id)| EntityRef {
+                property: property.clone(),
+                entity_id: entity_id.clone(),
+                name: self.name(entity_id),
+            })
+            .collect()
+    }
+
+    fn expand_entity<'a>(&'a self, entity: &Value, depth: usize, path: &mut Vec<&'a str>) -> Value {
+        let mut entity = entity.clone();
+        if depth == 0 {
+            return entity;
+        }
+        if let Some(properties) = entity.as_object_mut() {
+            for (property, value) in properties.iter_mut() {
+                if !property.starts_with('@') {
+                    self.expand_value(value, depth, path);
+                }
+            }
+        }
+        entity
+    }
+
+    fn expand_value<'a>(&'a self, value: &mut Value, depth: usize, path: &mut Vec<&'a str>) {
+        match value {
+            Value::Array(values) => {
+                for value in values {
+                    self.expand_value(value, depth, path);
+                }
+            }
+            Value::Object(reference) => {
+                let target = reference.get("@id").and_then(|id| id.as_str());
+                if let Some((id, target)) = target.and_then(|id| self.entities.get_key_value(id))
+                    && !path.contains(&id.as_str())
+                {
+                    path.push(id);
+                    *value = self.expand_entity(target, depth - 1, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn parts_of<'a>(&'a self, entity_id: &str, depth: usize, path: &mut Vec<&'a str>) -> PartTree {
+        let mut tree = PartTree {
+            entity_id: entity_id.to_string(),
+            name: self.name(entity_id),
+            parts: Vec::new(),
+        };
+        if depth == 0 {
+            return tree;
+        }
+        let parts = self
+            .outgoing
+            .get(entity_id)
+            .into_iter()
+            .flatten()
+            .filter(|(property, _)| property == "hasPart");
+        for (_, part) in parts {
+            if !path.contains(&part.as_str()) {
+                path.push(part);
+                tree.parts.push(self.parts_of(part, depth - 1, path));
+                path.pop();
+            }
+        }
+        tree
+    }
+}
+
+fn check_depth(parameter: &str, depth: usize) -> Result<(), IndexError> {
+    if depth > MAX_DEPTH {
+        return Err(IndexError::InvalidQuery {
+            path: parameter.to_string(),
+            reason: format!("depth must be at most {}", MAX_DEPTH),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> EntityGraph {
+        EntityGraph::new(vec![
+            json!({"@id": "./", "@type": "Dataset", "name": "Root",
+                   "hasPart": [{"@id": "data/"}, {"@id": "README.md"}],
+                   "author": {"@id": "#alice"}}),
+            json!({"@id": "data/", "@type": "Dataset", "name": "Data",
+                   "hasPart": {"@id": "data/counts.csv"}, "isPartOf": {"@id": "./"}}),
+            json!({"@id": "data/counts.csv", "@type": "File", "author": {"@id": "#alice"}}),
+            json!({"@id": "README.md", "@type": "File"}),
+            json!({"@id": "#alice", "@type": "Person", "name": "Alice",
+                   "affiliation": {"@id": "https://ror.org/05gq02987"}}),
+        ])
+    }
+
+    #[test]
+    fn test_outgoing_and_incoming() {
+        let graph = graph();
+        let outgoing = graph.outgoing("./").unwrap();
+        assert_eq!(outgoing.len(), 3);
+        assert_eq!(outgoing[0].property, "author");
+        assert_eq!(outgoing[1].entity_id, "data/");
+        assert_eq!(outgoing[1].name.as_deref(), Some("Data"));
+
+        let incoming = graph.incoming("#alice").unwrap();
+        let sources: Vec<_> = incoming.iter().map(|r| r.entity_id.as_str()).collect();
+        assert_eq!(sources, vec!["./", "data/counts.csv"]);
+        assert!(incoming.iter().all(|r| r.property == "author"));
+
+        assert!(matches!(
+            graph.incoming("#bob"),
+            Err(IndexError::EntityNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_referenced_only_entity() {
+        let graph = graph();
+        let ror = "https://ror.org/05gq02987";
+        let incoming = graph.incoming(ror).unwrap();
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].entity_id, "#alice");
+        assert_eq!(incoming[0].property, "affiliation");
+
+        assert!(graph.outgoing(ror).unwrap().is_empty());
+        assert_eq!(graph.expand(ror, 2).unwrap(), json!({"@id": ror}));
+        assert!(graph.part_tree(ror, 1).unwrap().parts.is_empty());
+    }
+
+    #[test]
+    fn test_expand_and_part_tree() {
+        let graph = graph();
+        assert_eq!(graph.expand("./", 0).unwrap(), *graph.get("./").unwrap());
+
+        let expanded = graph.expand("./", 2).unwrap();
+        assert_eq!(expanded["author"]["name"], "Alice");
+        // Unknown entities and references back up the path stay references
+        assert_eq!(
+            expanded["author"]["affiliation"],
+            json!({"@id": "https://ror.org/05gq02987"})
+        );
+        assert_eq!(expanded["hasPart"][0]["isPartOf"], json!({"@id": "./"}));
+        // Below the depth, references are not expanded
+        assert_eq!(expanded["hasPart"][0]["hasPart"]["@type"], "File");
+        assert_eq!(
+            expanded["hasPart"][0]["hasPart"]["author"],
+            json!({"@id": "#alice"})
+        );
+
+        let tree = graph.part_tree("./", MAX_DEPTH).unwrap();
+        assert_eq!(tree.parts.len(), 2);
+        assert_eq!(tree.parts[0].parts[0].entity_id, "data/counts.csv");
+        assert!(graph.part_tree("./", 1).unwrap().parts[0].parts.is_empty());
+        assert!(matches!(
+            graph.expand("./", MAX_DEPTH + 1),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+    }
+}
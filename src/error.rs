@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use tantivy::query::QueryParserError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,12 +7,19 @@ pub enum IndexError {
     #[error("Tantivy error: {0}")]
     Tantivy(#[from] tantivy::TantivyError),
 
-    #[error("Query parse error: {0}")]
-    QueryParse(#[from] tantivy::query::QueryParserError),
+    #[error("Query parse error: {source}")]
+    QueryParse {
+        source: QueryParserError,
+        /// Byte offset in the query string where parsing failed, if known
+        position: Option<usize>,
+    },
 
     #[error("Crate not found: {0}")]
     CrateNotFound(String),
 
+    #[error("Entity not found: {0}")]
+    EntityNotFound(String),
+
     #[error("Failed to load crate from {path}: {reason}")]
     LoadError { path: String, reason: String },
 
@@ -29,4 +37,53 @@ pub enum IndexError {
 
     #[error("Invalid crate format: {0}")]
     InvalidCrateFormat(String),
+
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    #[error("Invalid query at {path}: {reason}")]
+    InvalidQuery { path: String, reason: String },
+
+    #[error("Invalid search cursor: {0}")]
+    InvalidCursor(String),
+
+    #[error("Manifest database error: {0}")]
+    Database(Box<redb::Error>),
+
+    #[error("RDF error: {0}")]
+    Rdf(String),
+}
+
+impl IndexError {
+    /// Parse error of `query`, locating syntax errors in it
+    pub(crate) fn query_parse(source: QueryParserError, query: &str) -> Self {
+        let position = match source {
+            QueryParserError::SyntaxError(_) => tantivy::query_grammar::parse_query_lenient(query)
+                .1
+                .first()
+                .map(|e| e.pos),
+            _ => None,
+        };
+        IndexError::QueryParse { source, position }
+    }
+
+    /// Stable machine-readable code for the kind of error, e.g. `query_parse`
+    pub fn code(&self) -> &'static str {
+        match self {
+            IndexError::Tantivy(_) | IndexError::OpenDirectory(_) => "index_error",
+            IndexError::QueryParse { .. } => "query_parse",
+            IndexError::CrateNotFound(_) => "crate_not_found",
+            IndexError::EntityNotFound(_) => "entity_not_found",
+            IndexError::LoadError { .. } => "load_error",
+            IndexError::Io(_) => "io_error",
+            IndexError::Json(_) => "json_error",
+            IndexError::InvalidPath(_) => "invalid_path",
+            IndexError::InvalidCrateFormat(_) => "invalid_crate_format",
+            IndexError::Storage(_) => "storage_error",
+            IndexError::InvalidQuery { .. } => "invalid_query",
+            IndexError::InvalidCursor(_) => "invalid_cursor",
+            IndexError::Database(_) => "database_error",
+            IndexError::Rdf(_) => "rdf_error",
+        }
+    }
 }
@@ -0,0 +1,152 @@
+//! Score explanations for debugging the ranking of a hit.
+//!
+//! Wraps Tantivy's `Query::explain` for a single entity document and breaks
+//! the score down by the query's terms, each scored on its own.
+
+use std::cmp::Ordering;
+
+use serde::Deserialize;
+use tantivy::{
+    DocAddress, DocSet, Searcher, Term,
+    query::{EnableScoring, Query, TermQuery},
+    schema::{IndexRecordOption, Type},
+};
+
+use crate::error::IndexError;
+
+/// Why an entity scores as it does for a query
+#[derive(Debug, Clone)]
+pub struct ScoreExplanation {
+    pub entity_id: String,
+    pub crate_id: String,
+    /// The parsed query tree, as Tantivy prints it
+    pub parsed_query: String,
+    /// Whether the entity matches the query at all
+    pub matched: bool,
+    /// Score of the entity, 0 when it does not match
+    pub score: f32,
+    /// Fields with at least one matching query term
+    pub matched_fields: Vec<String>,
+    /// Query terms found in the entity, highest scoring first
+    pub terms: Vec<TermContribution>,
+    /// Tantivy's explanation tree, when the entity matches
+    pub details: Option<ExplanationNode>,
+}
+
+/// Score of one query term for an entity, computed as if it were queried alone
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermContribution {
+    /// Field, with the JSON path for properties (e.g. `properties.author.name`)
+    pub field: String,
+    pub term: String,
+    pub score: f32,
+}
+
+/// One node of Tantivy's explanation tree
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExplanationNode {
+    pub description: String,
+    pub value: f32,
+    #[serde(default)]
+    pub details: Vec<ExplanationNode>,
+    #[serde(default)]
+    pub context: Vec<String>,
+}
+
+/// Explain how `query` scores the document at `doc`
+///
+/// `terms_query` is the query whose terms are broken down; it is the
+/// unscoped query, so crate filters do not show up as contributions.
+pub(crate) fn explain(
+    searcher: &Searcher,
+    query: &dyn Query,
+    terms_query: &dyn Query,
+    doc: DocAddress,
+    entity_id: &str,
+    crate_id: &str,
+) -> Result<ScoreExplanation, IndexError> {
+    let explanation = explain_doc(searcher, query, doc)?;
+
+    let mut query_terms: Vec<Term> = Vec::new();
+    terms_query.query_terms(&mut |term, _| {
+        if !query_terms.contains(term) {
+            query_terms.push(term.clone());
+        }
+    });
+
+    let schema = searcher.schema();
+    let mut terms = Vec::new();
+    let mut matched_fields: Vec<String> = Vec::new();
+    for term in query_terms {
+        let term_query = TermQuery::new(term.clone(), IndexRecordOption::WithFreqs);
+        let Some(term_explanation) = explain_doc(searcher, &term_query, doc)? else {
+            continue;
+        };
+        // JSON path segments are separated by \u{1} in terms
+        let field = term.get_full_path(schema).replace('\u{1}', ".");
+        if !matched_fields.contains(&field) {
+            matched_fields.push(field.clone());
+        }
+        terms.push(TermContribution {
+            field,
+            term: term_text(&term),
+            score: term_explanation.value(),
+        });
+    }
+    terms.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.field.cmp(&b.field))
+            .then_with(|| a.term.cmp(&b.term))
+    });
+
+    let details = explanation
+        .as_ref()
+        .map(|e| serde_json::from_value(serde_json::to_value(e)?))
+        .transpose()?;
+    Ok(ScoreExplanation {
+        entity_id: entity_id.to_string(),
+        crate_id: crate_id.to_string(),
+        parsed_query: format!("{:?}", terms_query),
+        matched: explanation.is_some(),
+        score: explanation.as_ref().map_or(0.0, |e| e.value()),
+        matched_fields,
+        terms,
+        details,
+    })
+}
+
+/// Tantivy's explanation of a document's score, `None` if it does not match
+fn explain_doc(
+    searcher: &Searcher,
+    query: &dyn Query,
+    doc: DocAddress,
+) -> Result<Option<tantivy::query::Explanation>, IndexError> {
+    let weight = query.weight(EnableScoring::enabled_from_searcher(searcher))?;
+    let reader = searcher.segment_reader(doc.segment_ord);
+    let mut scorer = weight.scorer(reader, 1.0)?;
+    // Scorers start on their first match and may only seek forward
+    if scorer.doc() > doc.doc_id || scorer.seek(doc.doc_id) != doc.doc_id {
+        return Ok(None);
+    }
+    Ok(Some(weight.explain(reader, doc.doc_id)?))
+}
+
+/// Readable value of a term, looking past the JSON path of properties terms
+fn term_text(term: &Term) -> String {
+    let value = term.value();
+    if let Some(text) = value.as_str() {
+        return text.to_string();
+    }
+    if value.json_path_type() == Some(Type::Str) {
+        // JSON terms are `<path>\0<type code><value>`
+        let bytes = term.serialized_value_bytes();
+        if let Some(end) = bytes.iter().position(|&b| b == 0)
+            && let Some(text) = bytes.get(end + 2..)
+        {
+            return String::from_utf8_lossy(text).into_owned();
+        }
+    }
+    format!("{:?}", term)
+}
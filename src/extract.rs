@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 const TEXT_FIELDS: &[&str] = &[
@@ -36,6 +37,23 @@ pub struct RootMetadata {
     pub description: Option<String>,
 }
 
+/// Maximum length of the description kept in an entity summary
+const SUMMARY_DESCRIPTION_CHARS: usize = 300;
+
+/// Compact projection of an entity stored alongside its search document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntitySummary {
+    /// Entity name, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// @type values
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    /// Description, truncated to a few hundred characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
 /// Extract full-text searchable content from a JSON-LD entity
 pub fn extract_text(entity: &Value) -> String {
     let mut parts = Vec::new();
@@ -57,9 +75,24 @@ pub fn extract_text(entity: &Value) -> String {
     parts.join(" ")
 }
 
+/// First string of a plain, array or `{"@value": ...}` property value
+fn first_string(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(s) => Some(s),
+        Value::Array(arr) => arr.iter().find_map(first_string),
+        Value::Object(obj) => obj.get("@value").and_then(|v| v.as_str()),
+        _ => None,
+    }
+}
+
 fn collect_strings(parts: &mut Vec<String>, value: &Value) {
     match value {
         Value::String(s) => parts.push(s.clone()),
+        Value::Object(obj) => {
+            if let Some(Value::String(s)) = obj.get("@value") {
+                parts.push(s.clone());
+            }
+        }
         Value::Array(arr) => {
             for v in arr {
                 collect_strings(parts, v);
@@ -85,6 +118,42 @@ fn collect_names(parts: &mut Vec<String>, value: &Value) {
     }
 }
 
+/// Extract `keywords`, given either as an array or as a comma-separated string
+pub fn extract_keywords(entity: &Value) -> Vec<String> {
+    let mut parts = Vec::new();
+    if let Some(value) = entity.get("keywords") {
+        collect_strings(&mut parts, value);
+    }
+    parts
+        .iter()
+        .flat_map(|s| s.split(','))
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Extract inline author/creator/contributor/publisher names
+pub fn extract_person_names(entity: &Value) -> Vec<String> {
+    let mut parts = Vec::new();
+    for field in PERSON_FIELDS {
+        match entity.get(field) {
+            Some(Value::String(name)) => parts.push(name.clone()),
+            Some(Value::Array(arr)) => {
+                for v in arr {
+                    match v {
+                        Value::String(name) => parts.push(name.clone()),
+                        other => collect_names(&mut parts, other),
+                    }
+                }
+            }
+            Some(value) => collect_names(&mut parts, value),
+            None => {}
+        }
+    }
+    parts
+}
+
 /// Extract @type as a list of type names
 pub fn extract_types(entity: &Value) -> Vec<String> {
     match entity.get("@type") {
@@ -97,11 +166,66 @@ pub fn extract_types(entity: &Value) -> Vec<String> {
     }
 }
 
+/// Build the compact summary of an entity (name, types, short description)
+pub fn extract_summary(entity: &Value) -> EntitySummary {
+    let name = entity.get("name").and_then(first_string).map(String::from);
+
+    let description = entity.get("description").and_then(first_string).map(|d| {
+        match d.char_indices().nth(SUMMARY_DESCRIPTION_CHARS) {
+            Some((end, _)) => format!("{}…", &d[..end]),
+            None => d.to_string(),
+        }
+    });
+
+    EntitySummary {
+        name,
+        types: extract_types(entity),
+        description,
+    }
+}
+
+/// Extract `datePublished` as a Unix timestamp (seconds, UTC)
+pub fn extract_date_published(entity: &Value) -> Option<i64> {
+    let value = match entity.get("datePublished")? {
+        Value::Array(values) => values.first()?,
+        value => value,
+    };
+    parse_date(value.as_str()?)
+}
+
+/// Parse an ISO 8601 date or date-time, allowing reduced precision (`2024`, `2024-05`)
+pub fn parse_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(dt.timestamp());
+    }
+    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Some(dt.and_utc().timestamp());
+    }
+    let date = match value.len() {
+        4 => chrono::NaiveDate::parse_from_str(&format!("{}-01-01", value), "%Y-%m-%d"),
+        7 => chrono::NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d"),
+        _ => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d"),
+    };
+    date.ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp())
+}
+
 /// Extract @id from entity
 pub fn extract_id(entity: &Value) -> Option<&str> {
     entity.get("@id").and_then(|v| v.as_str())
 }
 
+/// `@id`s referenced by a property value, e.g. `[{"@id": "#alice"}]`
+pub fn extract_references(value: &Value) -> impl Iterator<Item = &str> {
+    let values = match value {
+        Value::Array(values) => values.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+    values.iter().filter_map(|v| v.get("@id")?.as_str())
+}
+
 /// Check if an entity conforms to the RO-Crate specification
 pub fn conforms_to_rocrate(entity: &Value) -> bool {
     let conforms_to = match entity.get("conformsTo") {
@@ -401,6 +525,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_summary() {
+        let entity = serde_json::json!({
+            "@id": "#alice",
+            "@type": "Person",
+            "name": "Alice",
+            "description": "x".repeat(400)
+        });
+        let summary = extract_summary(&entity);
+        assert_eq!(summary.name, Some("Alice".to_string()));
+        assert_eq!(summary.types, vec!["Person"]);
+        assert_eq!(summary.description.unwrap().chars().count(), 301);
+    }
+
+    #[test]
+    fn test_extract_summary_array_and_value_forms() {
+        let entity = serde_json::json!({
+            "@id": "#alice",
+            "@type": "Person",
+            "name": ["Alice", "A. Smith"],
+            "description": {"@value": "Chemist", "@language": "en"}
+        });
+        let summary = extract_summary(&entity);
+        assert_eq!(summary.name, Some("Alice".to_string()));
+        assert_eq!(summary.description, Some("Chemist".to_string()));
+        assert_eq!(extract_text(&entity), "Alice A. Smith Chemist");
+    }
+
+    #[test]
+    fn test_extract_keywords_and_people() {
+        let entity = serde_json::json!({
+            "keywords": "ecology, E. coli ,",
+            "author": [{"@id": "#alice"}, {"name": "Bob"}, "Carol"],
+            "publisher": {"@id": "https://ror.org/1", "name": "Uni"}
+        });
+        assert_eq!(extract_keywords(&entity), vec!["ecology", "E. coli"]);
+        assert_eq!(extract_person_names(&entity), vec!["Bob", "Carol", "Uni"]);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2024-01-01"), Some(1704067200));
+        assert_eq!(parse_date("2024"), Some(1704067200));
+        assert_eq!(parse_date("2024-01"), Some(1704067200));
+        assert_eq!(parse_date("2024-01-01T01:00:00+01:00"), Some(1704067200));
+        assert_eq!(parse_date("2024-01-01T00:00:00"), Some(1704067200));
+        assert_eq!(parse_date("last spring"), None);
+
+        let entity = serde_json::json!({"datePublished": ["2024-01-01", "2025"]});
+        assert_eq!(extract_date_published(&entity), Some(1704067200));
+    }
+
     #[test]
     fn test_conforms_to_rocrate() {
         let entity = serde_json::json!({
@@ -1,64 +1,249 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tantivy::{
-    Index, IndexReader, IndexSettings, IndexWriter, TantivyDocument, Term,
+    DateTime, Index, IndexReader, IndexSettings, IndexWriter, ReloadPolicy, TantivyDocument, Term,
     directory::MmapDirectory,
+    indexer::{LogMergePolicy, MergePolicy, NoMergePolicy},
     schema::{FAST, Field, STORED, STRING, Schema, TEXT},
 };
 
+use crate::embed::Embedder;
 use crate::error::IndexError;
-use crate::extract::{extract_id, extract_text, extract_types, resolve_id};
+use crate::extract::{
+    extract_date_published, extract_id, extract_summary, extract_text, extract_types, resolve_id,
+};
+use crate::suggest::suggestion_terms;
+use crate::types::expand_types;
+use crate::vectors::{EntityVector, VectorIndex};
+
+const DEFAULT_HEAP_SIZE: usize = 50_000_000; // 50MB
 
-const HEAP_SIZE: usize = 50_000_000; // 50MB
+/// Entity vectors, kept inside the index directory so a rebuild drops them too
+const VECTORS_FILE_NAME: &str = "vectors.bin";
+
+/// Segment merge policy used by the index writer
+#[derive(Debug, Clone)]
+pub enum MergePolicyConfig {
+    /// Tantivy's log merge policy, merging segments of similar size
+    Log {
+        /// Minimum number of segments merged together
+        min_num_segments: usize,
+        /// Segments with more documents than this are never merged
+        max_docs_before_merge: usize,
+        /// Ratio of deleted documents that makes a segment a merge candidate
+        del_docs_ratio_before_merge: f32,
+    },
+    /// Never merge segments
+    NoMerge,
+}
+
+impl Default for MergePolicyConfig {
+    fn default() -> Self {
+        MergePolicyConfig::Log {
+            min_num_segments: 8,
+            max_docs_before_merge: 10_000_000,
+            del_docs_ratio_before_merge: 1.0,
+        }
+    }
+}
+
+impl MergePolicyConfig {
+    fn build(&self) -> Box<dyn MergePolicy> {
+        match self {
+            MergePolicyConfig::Log {
+                min_num_segments,
+                max_docs_before_merge,
+                del_docs_ratio_before_merge,
+            } => {
+                let mut policy = LogMergePolicy::default();
+                policy.set_min_num_segments(*min_num_segments);
+                policy.set_max_docs_before_merge(*max_docs_before_merge);
+                policy.set_del_docs_ratio_before_merge(*del_docs_ratio_before_merge);
+                Box::new(policy)
+            }
+            MergePolicyConfig::NoMerge => Box::new(NoMergePolicy),
+        }
+    }
+}
+
+/// Settings for the long-lived index writer
+#[derive(Debug, Clone)]
+pub struct WriterSettings {
+    /// Memory budget of the writer in bytes
+    pub heap_size: usize,
+    /// Segment merge policy
+    pub merge_policy: MergePolicyConfig,
+    /// Inside a batch, commit automatically after this many crate operations
+    pub auto_commit_interval: Option<usize>,
+}
+
+impl Default for WriterSettings {
+    fn default() -> Self {
+        Self {
+            heap_size: DEFAULT_HEAP_SIZE,
+            merge_policy: MergePolicyConfig::default(),
+            auto_commit_interval: None,
+        }
+    }
+}
+
+impl WriterSettings {
+    /// Set the writer memory budget in bytes
+    pub fn with_heap_size(mut self, heap_size: usize) -> Self {
+        self.heap_size = heap_size;
+        self
+    }
+
+    /// Set the segment merge policy
+    pub fn with_merge_policy(mut self, merge_policy: MergePolicyConfig) -> Self {
+        self.merge_policy = merge_policy;
+        self
+    }
+
+    /// Set the auto-commit interval used inside batches
+    pub fn with_auto_commit_interval(mut self, interval: Option<usize>) -> Self {
+        self.auto_commit_interval = interval;
+        self
+    }
+}
 
 pub struct SearchIndex {
     pub index: Index,
     reader: IndexReader,
+    /// Created on first write and kept until `release_writer` or drop; it
+    /// holds tantivy's directory lock, so no other process can write meanwhile
+    writer: Option<IndexWriter>,
+    settings: WriterSettings,
+    /// Directory of a persistent index
+    path: Option<PathBuf>,
+    /// Entity vectors for semantic search, if an embedder is set
+    vectors: Option<VectorIndex>,
     pub(crate) id_field: Field,
     pub(crate) occurs_in_field: Field,
     pub(crate) entity_type_field: Field,
+    pub(crate) entity_type_exact_field: Field,
     pub(crate) content_field: Field,
     pub(crate) properties_field: Field,
+    pub(crate) summary_field: Field,
+    pub(crate) ancestors_field: Field,
+    pub(crate) sort_name_field: Field,
+    pub(crate) date_published_field: Field,
+    pub(crate) indexed_at_field: Field,
+    pub(crate) entity_count_field: Field,
+    pub(crate) suggest_field: Field,
 }
 
 impl SearchIndex {
     /// Create an in-memory index
     pub fn new_in_memory() -> Result<Self, IndexError> {
+        Self::new_in_memory_with(WriterSettings::default())
+    }
+
+    /// Create an in-memory index with custom writer settings
+    pub fn new_in_memory_with(settings: WriterSettings) -> Result<Self, IndexError> {
         let schema = Self::build_schema();
         let index = Index::create_in_ram(schema);
-        Self::from_index(index)
+        Self::from_index(index, settings, None)
     }
 
     /// Create or open a persistent index at the given path
     pub fn open_or_create(path: &Path) -> Result<Self, IndexError> {
+        Self::open_or_create_with(path, WriterSettings::default())
+    }
+
+    /// Create or open a persistent index with custom writer settings
+    pub fn open_or_create_with(path: &Path, settings: WriterSettings) -> Result<Self, IndexError> {
         let schema = Self::build_schema();
 
-        let index = if path.exists() {
+        let existing = if path.exists() {
             //println!("Opening existing index at {:?}", path);
-            Index::open_in_dir(path)?
+            Some(Index::open_in_dir(path)?)
         } else {
-            std::fs::create_dir_all(path)?;
-            let dir = MmapDirectory::open(path)?;
-            Index::create(dir, schema, IndexSettings::default())?
+            None
         };
 
-        Self::from_index(index)
+        let index = match existing {
+            Some(index) if index.schema() == schema => index,
+            outdated => {
+                // The index is derived data: an index built with an older schema
+                // is dropped and refilled from stored metadata by the caller
+                if outdated.is_some() {
+                    drop(outdated);
+                    std::fs::remove_dir_all(path)?;
+                }
+                std::fs::create_dir_all(path)?;
+                let dir = MmapDirectory::open(path)?;
+                Index::create(dir, schema, IndexSettings::default())?
+            }
+        };
+
+        Self::from_index(index, settings, Some(path.to_path_buf()))
     }
 
-    fn from_index(index: Index) -> Result<Self, IndexError> {
+    fn from_index(
+        index: Index,
+        settings: WriterSettings,
+        path: Option<PathBuf>,
+    ) -> Result<Self, IndexError> {
         let schema = index.schema();
-        let reader = index.reader()?;
+        // Reloaded explicitly in `commit`, so the reader generation (and with
+        // it any search cursor) only changes when the content does
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
 
         Ok(Self {
             id_field: schema.get_field("id").unwrap(),
             occurs_in_field: schema.get_field("occurs_in").unwrap(),
             entity_type_field: schema.get_field("entity_type").unwrap(),
+            entity_type_exact_field: schema.get_field("entity_type_exact").unwrap(),
             content_field: schema.get_field("content").unwrap(),
             properties_field: schema.get_field("properties").unwrap(),
+            summary_field: schema.get_field("summary").unwrap(),
+            ancestors_field: schema.get_field("ancestors").unwrap(),
+            sort_name_field: schema.get_field("sort_name").unwrap(),
+            date_published_field: schema.get_field("date_published").unwrap(),
+            indexed_at_field: schema.get_field("indexed_at").unwrap(),
+            entity_count_field: schema.get_field("entity_count").unwrap(),
+            suggest_field: schema.get_field("suggest").unwrap(),
             index,
             reader,
+            writer: None,
+            settings,
+            path,
+            vectors: None,
         })
     }
 
+    /// Compute entity vectors with `embedder` from now on
+    ///
+    /// Vectors stored by the same embedder are loaded; crates without
+    /// vectors (see `has_vectors`) need to be indexed again.
+    pub fn enable_vectors(&mut self, embedder: Arc<dyn Embedder>) {
+        let path = self.path.as_ref().map(|p| p.join(VECTORS_FILE_NAME));
+        self.vectors = Some(VectorIndex::open(embedder, path));
+    }
+
+    /// Entity vectors, if semantic search is enabled
+    pub(crate) fn vectors(&self) -> Option<&VectorIndex> {
+        self.vectors.as_ref()
+    }
+
+    /// Whether a crate's vectors are committed (always true without an embedder)
+    pub fn has_vectors(&self, crate_id: &str) -> bool {
+        self.vectors
+            .as_ref()
+            .is_none_or(|vectors| vectors.contains_crate(crate_id))
+    }
+
+    /// Drop the vectors of crates `keep` rejects (uncommitted), returning how many
+    pub fn retain_vectors(&mut self, keep: impl Fn(&str) -> bool) -> usize {
+        self.vectors
+            .as_mut()
+            .map_or(0, |vectors| vectors.retain_crates(keep))
+    }
+
     fn build_schema() -> Schema {
         let mut builder = Schema::builder();
 
@@ -68,19 +253,83 @@ impl SearchIndex {
         // Crate identifier this entity belongs to
         builder.add_text_field("occurs_in", STRING | STORED | FAST);
 
-        // @type values (multi-valued)
+        // IDs of the crates enclosing `occurs_in`, root first (multi-valued)
+        builder.add_text_field("ancestors", STRING);
+
+        // @type values plus all their known supertypes (multi-valued)
         builder.add_text_field("entity_type", STRING | FAST);
 
-        // Full-text content (not stored, just indexed)
-        builder.add_text_field("content", TEXT);
+        // Literal @type values only (multi-valued)
+        builder.add_text_field("entity_type_exact", STRING | FAST);
+
+        // Full-text content (stored for snippet generation)
+        builder.add_text_field("content", TEXT | STORED);
+
+        // Entity JSON: tokenized for search, raw fast columns for exact
+        // values, ranges and existence checks on property paths
+        builder.add_json_field("properties", TEXT | FAST);
 
-        builder.add_json_field("properties", TEXT);
+        // JSON-encoded EntitySummary returned with hits (stored, not indexed)
+        builder.add_text_field("summary", STORED);
+
+        // Sort keys: lowercased name, datePublished, crate index time and
+        // the number of entities in the crate
+        builder.add_text_field("sort_name", FAST);
+        builder.add_date_field("date_published", FAST);
+        builder.add_date_field("indexed_at", FAST);
+        builder.add_u64_field("entity_count", FAST);
+
+        // Type-ahead terms for names, keywords, authors and types (see `suggest`)
+        builder.add_text_field("suggest", STRING);
 
         builder.build()
     }
 
-    pub fn writer(&self) -> Result<IndexWriter, IndexError> {
-        Ok(self.index.writer(HEAP_SIZE)?)
+    /// Get the shared writer, creating it on first use
+    pub fn writer(&mut self) -> Result<&mut IndexWriter, IndexError> {
+        if self.writer.is_none() {
+            let writer = self.index.writer(self.settings.heap_size)?;
+            writer.set_merge_policy(self.settings.merge_policy.build());
+            self.writer = Some(writer);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    /// Writer settings this index was opened with
+    pub fn settings(&self) -> &WriterSettings {
+        &self.settings
+    }
+
+    /// Commit pending writes (if any) and make them visible to searchers
+    pub fn commit(&mut self) -> Result<(), IndexError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.commit()?;
+        }
+        if let Some(vectors) = self.vectors.as_mut() {
+            vectors.commit()?;
+        }
+        self.reload_reader()
+    }
+
+    /// Drop the writer and release the directory lock
+    ///
+    /// Writes made since the last `commit` are discarded.
+    pub fn release_writer(&mut self) -> Result<(), IndexError> {
+        if let Some(writer) = self.writer.take() {
+            writer.wait_merging_threads()?;
+        }
+        Ok(())
+    }
+
+    /// Discard all writes since the last commit
+    pub fn rollback(&mut self) -> Result<(), IndexError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.rollback()?;
+        }
+        if let Some(vectors) = self.vectors.as_mut() {
+            vectors.rollback();
+        }
+        Ok(())
     }
 
     pub fn reload_reader(&mut self) -> Result<(), IndexError> {
@@ -96,13 +345,39 @@ impl SearchIndex {
         self.index.schema()
     }
 
-    /// Index all entities from a crate's JSON-LD graph
+    /// Index all entities from a crate's JSON-LD graph (uncommitted)
+    ///
+    /// `ancestors` are the IDs of the crates enclosing this one, root first;
+    /// `indexed_at` is when the crate was added (Unix seconds), if known.
+    /// With an embedder, the crate's vectors are replaced as well.
     pub fn index_entities(
-        &self,
-        writer: &mut IndexWriter,
+        &mut self,
         crate_id: &str,
+        ancestors: &[String],
+        indexed_at: Option<i64>,
         entities: &[serde_json::Value],
     ) -> Result<usize, IndexError> {
+        let (id_field, occurs_in_field, properties_field, entity_type_field, content_field) = (
+            self.id_field,
+            self.occurs_in_field,
+            self.properties_field,
+            self.entity_type_field,
+            self.content_field,
+        );
+        let summary_field = self.summary_field;
+        let ancestors_field = self.ancestors_field;
+        let entity_type_exact_field = self.entity_type_exact_field;
+        let (sort_name_field, date_published_field, indexed_at_field, entity_count_field) = (
+            self.sort_name_field,
+            self.date_published_field,
+            self.indexed_at_field,
+            self.entity_count_field,
+        );
+        let suggest_field = self.suggest_field;
+        let entity_count = entities.iter().filter(|e| extract_id(e).is_some()).count() as u64;
+        let embed = self.vectors.is_some();
+        let mut texts = Vec::new();
+        let writer = self.writer()?;
         let mut count = 0;
 
         for entity in entities {
@@ -114,30 +389,168 @@ impl SearchIndex {
             let resolved_id = resolve_id(entity_id, crate_id);
             let types = extract_types(entity);
             let content = extract_text(entity);
+            let summary = extract_summary(entity);
 
             let mut doc = TantivyDocument::new();
-            doc.add_text(self.id_field, &resolved_id);
-            doc.add_text(self.occurs_in_field, crate_id);
-            doc.add_field_value(self.properties_field, entity);
+            doc.add_text(id_field, &resolved_id);
+            doc.add_text(occurs_in_field, crate_id);
+            for ancestor in ancestors {
+                doc.add_text(ancestors_field, ancestor);
+            }
+            doc.add_field_value(properties_field, entity);
+            doc.add_text(summary_field, serde_json::to_string(&summary)?);
+
+            if let Some(name) = &summary.name {
+                doc.add_text(sort_name_field, name.to_lowercase());
+            }
+            if let Some(published) = extract_date_published(entity) {
+                doc.add_date(
+                    date_published_field,
+                    DateTime::from_timestamp_secs(published),
+                );
+            }
+            if let Some(indexed_at) = indexed_at {
+                doc.add_date(indexed_at_field, DateTime::from_timestamp_secs(indexed_at));
+            }
+            doc.add_u64(entity_count_field, entity_count);
+            for term in suggestion_terms(entity) {
+                doc.add_text(suggest_field, term);
+            }
 
             for t in &types {
-                doc.add_text(self.entity_type_field, t);
+                doc.add_text(entity_type_exact_field, t);
+            }
+            for t in expand_types(&types) {
+                doc.add_text(entity_type_field, &t);
             }
 
             if !content.is_empty() {
-                doc.add_text(self.content_field, &content);
+                doc.add_text(content_field, &content);
+                if embed {
+                    texts.push((resolved_id, content));
+                }
             }
 
             writer.add_document(doc)?;
             count += 1;
         }
 
+        if let Some(vectors) = self.vectors.as_mut() {
+            let entity_vectors = texts
+                .into_iter()
+                .map(|(entity_id, text)| EntityVector {
+                    entity_id,
+                    vector: vectors.embedder().embed(&text),
+                })
+                .filter(|v| v.vector.iter().any(|&x| x != 0.0))
+                .collect();
+            vectors.stage_crate(crate_id, entity_vectors);
+        }
+
         Ok(count)
     }
 
-    /// Remove all indexed documents for a crate
-    pub fn remove_crate(&self, writer: &mut IndexWriter, crate_id: &str) {
+    /// Check if any committed documents exist for a crate
+    pub fn contains_crate(&self, crate_id: &str) -> Result<bool, IndexError> {
+        let term = Term::from_field_text(self.occurs_in_field, crate_id);
+        Ok(self.searcher().doc_freq(&term)? > 0)
+    }
+
+    /// Remove all indexed documents for a crate (uncommitted)
+    pub fn remove_crate(&mut self, crate_id: &str) -> Result<(), IndexError> {
         let term = Term::from_field_text(self.occurs_in_field, crate_id);
-        writer.delete_term(term);
+        self.writer()?.delete_term(term);
+        if let Some(vectors) = self.vectors.as_mut() {
+            vectors.stage_removal(crate_id);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl SearchIndex {
+    /// In-memory index with the entities of each crate indexed and committed
+    pub(crate) fn with_entities(crates: &[(&str, Vec<serde_json::Value>)]) -> Self {
+        let mut index = Self::new_in_memory().unwrap();
+        for (crate_id, entities) in crates {
+            index.index_entities(crate_id, &[], None, entities).unwrap();
+        }
+        index.commit().unwrap();
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::collector::Count;
+    use tantivy::query::AllQuery;
+
+    fn doc_count(index: &SearchIndex) -> usize {
+        index.searcher().search(&AllQuery, &Count).unwrap()
+    }
+
+    #[test]
+    fn test_writes_visible_after_commit() {
+        let mut index = SearchIndex::new_in_memory().unwrap();
+        let entities = vec![serde_json::json!({"@id": "./", "@type": "Dataset"})];
+
+        index
+            .index_entities("crate-a", &[], None, &entities)
+            .unwrap();
+        index
+            .index_entities("crate-b", &[], None, &entities)
+            .unwrap();
+        assert_eq!(doc_count(&index), 0);
+
+        index.commit().unwrap();
+        assert_eq!(doc_count(&index), 2);
+
+        index.remove_crate("crate-a").unwrap();
+        index.commit().unwrap();
+        assert_eq!(doc_count(&index), 1);
+    }
+
+    #[test]
+    fn test_rollback_discards_pending_writes() {
+        let mut index = SearchIndex::new_in_memory_with(
+            WriterSettings::default()
+                .with_heap_size(15_000_000)
+                .with_merge_policy(MergePolicyConfig::NoMerge),
+        )
+        .unwrap();
+        let entities = vec![serde_json::json!({"@id": "./", "@type": "Dataset"})];
+
+        index
+            .index_entities("crate-a", &[], None, &entities)
+            .unwrap();
+        index.rollback().unwrap();
+        index.commit().unwrap();
+        assert_eq!(doc_count(&index), 0);
+    }
+
+    #[test]
+    fn test_release_writer_unlocks_directory() {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        let entities = vec![serde_json::json!({"@id": "./", "@type": "Dataset"})];
+
+        let mut first = SearchIndex::open_or_create(&dir).unwrap();
+        first
+            .index_entities("crate-a", &[], None, &entities)
+            .unwrap();
+        first.commit().unwrap();
+
+        let mut second = SearchIndex::open_or_create(&dir).unwrap();
+        assert!(second.writer().is_err());
+
+        first.release_writer().unwrap();
+        second
+            .index_entities("crate-b", &[], None, &entities)
+            .unwrap();
+        second.commit().unwrap();
+        assert_eq!(doc_count(&second), 2);
+
+        drop((first, second));
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,12 +1,28 @@
+pub mod aggregate;
 pub mod config;
+pub mod dsl;
+pub mod embed;
+pub mod entity_graph;
 pub mod error;
+pub mod explain;
 pub mod extract;
 pub mod index;
 pub mod loader;
+pub mod manifest_db;
+pub mod paging;
 pub mod query;
+pub mod rdf;
+pub mod registry;
+pub mod relations;
+pub mod sort;
+pub mod spelling;
+pub mod storage;
 pub mod store;
+pub mod suggest;
+pub mod types;
+pub mod vectors;
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::sync::{Arc, RwLock};
 
@@ -18,13 +34,31 @@ use crate::extract::{detect_subcrates_from_url, extract_root_metadata, get_subcr
 use crate::index::SearchIndex;
 use crate::loader::find_subcrate_metadata_in_zip;
 use crate::query::QueryEngine;
+use crate::rdf::RdfStore;
+use crate::registry::EntityRegistry;
+use crate::relations::RelationGraph;
 use crate::store::CrateStore;
 
 // Re-export key types for convenience
+pub use crate::aggregate::{Aggregates, Aggregation, AggregationResult, Bucket, DateInterval};
 pub use crate::config::{Config as IndexConfig, CrateEntry, Manifest};
-pub use crate::extract::SubcrateInfo;
+pub use crate::dsl::QueryDsl;
+pub use crate::embed::{Embedder, HashingEmbedder};
+pub use crate::entity_graph::{EntityGraph, EntityRef, PartTree};
+pub use crate::explain::{ExplanationNode, ScoreExplanation, TermContribution};
+pub use crate::extract::{EntitySummary, SubcrateInfo};
+pub use crate::index::{MergePolicyConfig, WriterSettings};
 pub use crate::loader::CrateSource;
-pub use crate::query::SearchHit;
+pub use crate::manifest_db::ManifestKey;
+pub use crate::query::{
+    CrateHit, CrateScope, CrateSearchPage, SearchHit, SearchOptions, SearchPage,
+};
+pub use crate::rdf::{CrateFormat, SparqlResults};
+pub use crate::registry::{EntityOccurrence, RegisteredEntity};
+pub use crate::relations::{CrateRelation, GraphFormat, RelationKind};
+pub use crate::sort::{SortField, SortKey, SortOrder};
+pub use crate::storage::{FsStorage, MemoryStorage, MetadataStorage, S3Config, S3Storage};
+pub use crate::suggest::{SuggestField, Suggestion};
 
 /// Thread-safe RO-Crate index
 pub type SharedCrateIndex = Arc<RwLock<CrateIndex>>;
@@ -42,78 +76,163 @@ pub struct AddResult {
 
 /// Main interface for indexing and searching RO-Crates
 pub struct CrateIndex {
-    config: Config,
+    storage: Box<dyn MetadataStorage>,
     manifest: Manifest,
     store: CrateStore,
     search_index: SearchIndex,
+    /// Crate metadata as RDF, one named graph per crate
+    rdf: RdfStore,
+    /// Occurrences of entities with absolute `@id`s across crates
+    registry: EntityRegistry,
+    /// Entity graph of each crate, built when it is indexed
+    graphs: HashMap<String, EntityGraph>,
+    /// Links between crates, e.g. `isBasedOn` or shared DOIs
+    relations: RelationGraph,
+    /// Whether commits and manifest saves are deferred until `commit_batch`
+    in_batch: bool,
+    /// Crate operations since the last commit
+    pending_ops: usize,
 }
 
 impl CrateIndex {
-    /// Create a new in-memory index (for testing)
+    /// Create a new in-memory index (for testing and embedding)
+    ///
+    /// Metadata is kept in RAM and nothing is written to disk.
     pub fn new_in_memory() -> Result<Self, IndexError> {
+        Self::new_in_memory_with(WriterSettings::default())
+    }
+
+    /// Create a new in-memory index with custom writer settings
+    pub fn new_in_memory_with(settings: WriterSettings) -> Result<Self, IndexError> {
         Ok(Self {
-            config: Config::from_current_dir()?,
+            storage: Box::new(MemoryStorage::new()),
             manifest: Manifest::default(),
             store: CrateStore::new(),
-            search_index: SearchIndex::new_in_memory()?,
+            search_index: SearchIndex::new_in_memory_with(settings)?,
+            rdf: RdfStore::new()?,
+            registry: EntityRegistry::new(),
+            graphs: HashMap::new(),
+            relations: RelationGraph::new(),
+            in_batch: false,
+            pending_ops: 0,
         })
     }
 
+    /// Compute entity vectors with `embedder`, enabling semantic search
+    ///
+    /// For persistent indexes use `IndexConfig::with_embedder` instead, so
+    /// that crates indexed before are embedded on open.
+    pub fn with_embedder(mut self, embedder: Arc<dyn Embedder>) -> Self {
+        self.search_index.enable_vectors(embedder);
+        self
+    }
+
     /// Open or create a persistent index in the current directory
     pub fn open_or_create() -> Result<Self, IndexError> {
-        let config = Config::from_current_dir()?;
-        config.ensure_directories()?;
+        Self::open_with_config(Config::from_current_dir()?)
+    }
 
-        let manifest = config.load_manifest()?;
-        let search_index = SearchIndex::open_or_create(&config.index_dir())?;
+    /// Open or create at a specific base path
+    pub fn open_or_create_at(base_path: &Path) -> Result<Self, IndexError> {
+        Self::open_with_config(Config::new(base_path.to_path_buf()))
+    }
 
-        let mut idx = Self {
-            config,
-            manifest,
-            store: CrateStore::new(),
-            search_index,
-        };
+    /// Open or create a persistent index from an explicit configuration
+    pub fn open_with_config(config: Config) -> Result<Self, IndexError> {
+        let storage = FsStorage::open(config.clone())?;
 
-        // Load all crate metadata into memory
-        idx.load_all_metadata()?;
+        let manifest = storage.load_manifest()?;
+        config.migrate_metadata_files(&manifest)?;
 
-        Ok(idx)
+        Self::open_with_storage(config, Box::new(storage))
     }
 
-    /// Open or create at a specific base path
-    pub fn open_or_create_at(base_path: &Path) -> Result<Self, IndexError> {
-        let config = Config::new(base_path.to_path_buf());
+    /// Open or create a persistent search index under `config`, keeping
+    /// metadata and the manifest in `storage`
+    ///
+    /// Crates listed in the manifest but missing from the local search index
+    /// (e.g. added by another replica sharing the storage) are indexed on open.
+    pub fn open_with_storage(
+        config: Config,
+        storage: Box<dyn MetadataStorage>,
+    ) -> Result<Self, IndexError> {
         config.ensure_directories()?;
 
-        let manifest = config.load_manifest()?;
-        let search_index = SearchIndex::open_or_create(&config.index_dir())?;
+        let manifest = storage.load_manifest()?;
+        let mut search_index = SearchIndex::open_or_create_with(
+            &config.index_dir(),
+            config.writer_settings().clone(),
+        )?;
+        if let Some(embedder) = config.embedder() {
+            search_index.enable_vectors(embedder.clone());
+        }
 
         let mut idx = Self {
-            config,
+            storage,
             manifest,
             store: CrateStore::new(),
             search_index,
+            rdf: RdfStore::new()?,
+            registry: EntityRegistry::new(),
+            graphs: HashMap::new(),
+            relations: RelationGraph::new(),
+            in_batch: false,
+            pending_ops: 0,
         };
 
+        // Load all crate metadata into memory
         idx.load_all_metadata()?;
 
         Ok(idx)
     }
 
-    /// Load all metadata files listed in manifest into memory
+    /// Load all metadata listed in manifest into memory, indexing crates
+    /// that are not yet in the search index (or have no vectors yet)
     fn load_all_metadata(&mut self) -> Result<(), IndexError> {
+        let mut reindexed = 0;
+
         for crate_id in self.manifest.crate_ids() {
-            let metadata_path = self.config.metadata_path_for_crate(&crate_id);
-            if metadata_path.exists() {
-                let content = std::fs::read_to_string(&metadata_path)?;
+            if let Some(content) = self.storage.get_metadata(&crate_id)? {
                 let crate_data: RoCrate = rocraters::ro_crate::read::read_crate_obj(&content, 0)
                     .map_err(|e| IndexError::LoadError {
-                        path: metadata_path.display().to_string(),
+                        path: crate_id.clone(),
                         reason: format!("{:#?}", e),
                     })?;
+
+                let entities = self.graph_to_json(&crate_data)?;
+                let indexed = self.search_index.contains_crate(&crate_id)?;
+                if !indexed || !self.search_index.has_vectors(&crate_id) {
+                    if indexed {
+                        self.search_index.remove_crate(&crate_id)?;
+                    }
+                    let entry = self.manifest.get(&crate_id);
+                    let ancestors = entry
+                        .map(|entry| entry.ancestors().to_vec())
+                        .unwrap_or_default();
+                    let indexed_at = entry.and_then(|entry| entry.indexed_at);
+                    self.search_index
+                        .index_entities(&crate_id, &ancestors, indexed_at, &entities)?;
+                    reindexed += 1;
+                }
+
+                self.registry.insert_crate(&crate_id, &entities);
+                self.relations.insert_crate(&crate_id, &entities);
+                self.graphs
+                    .insert(crate_id.clone(), EntityGraph::new(entities));
                 self.store.insert(crate_id.clone(), crate_data);
+                self.load_rdf(&crate_id, &content);
             }
         }
+
+        // Vectors of crates removed while no embedder was set
+        let manifest = &self.manifest;
+        let stale = self
+            .search_index
+            .retain_vectors(|crate_id| manifest.get(crate_id).is_some());
+
+        if reindexed > 0 || stale > 0 {
+            self.search_index.commit()?;
+        }
         Ok(())
     }
 
@@ -122,6 +241,51 @@ impl CrateIndex {
         Arc::new(RwLock::new(self))
     }
 
+    /// Start a batch: index commits and manifest saves are deferred until
+    /// `commit_batch` (or the configured auto-commit interval is reached)
+    pub fn begin_batch(&mut self) {
+        self.in_batch = true;
+    }
+
+    /// Commit all pending changes and end the current batch
+    ///
+    /// The index writer is released afterwards so other processes (e.g.
+    /// `rocrate-idx add` next to a running server) can take the write lock.
+    /// Outside a batch the writer is kept between operations and the lock
+    /// stays held until the index is dropped.
+    pub fn commit_batch(&mut self) -> Result<(), IndexError> {
+        self.in_batch = false;
+        self.commit()?;
+        self.search_index.release_writer()
+    }
+
+    /// Check if a batch is currently open
+    pub fn in_batch(&self) -> bool {
+        self.in_batch
+    }
+
+    /// Commit the search index and persist the manifest
+    pub fn commit(&mut self) -> Result<(), IndexError> {
+        self.search_index.commit()?;
+        self.storage.save_manifest(&self.manifest)?;
+        self.manifest.clear_changes();
+        self.pending_ops = 0;
+        Ok(())
+    }
+
+    /// Record a finished crate operation and commit if required
+    fn operation_done(&mut self) -> Result<(), IndexError> {
+        self.pending_ops += 1;
+
+        let auto_commit = self.search_index.settings().auto_commit_interval;
+        let interval_reached = auto_commit.is_some_and(|n| self.pending_ops >= n);
+
+        if !self.in_batch || interval_reached {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
     /// Check if a crate ID is already indexed (cycle detection)
     pub fn is_indexed(&self, crate_id: &str) -> bool {
         self.manifest.contains(crate_id)
@@ -152,9 +316,8 @@ impl CrateIndex {
 
         let (crate_data, raw_json, root_prefix) = loader::load_with_json(source)?;
 
-        // Save metadata to disk
-        let metadata_path = self.config.metadata_path_for_crate(&crate_id);
-        std::fs::write(&metadata_path, &raw_json)?;
+        // Save metadata
+        self.storage.put_metadata(&crate_id, &raw_json)?;
 
         // Convert to JSON for indexing and subcrate detection
         let entities = self.graph_to_json(&crate_data)?;
@@ -163,17 +326,21 @@ impl CrateIndex {
         let root_metadata = extract_root_metadata(&entities);
 
         // Index the parent crate
-        let entity_count = self.index_crate(&crate_id, &entities)?;
+        let indexed_at = chrono::Utc::now().timestamp();
+        let entity_count = self.index_crate(&crate_id, &ancestry, indexed_at, &entities)?;
 
         // Store in memory
         self.store.insert(crate_id.clone(), crate_data);
+        self.load_rdf(&crate_id, &raw_json);
 
         // Create and save manifest entry with ancestry and metadata
         let entry = CrateEntry::with_parent(crate_id.clone(), ancestry.clone())
             .with_name(root_metadata.name)
-            .with_description(root_metadata.description);
+            .with_description(root_metadata.description)
+            .with_source(Some(source.location()))
+            .with_indexed_at(Some(indexed_at));
         self.manifest.add_crate(entry);
-        self.config.save_manifest(&self.manifest)?;
+        self.operation_done()?;
 
         // Build ancestry for subcrates (current crate becomes part of their ancestry)
         let mut subcrate_ancestry = ancestry;
@@ -378,8 +545,7 @@ impl CrateIndex {
         let (crate_data, raw_json) = loader::load_from_directory_with_json(dir_path)?;
 
         // Save metadata
-        let metadata_path = self.config.metadata_path_for_crate(crate_id);
-        std::fs::write(&metadata_path, &raw_json)?;
+        self.storage.put_metadata(crate_id, &raw_json)?;
 
         // Convert and index
         let entities = self.graph_to_json(&crate_data)?;
@@ -387,17 +553,21 @@ impl CrateIndex {
         // Extract name and description
         let root_metadata = extract_root_metadata(&entities);
 
-        let entity_count = self.index_crate(crate_id, &entities)?;
+        let indexed_at = chrono::Utc::now().timestamp();
+        let entity_count = self.index_crate(crate_id, &ancestry, indexed_at, &entities)?;
 
         // Store
         self.store.insert(crate_id.to_string(), crate_data);
+        self.load_rdf(crate_id, &raw_json);
 
         // Create entry with ancestry
         let entry = CrateEntry::with_parent(crate_id.to_string(), ancestry.clone())
             .with_name(root_metadata.name)
-            .with_description(root_metadata.description);
+            .with_description(root_metadata.description)
+            .with_source(Some(dir_path.display().to_string()))
+            .with_indexed_at(Some(indexed_at));
         self.manifest.add_crate(entry);
-        self.config.save_manifest(&self.manifest)?;
+        self.operation_done()?;
 
         // Build ancestry for nested subcrates
         let mut subcrate_ancestry = ancestry;
@@ -414,10 +584,12 @@ impl CrateIndex {
         })
     }
 
-    /// Index entities for a crate
+    /// Index entities for a crate (committed by `operation_done`)
     fn index_crate(
         &mut self,
         crate_id: &str,
+        ancestry: &[String],
+        indexed_at: i64,
         entities: &[serde_json::Value],
     ) -> Result<usize, IndexError> {
         // Remove existing if present (update semantics)
@@ -425,23 +597,29 @@ impl CrateIndex {
             self.remove_from_index(crate_id)?;
         }
 
-        let mut writer = self.search_index.writer()?;
-        let count = self
-            .search_index
-            .index_entities(&mut writer, crate_id, entities)?;
-        writer.commit()?;
-        self.search_index.reload_reader()?;
+        self.registry.insert_crate(crate_id, entities);
+        self.relations.insert_crate(crate_id, entities);
+        self.graphs
+            .insert(crate_id.to_string(), EntityGraph::new(entities.to_vec()));
+        self.search_index
+            .index_entities(crate_id, ancestry, Some(indexed_at), entities)
+    }
 
-        Ok(count)
+    /// Load crate metadata into the RDF store, warning if it can't be converted
+    ///
+    /// Such crates are still indexed, they are just missing from SPARQL results.
+    fn load_rdf(&self, crate_id: &str, json: &str) {
+        if let Err(e) = self.rdf.insert_crate(crate_id, json) {
+            eprintln!(
+                "Warning: crate {} is not queryable with SPARQL: {}",
+                crate_id, e
+            );
+        }
     }
 
     /// Remove crate from search index only (not from store/manifest)
     fn remove_from_index(&mut self, crate_id: &str) -> Result<(), IndexError> {
-        let mut writer = self.search_index.writer()?;
-        self.search_index.remove_crate(&mut writer, crate_id);
-        writer.commit()?;
-        self.search_index.reload_reader()?;
-        Ok(())
+        self.search_index.remove_crate(crate_id)
     }
 
     /// Add a crate from a path (convenience method)
@@ -464,20 +642,19 @@ impl CrateIndex {
         // Remove from search index
         self.remove_from_index(crate_id)?;
 
-        // Remove from memory store
+        // Remove from memory store, RDF graphs, entity registry, entity graph and relations
         self.store.remove(crate_id);
+        self.rdf.remove_crate(crate_id)?;
+        self.registry.remove_crate(crate_id);
+        self.graphs.remove(crate_id);
+        self.relations.remove_crate(crate_id);
 
-        // Remove metadata file
-        let metadata_path = self.config.metadata_path_for_crate(crate_id);
-        if metadata_path.exists() {
-            std::fs::remove_file(metadata_path)?;
-        }
+        // Remove metadata file and sidecar
+        self.storage.delete_metadata(crate_id)?;
 
         // Update manifest
         self.manifest.remove_crate(crate_id);
-        self.config.save_manifest(&self.manifest)?;
-
-        Ok(())
+        self.operation_done()
     }
 
     /// Full-text search
@@ -485,38 +662,301 @@ impl CrateIndex {
         QueryEngine::new(&self.search_index).search(query, limit)
     }
 
-    /// Search by entity type
+    /// Query engine over this index, e.g. to scope searches to a crate subtree
+    pub fn query_engine(&self) -> QueryEngine<'_> {
+        QueryEngine::new(&self.search_index)
+    }
+
+    /// Full-text search returning one page of hits with the total hit count
+    pub fn search_page(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchPage, IndexError> {
+        self.query_engine()
+            .with_scope(options.scope.clone())
+            .with_fuzziness(options.fuzziness)
+            .search_page(query, options)
+    }
+
+    /// Structured query search returning one page of hits
+    pub fn search_dsl(
+        &self,
+        query: &QueryDsl,
+        options: &SearchOptions,
+    ) -> Result<SearchPage, IndexError> {
+        self.query_engine()
+            .with_scope(options.scope.clone())
+            .search_dsl(query, options)
+    }
+
+    /// Run a SPARQL 1.1 query over the crates' metadata
+    ///
+    /// Each crate is a named graph (see `rdf::crate_iri`); without `FROM`
+    /// clauses the default graph is the union of all crates.
+    pub fn sparql(&self, query: &str) -> Result<SparqlResults, IndexError> {
+        self.rdf.query(query)
+    }
+
+    /// Type-ahead suggestions for names, keywords, authors and types
+    ///
+    /// With `field` set, only that suggester is consulted.
+    pub fn suggest(
+        &self,
+        prefix: &str,
+        field: Option<SuggestField>,
+        limit: usize,
+        scope: Option<CrateScope>,
+    ) -> Result<Vec<Suggestion>, IndexError> {
+        let fields = match field {
+            Some(field) => vec![field],
+            None => SuggestField::ALL.to_vec(),
+        };
+        self.query_engine()
+            .with_scope(scope)
+            .suggest(prefix, &fields, limit)
+    }
+
+    /// Aggregate over the entities matching a query string (all entities if `None`)
+    pub fn aggregate(
+        &self,
+        query: Option<&str>,
+        aggs: &BTreeMap<String, Aggregation>,
+        scope: Option<CrateScope>,
+    ) -> Result<Aggregates, IndexError> {
+        self.query_engine().with_scope(scope).aggregate(query, aggs)
+    }
+
+    /// Aggregate over the entities matching a structured query
+    pub fn aggregate_dsl(
+        &self,
+        query: &QueryDsl,
+        aggs: &BTreeMap<String, Aggregation>,
+        scope: Option<CrateScope>,
+    ) -> Result<Aggregates, IndexError> {
+        self.query_engine()
+            .with_scope(scope)
+            .aggregate_dsl(query, aggs)
+    }
+
+    /// Crate-level search: ranked crates with their best matching entities
+    pub fn search_crates(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<CrateSearchPage, IndexError> {
+        let mut page = self
+            .query_engine()
+            .with_scope(options.scope.clone())
+            .with_fuzziness(options.fuzziness)
+            .search_crates(query, options)?;
+        self.fill_crate_details(&mut page.hits);
+        Ok(page)
+    }
+
+    /// Crates with content similar to a crate, outside its own subtree
+    pub fn similar_crates(
+        &self,
+        crate_id: &str,
+        limit: usize,
+    ) -> Result<Vec<CrateHit>, IndexError> {
+        if self.manifest.get(crate_id).is_none() {
+            return Err(IndexError::CrateNotFound(crate_id.to_string()));
+        }
+        let mut hits = self.query_engine().similar_crates(crate_id, limit)?;
+        self.fill_crate_details(&mut hits);
+        Ok(hits)
+    }
+
+    /// Entities with content similar to an entity, outside the crates it occurs in
+    pub fn similar_entities(
+        &self,
+        entity_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.query_engine().similar_entities(entity_id, limit)
+    }
+
+    /// Explain the score of an entity in a crate for a query
+    ///
+    /// The scope and fuzziness of `options` apply as in `search_page`.
+    pub fn explain(
+        &self,
+        query: &str,
+        entity_id: &str,
+        crate_id: &str,
+        options: &SearchOptions,
+    ) -> Result<ScoreExplanation, IndexError> {
+        self.query_engine()
+            .with_scope(options.scope.clone())
+            .with_fuzziness(options.fuzziness)
+            .explain(query, entity_id, crate_id)
+    }
+
+    /// Add manifest names and descriptions to crate hits
+    fn fill_crate_details(&self, hits: &mut [CrateHit]) {
+        for hit in hits {
+            if let Some(entry) = self.manifest.get(&hit.crate_id) {
+                hit.name = entry.name.clone();
+                hit.description = entry.description.clone();
+            }
+        }
+    }
+
+    /// Search by entity type including schema.org subtypes, optionally within
+    /// a crate subtree
     pub fn search_by_type(
         &self,
         type_name: &str,
         limit: usize,
+        scope: Option<CrateScope>,
     ) -> Result<Vec<SearchHit>, IndexError> {
-        QueryEngine::new(&self.search_index).search_by_type(type_name, limit)
+        self.query_engine()
+            .with_scope(scope)
+            .search_by_type(type_name, limit)
     }
 
-    /// Search by entity ID (exact match)
-    pub fn search_by_id(&self, entity_id: &str) -> Result<Vec<SearchHit>, IndexError> {
-        QueryEngine::new(&self.search_index).search_by_id(entity_id)
+    /// Search by literal entity type, ignoring subtypes, optionally within a
+    /// crate subtree
+    pub fn search_by_type_exact(
+        &self,
+        type_name: &str,
+        limit: usize,
+        scope: Option<CrateScope>,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.query_engine()
+            .with_scope(scope)
+            .search_by_type_exact(type_name, limit)
+    }
+
+    /// Search by entity ID (exact match), optionally within a crate subtree
+    pub fn search_by_id(
+        &self,
+        entity_id: &str,
+        scope: Option<CrateScope>,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.query_engine()
+            .with_scope(scope)
+            .search_by_id(entity_id)
     }
 
-    /// Combined type + content search
+    /// Combined type (or subtype) + content search, optionally within a crate
+    /// subtree
     pub fn search_typed(
         &self,
         type_name: &str,
         content: &str,
         limit: usize,
+        scope: Option<CrateScope>,
     ) -> Result<Vec<SearchHit>, IndexError> {
-        QueryEngine::new(&self.search_index).search_typed_content(type_name, content, limit)
+        self.query_engine()
+            .with_scope(scope)
+            .search_typed_content(type_name, content, limit)
     }
 
-    /// Find all crate IDs containing a keyword
-    pub fn find_crates(&self, query: &str) -> Result<HashSet<String>, IndexError> {
-        QueryEngine::new(&self.search_index).find_crates(query)
+    /// Combined literal type + content search, ignoring subtypes, optionally
+    /// within a crate subtree
+    pub fn search_typed_exact(
+        &self,
+        type_name: &str,
+        content: &str,
+        limit: usize,
+        scope: Option<CrateScope>,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.query_engine()
+            .with_scope(scope)
+            .search_typed_content_exact(type_name, content, limit)
+    }
+
+    /// Find all crate IDs containing a keyword, optionally within a crate subtree
+    pub fn find_crates(
+        &self,
+        query: &str,
+        scope: Option<CrateScope>,
+    ) -> Result<HashSet<String>, IndexError> {
+        self.query_engine().with_scope(scope).find_crates(query)
+    }
+
+    /// Find all crates referencing an entity ID, optionally within a crate subtree
+    pub fn find_crates_by_entity(
+        &self,
+        entity_id: &str,
+        scope: Option<CrateScope>,
+    ) -> Result<HashSet<String>, IndexError> {
+        self.query_engine()
+            .with_scope(scope)
+            .find_crates_by_entity(entity_id)
     }
 
-    /// Find all crates referencing an entity ID
-    pub fn find_crates_by_entity(&self, entity_id: &str) -> Result<HashSet<String>, IndexError> {
-        QueryEngine::new(&self.search_index).find_crates_by_entity(entity_id)
+    /// Every crate an entity with an absolute `@id` (e.g. an ORCID) occurs in,
+    /// with the properties referencing it there and its merged names
+    pub fn registered_entity(&self, entity_id: &str) -> Option<RegisteredEntity> {
+        self.registry.get(entity_id)
+    }
+
+    /// Links between a crate and other indexed crates, both ways
+    ///
+    /// Beyond subcrate ancestry: `isBasedOn`, `citation`, `isPartOf`,
+    /// `mentions` and `hasPart` references resolving to the crates, and DOIs
+    /// both crates mention.
+    pub fn related_crates(&self, crate_id: &str) -> Result<Vec<CrateRelation>, IndexError> {
+        if !self.manifest.contains(crate_id) {
+            return Err(IndexError::CrateNotFound(crate_id.to_string()));
+        }
+        Ok(self.relations.related(crate_id))
+    }
+
+    /// Links between all indexed crates
+    pub fn crate_relations(&self) -> Vec<CrateRelation> {
+        self.relations.all()
+    }
+
+    /// Graph of the links between indexed crates as DOT or GraphML, with
+    /// crates labelled by name
+    pub fn relation_graph(&self, format: GraphFormat) -> String {
+        relations::render_graph(&self.crate_relations(), format, |crate_id| {
+            self.manifest
+                .get(crate_id)
+                .and_then(|entry| entry.name.clone())
+                .unwrap_or_else(|| crate_id.to_string())
+        })
+    }
+
+    /// Navigable graph of a crate's entities
+    pub fn entity_graph(&self, crate_id: &str) -> Result<&EntityGraph, IndexError> {
+        self.graphs
+            .get(crate_id)
+            .ok_or_else(|| IndexError::CrateNotFound(crate_id.to_string()))
+    }
+
+    /// An entity of a crate, with its references expanded `depth` levels deep
+    pub fn get_entity(
+        &self,
+        crate_id: &str,
+        entity_id: &str,
+        depth: usize,
+    ) -> Result<serde_json::Value, IndexError> {
+        self.entity_graph(crate_id)?.expand(entity_id, depth)
+    }
+
+    /// References from an entity of a crate to others
+    pub fn outgoing(&self, crate_id: &str, entity_id: &str) -> Result<Vec<EntityRef>, IndexError> {
+        self.entity_graph(crate_id)?.outgoing(entity_id)
+    }
+
+    /// References to an entity of a crate from others in it (backlinks)
+    pub fn incoming(&self, crate_id: &str, entity_id: &str) -> Result<Vec<EntityRef>, IndexError> {
+        self.entity_graph(crate_id)?.incoming(entity_id)
+    }
+
+    /// The `hasPart` tree below an entity of a crate, `depth` levels deep
+    pub fn part_tree(
+        &self,
+        crate_id: &str,
+        entity_id: &str,
+        depth: usize,
+    ) -> Result<PartTree, IndexError> {
+        self.entity_graph(crate_id)?.part_tree(entity_id, depth)
     }
 
     /// Get raw crate data from memory
@@ -529,12 +969,18 @@ impl CrateIndex {
         if !self.manifest.contains(crate_id) {
             return Ok(None);
         }
-        let metadata_path = self.config.metadata_path_for_crate(crate_id);
-        if metadata_path.exists() {
-            Ok(Some(std::fs::read_to_string(metadata_path)?))
-        } else {
-            Ok(None)
-        }
+        self.storage.get_metadata(crate_id)
+    }
+
+    /// Get crate metadata converted to an RDF serialisation
+    pub fn get_crate_as(
+        &self,
+        crate_id: &str,
+        format: CrateFormat,
+    ) -> Result<Option<String>, IndexError> {
+        self.get_crate_json(crate_id)?
+            .map(|json| rdf::convert_crate(crate_id, &json, format))
+            .transpose()
     }
 
     /// Get crate entry info from manifest
@@ -542,6 +988,28 @@ impl CrateIndex {
         self.manifest.get(crate_id)
     }
 
+    /// Find crate entries by parent, source or name
+    ///
+    /// Uses the storage backend's secondary indexes when available.
+    pub fn find_crate_entries(&self, key: ManifestKey<'_>) -> Result<Vec<&CrateEntry>, IndexError> {
+        if self.manifest.changes().is_empty()
+            && let Some(crate_ids) = self.storage.lookup(key)?
+        {
+            return Ok(crate_ids
+                .iter()
+                .filter_map(|id| self.manifest.get(id))
+                .collect());
+        }
+
+        // Backend has no index, or uncommitted changes are not in it yet
+        Ok(self
+            .manifest
+            .crates
+            .values()
+            .filter(|entry| key.matches(entry))
+            .collect())
+    }
+
     /// List all indexed crate IDs
     pub fn list_crates(&self) -> Vec<String> {
         self.manifest.crate_ids()
@@ -603,9 +1071,8 @@ impl CrateIndex {
             });
         }
 
-        // Save metadata to disk
-        let metadata_path = self.config.metadata_path_for_crate(&crate_id);
-        std::fs::write(&metadata_path, json_str)?;
+        // Save metadata
+        self.storage.put_metadata(&crate_id, json_str)?;
 
         // Convert to JSON for indexing
         let entities = self.graph_to_json(&crate_data)?;
@@ -614,17 +1081,20 @@ impl CrateIndex {
         let root_metadata = extract_root_metadata(&entities);
 
         // Index the crate
-        let entity_count = self.index_crate(&crate_id, &entities)?;
+        let indexed_at = chrono::Utc::now().timestamp();
+        let entity_count = self.index_crate(&crate_id, &[], indexed_at, &entities)?;
 
         // Store in memory
         self.store.insert(crate_id.clone(), crate_data);
+        self.load_rdf(&crate_id, json_str);
 
         // Create manifest entry (root level, no ancestry)
         let entry = CrateEntry::new(crate_id.clone())
             .with_name(root_metadata.name)
-            .with_description(root_metadata.description);
+            .with_description(root_metadata.description)
+            .with_indexed_at(Some(indexed_at));
         self.manifest.add_crate(entry);
-        self.config.save_manifest(&self.manifest)?;
+        self.operation_done()?;
 
         // For JSON uploads, check for URL subcrates only (no local file access)
         let url_subcrates: Vec<_> = detect_subcrates_from_url(&entities, None)
@@ -702,4 +1172,774 @@ mod tests {
         let index = CrateIndex::new_in_memory().unwrap();
         assert!(!index.is_indexed("test-crate"));
     }
+
+    fn batch_crate(name: &str) -> String {
+        serde_json::json!({
+            "@context": "https://w3id.org/ro/crate/1.1/context",
+            "@graph": [
+                {
+                    "@id": "ro-crate-metadata.json",
+                    "@type": "CreativeWork",
+                    "conformsTo": {"@id": "https://w3id.org/ro/crate/1.1"},
+                    "about": {"@id": "./"}
+                },
+                {"@id": "./", "@type": "Dataset", "name": name}
+            ]
+        })
+        .to_string()
+    }
+
+    fn open_temp(settings: WriterSettings) -> (CrateIndex, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        let config = Config::new(dir.clone()).with_writer_settings(settings);
+        (CrateIndex::open_with_config(config).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_batch_defers_commit_until_commit_batch() {
+        let (mut index, dir) = open_temp(WriterSettings::default());
+
+        index.begin_batch();
+        assert!(index.in_batch());
+        index
+            .add_from_json(&batch_crate("batched"), Some("a"))
+            .unwrap();
+        index
+            .add_from_json(&batch_crate("batched"), Some("b"))
+            .unwrap();
+        assert!(index.search("batched", 10).unwrap().is_empty());
+
+        index.commit_batch().unwrap();
+        assert!(!index.in_batch());
+        assert_eq!(index.search("batched", 10).unwrap().len(), 2);
+
+        // The writer is released, so a second process can open and write
+        let mut other = CrateIndex::open_with_config(Config::new(dir.clone())).unwrap();
+        assert_eq!(other.crate_count(), 2);
+        other
+            .add_from_json(&batch_crate("other"), Some("c"))
+            .unwrap();
+        assert_eq!(other.search("other", 10).unwrap().len(), 1);
+
+        drop((index, other));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_auto_commit_interval_inside_batch() {
+        let settings = WriterSettings::default().with_auto_commit_interval(Some(2));
+        let (mut index, dir) = open_temp(settings);
+
+        index.begin_batch();
+        index
+            .add_from_json(&batch_crate("batched"), Some("a"))
+            .unwrap();
+        assert!(index.search("batched", 10).unwrap().is_empty());
+        index
+            .add_from_json(&batch_crate("batched"), Some("b"))
+            .unwrap();
+        assert_eq!(index.search("batched", 10).unwrap().len(), 2);
+        index
+            .add_from_json(&batch_crate("batched"), Some("c"))
+            .unwrap();
+        assert_eq!(index.search("batched", 10).unwrap().len(), 2);
+
+        index.commit_batch().unwrap();
+        assert_eq!(index.search("batched", 10).unwrap().len(), 3);
+
+        drop(index);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_commits_each_operation_outside_batch() {
+        let (mut index, dir) = open_temp(WriterSettings::default());
+
+        let result = index
+            .add_from_json(&batch_crate("single"), Some("a"))
+            .unwrap();
+        assert_eq!(index.search("single", 10).unwrap().len(), 1);
+        index.remove(&result.crate_id).unwrap();
+        assert!(index.search("single", 10).unwrap().is_empty());
+
+        drop(index);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    const TEST_CRATE: &str = r#"{
+        "@context": "https://w3id.org/ro/crate/1.2/context",
+        "@graph": [
+            {
+                "@id": "ro-crate-metadata.json",
+                "@type": "CreativeWork",
+                "conformsTo": {"@id": "https://w3id.org/ro/crate/1.2"},
+                "about": {"@id": "./"}
+            },
+            {
+                "@id": "./",
+                "@type": "Dataset",
+                "name": "Escherichia coli growth curves",
+                "description": "Optical density measurements",
+                "datePublished": "2024-01-01",
+                "license": {"@id": "https://spdx.org/licenses/CC-BY-4.0"}
+            }
+        ]
+    }"#;
+
+    /// Metadata of a crate with a root dataset that has the given properties
+    fn crate_json(root: serde_json::Value) -> String {
+        let mut root = root;
+        root["@id"] = "./".into();
+        root["@type"] = "Dataset".into();
+        serde_json::json!({
+            "@context": "https://w3id.org/ro/crate/1.2/context",
+            "@graph": [
+                {
+                    "@id": "ro-crate-metadata.json",
+                    "@type": "CreativeWork",
+                    "conformsTo": {"@id": "https://w3id.org/ro/crate/1.2"},
+                    "about": {"@id": "./"}
+                },
+                root
+            ]
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_in_memory_add_and_remove() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+
+        let result = index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+        assert_eq!(index.crate_count(), 1);
+        assert_eq!(
+            index.get_crate_info(&result.crate_id).unwrap().name,
+            Some("Escherichia coli growth curves".to_string())
+        );
+        assert!(index.get_crate_json(&result.crate_id).unwrap().is_some());
+        assert!(!index.search("coli", 10).unwrap().is_empty());
+
+        index.remove(&result.crate_id).unwrap();
+        assert_eq!(index.crate_count(), 0);
+        assert!(index.get_crate_json(&result.crate_id).unwrap().is_none());
+        assert!(index.search("coli", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_registry_follows_adds_and_removes() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        let license_id = "https://spdx.org/licenses/CC-BY-4.0";
+
+        let result = index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+        let license = index.registered_entity(license_id).unwrap();
+        assert_eq!(license.occurrences[0].crate_id, result.crate_id);
+        assert!(license.occurrences[0].roles.contains("license"));
+
+        index.remove(&result.crate_id).unwrap();
+        assert!(index.registered_entity(license_id).is_none());
+    }
+
+    #[test]
+    fn test_entity_graph_of_crate() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        let result = index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+
+        let backlinks = index
+            .incoming(&result.crate_id, "https://spdx.org/licenses/CC-BY-4.0")
+            .unwrap();
+        assert_eq!(backlinks[0].entity_id, "./");
+        assert_eq!(backlinks[0].property, "license");
+        assert!(matches!(
+            index.incoming(&result.crate_id, "#nobody"),
+            Err(IndexError::EntityNotFound(_))
+        ));
+        let root = index.get_entity(&result.crate_id, "./", 1).unwrap();
+        assert_eq!(root["name"], "Escherichia coli growth curves");
+        let descriptor = index
+            .incoming(&result.crate_id, "./")
+            .unwrap()
+            .into_iter()
+            .find(|r| r.property == "about")
+            .unwrap();
+        assert_eq!(descriptor.entity_id, "ro-crate-metadata.json");
+
+        index.remove(&result.crate_id).unwrap();
+        assert!(matches!(
+            index.entity_graph(&result.crate_id),
+            Err(IndexError::CrateNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_related_crates() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        let source = crate_json(serde_json::json!({
+            "name": "Raw counts",
+            "identifier": "https://doi.org/10.1234/counts"
+        }));
+        let derived = crate_json(serde_json::json!({
+            "name": "Normalised counts",
+            "isBasedOn": {"@id": "doi:10.1234/COUNTS"}
+        }));
+        let source_id = index.add_from_json(&source, None).unwrap().crate_id;
+        let derived_id = index.add_from_json(&derived, None).unwrap().crate_id;
+
+        let related = index.related_crates(&source_id).unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].kind, RelationKind::IsBasedOn);
+        assert_eq!(related[0].source, derived_id);
+        let dot = index.relation_graph(GraphFormat::Dot);
+        assert!(dot.contains("[label=\"Normalised counts\"]"));
+
+        index.remove(&derived_id).unwrap();
+        assert!(index.related_crates(&source_id).unwrap().is_empty());
+        assert!(matches!(
+            index.related_crates(&derived_id),
+            Err(IndexError::CrateNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_sparql_follows_adds_and_removes() {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        let ask = "ASK { ?d <http://schema.org/name> \"Escherichia coli growth curves\" }";
+        let is_true = |index: &CrateIndex| index.sparql(ask).unwrap().body.contains("true");
+
+        let mut index = CrateIndex::open_or_create_at(&dir).unwrap();
+        let result = index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+        assert!(is_true(&index));
+
+        // The graphs are rebuilt from the stored metadata on open
+        drop(index);
+        let mut index = CrateIndex::open_or_create_at(&dir).unwrap();
+        assert!(is_true(&index));
+        let graph = rdf::crate_iri(&result.crate_id);
+        let select = format!("SELECT ?g WHERE {{ GRAPH ?g {{ <{}> a ?type }} }}", graph);
+        assert!(index.sparql(&select).unwrap().body.contains(&graph));
+
+        index.remove(&result.crate_id).unwrap();
+        assert!(!is_true(&index));
+
+        drop(index);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_pages_with_cursor() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        let mut crate_ids = Vec::new();
+        for i in 0..3 {
+            let result = index
+                .add_from_json(TEST_CRATE, Some(&format!("growth-{}", i)))
+                .unwrap();
+            crate_ids.push(result.crate_id);
+        }
+
+        let options = SearchOptions::default().with_limit(2);
+        let first = index.search_page("coli", &options).unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(first.hits.len(), 2);
+        let cursor = first.next_cursor.clone().expect("more hits");
+
+        let second = index
+            .search_page("coli", &options.clone().with_cursor(Some(cursor.clone())))
+            .unwrap();
+        assert_eq!(second.hits.len(), 1);
+        assert!(second.next_cursor.is_none());
+        assert!(
+            first
+                .hits
+                .iter()
+                .all(|h| h.crate_id != second.hits[0].crate_id)
+        );
+
+        // Offset paging agrees with cursor paging
+        let offset = index
+            .search_page("coli", &options.clone().with_offset(2))
+            .unwrap();
+        assert_eq!(offset.hits[0].crate_id, second.hits[0].crate_id);
+
+        // Client-supplied offsets and limits may be arbitrarily large
+        let all = index
+            .search_page("coli", &options.clone().with_limit(usize::MAX))
+            .unwrap();
+        assert_eq!(all.hits.len(), 3);
+        let past_end = options
+            .clone()
+            .with_offset(usize::MAX)
+            .with_limit(usize::MAX);
+        let page = index.search_page("coli", &past_end).unwrap();
+        assert_eq!(page.total, 3);
+        assert!(page.hits.is_empty());
+        assert!(page.next_cursor.is_none());
+
+        // Cursors are invalidated by a commit
+        index.remove(&crate_ids[0]).unwrap();
+        assert_eq!(index.search_page("coli", &options).unwrap().total, 2);
+        assert!(matches!(
+            index.search_page("coli", &options.with_cursor(Some(cursor))),
+            Err(IndexError::InvalidCursor(_))
+        ));
+    }
+
+    #[test]
+    fn test_hits_carry_summary_and_snippet() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+
+        let options = SearchOptions::default().with_snippets(true);
+        let page = index.search_page("optical", &options).unwrap();
+        let hit = &page.hits[0];
+
+        let entity = hit.entity.as_ref().unwrap();
+        assert_eq!(
+            entity.name.as_deref(),
+            Some("Escherichia coli growth curves")
+        );
+        assert_eq!(entity.types, vec!["Dataset"]);
+        assert!(hit.snippet.as_ref().unwrap().contains("<b>Optical</b>"));
+    }
+
+    #[test]
+    fn test_similar_crates_and_entities() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        let source = index
+            .add_from_json(
+                &crate_json(serde_json::json!({
+                    "name": "E. coli growth",
+                    "description": "Optical density of bacterial cultures"
+                })),
+                Some("growth"),
+            )
+            .unwrap()
+            .crate_id;
+        let similar = index
+            .add_from_json(
+                &crate_json(serde_json::json!({
+                    "name": "Yeast growth",
+                    "description": "Optical density of yeast cultures"
+                })),
+                Some("yeast"),
+            )
+            .unwrap()
+            .crate_id;
+        index
+            .add_from_json(
+                &crate_json(serde_json::json!({
+                    "name": "Soil survey",
+                    "description": "Nitrogen in arable soil"
+                })),
+                Some("soil"),
+            )
+            .unwrap();
+
+        let hits = index.similar_crates(&source, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].crate_id, similar);
+        assert_eq!(hits[0].name.as_deref(), Some("Yeast growth"));
+
+        let hits = index.similar_entities(&source, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entity_id, similar);
+
+        assert!(matches!(
+            index.similar_crates("missing", 10),
+            Err(IndexError::CrateNotFound(_))
+        ));
+        assert!(matches!(
+            index.similar_entities("#missing", 10),
+            Err(IndexError::EntityNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_hybrid_search() {
+        let mut index = CrateIndex::new_in_memory()
+            .unwrap()
+            .with_embedder(Arc::new(HashingEmbedder::default()));
+        let yeast = index
+            .add_from_json(
+                &crate_json(serde_json::json!({"name": "Yeast transcriptome atlas"})),
+                Some("yeast"),
+            )
+            .unwrap()
+            .crate_id;
+        index
+            .add_from_json(
+                &crate_json(serde_json::json!({"name": "Soil nitrogen survey"})),
+                Some("soil"),
+            )
+            .unwrap();
+
+        let keyword = SearchOptions::default().with_spellcheck_below(0);
+        assert_eq!(
+            index
+                .search_page("transcriptomics", &keyword)
+                .unwrap()
+                .total,
+            0
+        );
+
+        let hybrid = keyword.clone().with_semantic_weight(0.5);
+        let page = index.search_page("transcriptomics", &hybrid).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.hits[0].entity_id, yeast);
+
+        // Keyword matches still rank first
+        let page = index
+            .search_page("soil OR transcriptomics", &hybrid)
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(
+            page.hits[0].entity.as_ref().unwrap().name.as_deref(),
+            Some("Soil nitrogen survey")
+        );
+
+        // Huge pages are capped, weights outside 0..=1 are rejected
+        let huge = hybrid
+            .clone()
+            .with_offset(usize::MAX)
+            .with_limit(usize::MAX);
+        let page = index.search_page("transcriptomics", &huge).unwrap();
+        assert!(page.hits.is_empty());
+        for weight in [-0.5, 1.5, f32::NAN] {
+            let options = keyword.clone().with_semantic_weight(weight);
+            assert!(matches!(
+                index.search_page("transcriptomics", &options),
+                Err(IndexError::InvalidQuery { .. })
+            ));
+        }
+
+        index.remove(&yeast).unwrap();
+        assert_eq!(
+            index.search_page("transcriptomics", &hybrid).unwrap().total,
+            0
+        );
+
+        let plain = CrateIndex::new_in_memory().unwrap();
+        assert!(matches!(
+            plain.search_page("transcriptomics", &hybrid),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fuzzy_search_and_did_you_mean() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+
+        let options =
+            SearchOptions::default().with_spellcheck_below(SearchOptions::SPELLCHECK_BELOW);
+        let page = index.search_page("escherichai", &options).unwrap();
+        assert_eq!(page.total, 0);
+        assert_eq!(page.did_you_mean.as_deref(), Some("escherichia"));
+
+        let fuzzy = options.clone().with_fuzziness(1);
+        let page = index.search_page("escherichai", &fuzzy).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.did_you_mean.as_deref(), Some("escherichia"));
+
+        let crates = index.search_crates("optcial", &options).unwrap();
+        assert_eq!(crates.did_you_mean.as_deref(), Some("optical"));
+
+        // Off unless requested
+        let page = index
+            .search_page("escherichai", &SearchOptions::default())
+            .unwrap();
+        assert!(page.did_you_mean.is_none());
+
+        assert!(matches!(
+            index.search_page("coli", &options.with_fuzziness(3)),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+        let options = SearchOptions::default();
+
+        let hit = index.search("optical OR name:coli", 1).unwrap().remove(0);
+        let explanation = index
+            .explain("optical OR name:coli", &hit.entity_id, &hit.crate_id, &options)
+            .unwrap();
+        assert!(explanation.matched);
+        assert!((explanation.score - hit.score).abs() < 1e-4);
+        assert!(explanation.parsed_query.contains("BooleanQuery"));
+        assert!(explanation.details.is_some());
+        assert!(explanation.matched_fields.contains(&"content".to_string()));
+        assert!(
+            explanation
+                .matched_fields
+                .contains(&"properties.name".to_string())
+        );
+        let terms: Vec<&str> = explanation.terms.iter().map(|t| t.term.as_str()).collect();
+        assert!(terms.contains(&"coli") && terms.contains(&"optical"));
+        assert!(explanation.terms.iter().all(|t| t.score > 0.0));
+
+        let missed = index
+            .explain("yeast", &hit.entity_id, &hit.crate_id, &options)
+            .unwrap();
+        assert!(!missed.matched);
+        assert_eq!(missed.score, 0.0);
+        assert!(missed.terms.is_empty() && missed.details.is_none());
+
+        assert!(matches!(
+            index.explain("coli", "#nobody", &hit.crate_id, &options),
+            Err(IndexError::EntityNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_crates_collapses_entities() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth-a")).unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth-b")).unwrap();
+
+        let options = SearchOptions::default().with_limit(1);
+        let first = index.search_crates("coli OR optical", &options).unwrap();
+        assert_eq!(first.total, 2);
+        assert_eq!(first.hits.len(), 1);
+        assert_eq!(
+            first.hits[0].name.as_deref(),
+            Some("Escherichia coli growth curves")
+        );
+        assert!(!first.hits[0].entities.is_empty());
+
+        let second = index
+            .search_crates(
+                "coli OR optical",
+                &options.with_cursor(first.next_cursor.clone()),
+            )
+            .unwrap();
+        assert_eq!(second.hits.len(), 1);
+        assert_ne!(second.hits[0].crate_id, first.hits[0].crate_id);
+        assert!(second.next_cursor.is_none());
+
+        assert_eq!(index.find_crates("coli", None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_search_crates_past_the_end() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+
+        // Client-supplied offsets and limits may be arbitrarily large
+        let past_end = SearchOptions::default()
+            .with_offset(usize::MAX)
+            .with_limit(usize::MAX);
+        let crates = index.search_crates("coli", &past_end).unwrap();
+        assert_eq!(crates.total, 1);
+        assert!(crates.hits.is_empty());
+        assert!(crates.next_cursor.is_none());
+    }
+
+    /// Serve `body` as JSON for every request, returning the base URL
+    fn serve_json(body: String) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_search_within_subtree() {
+        let base_url = serve_json(crate_json(serde_json::json!({
+            "name": "Escherichia coli subset"
+        })));
+
+        // Parent crate referencing the served crate as a subcrate
+        let mut parent_json: serde_json::Value = serde_json::from_str(TEST_CRATE).unwrap();
+        parent_json["@graph"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({
+                "@id": format!("{}/child/", base_url),
+                "@type": "Dataset",
+                "conformsTo": {"@id": "https://w3id.org/ro/crate/1.2"}
+            }));
+
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        let parent = index
+            .add_from_json(&parent_json.to_string(), Some("parent"))
+            .unwrap();
+        let other = index.add_from_json(TEST_CRATE, Some("other")).unwrap();
+        assert_eq!(parent.subcrates.len(), 1);
+        let child_id = parent.subcrates[0].crate_id.clone();
+        assert_eq!(
+            index.get_crate_info(&child_id).unwrap().ancestors(),
+            std::slice::from_ref(&parent.crate_id)
+        );
+
+        let crates = |scope: Option<CrateScope>| -> HashSet<String> {
+            let options = SearchOptions::default().with_limit(50).with_scope(scope);
+            index
+                .search_page("coli", &options)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.crate_id)
+                .collect()
+        };
+
+        assert_eq!(crates(None).len(), 3);
+        let subtree = crates(Some(CrateScope::new(parent.crate_id.clone())));
+        assert_eq!(
+            subtree,
+            HashSet::from([parent.crate_id.clone(), child_id.clone()])
+        );
+        let only = crates(Some(
+            CrateScope::new(parent.crate_id.clone()).with_descendants(false),
+        ));
+        assert_eq!(only, HashSet::from([parent.crate_id.clone()]));
+        assert!(!subtree.contains(&other.crate_id));
+
+        let child = || Some(CrateScope::new(child_id.clone()));
+        assert_eq!(index.search_by_type("Dataset", 10, None).unwrap().len(), 4);
+        let child_hits = index.search_by_type("Dataset", 10, child()).unwrap();
+        assert_eq!(child_hits.len(), 1);
+        let child_root = &child_hits[0].entity_id;
+        assert_eq!(index.search_by_id(child_root, child()).unwrap().len(), 1);
+        assert_eq!(
+            index
+                .search_typed("Dataset", "coli", 10, child())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            index.find_crates("coli", child()).unwrap(),
+            HashSet::from([child_id.clone()])
+        );
+        assert_eq!(
+            index.find_crates_by_entity(child_root, child()).unwrap(),
+            HashSet::from([child_id.clone()])
+        );
+    }
+
+    #[test]
+    fn test_type_search_matches_subtypes() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        index.add_from_json(TEST_CRATE, Some("growth")).unwrap();
+
+        // The metadata descriptor is a CreativeWork, the root a Dataset
+        assert_eq!(
+            index
+                .search_by_type("CreativeWork", 10, None)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            index
+                .search_by_type_exact("CreativeWork", 10, None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(index.search("entity_type:Thing", 10).unwrap().len(), 2);
+        assert_eq!(
+            index.search("entity_type_exact:Dataset", 10).unwrap().len(),
+            1
+        );
+
+        // Summaries keep the literal types
+        let hits = index.search_by_type_exact("Dataset", 10, None).unwrap();
+        assert_eq!(hits[0].entity.as_ref().unwrap().types, vec!["Dataset"]);
+    }
+
+    #[test]
+    fn test_search_sorted_by_fields() {
+        let mut index = CrateIndex::new_in_memory().unwrap();
+        for (id, name, date) in [
+            ("b", "beta growth", "2023-05-01"),
+            ("a", "Alpha growth", "2025"),
+            ("c", "gamma growth", "2024-02-29"),
+        ] {
+            let json = TEST_CRATE
+                .replace("Escherichia coli growth curves", name)
+                .replace("2024-01-01", date);
+            index.add_from_json(&json, Some(id)).unwrap();
+        }
+
+        // Crate IDs are generated with the given name as suffix
+        let name = |hit: &SearchHit| hit.crate_id.rsplit('/').next().unwrap().to_string();
+        let sorted = |sort: &str, options: SearchOptions| -> Vec<String> {
+            let options = options.with_sort(SortKey::parse_list(sort).unwrap());
+            index
+                .search_page("growth", &options)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| name(&h))
+                .collect()
+        };
+
+        assert_eq!(
+            sorted("datePublished:desc", SearchOptions::default()),
+            vec!["a", "c", "b"]
+        );
+        assert_eq!(
+            sorted("name", SearchOptions::default()),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            sorted("entityCount:desc,name:desc", SearchOptions::default()),
+            vec!["c", "b", "a"]
+        );
+
+        // Cursor paging follows the sort order
+        let options = SearchOptions::default()
+            .with_limit(2)
+            .with_sort(SortKey::parse_list("datePublished").unwrap());
+        let first = index.search_page("growth", &options).unwrap();
+        let second = index
+            .search_page("growth", &options.clone().with_cursor(first.next_cursor))
+            .unwrap();
+        let order: Vec<_> = first.hits.iter().chain(&second.hits).map(name).collect();
+        assert_eq!(order, vec!["b", "c", "a"]);
+        assert!(second.next_cursor.is_none());
+
+        // Huge client-supplied limits and offsets do not overflow
+        assert_eq!(
+            sorted("name", SearchOptions::default().with_limit(usize::MAX)),
+            vec!["a", "b", "c"]
+        );
+        let past_end = options.with_offset(usize::MAX).with_limit(usize::MAX);
+        let page = index.search_page("growth", &past_end).unwrap();
+        assert!(page.hits.is_empty());
+
+        // Entities without a date sort last
+        let page = index
+            .search_page(
+                "entity_type:Thing",
+                &SearchOptions::default()
+                    .with_limit(10)
+                    .with_sort(vec![SortKey::desc(SortField::DatePublished)]),
+            )
+            .unwrap();
+        assert_eq!(page.total, 6);
+        assert_eq!(name(&page.hits[0]), "a");
+        assert!(
+            page.hits[3..]
+                .iter()
+                .all(|h| h.entity.as_ref().unwrap().types == vec!["CreativeWork"])
+        );
+    }
 }
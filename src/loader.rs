@@ -96,6 +96,19 @@ impl CrateSource {
         }
     }
 
+    /// Human-readable location the crate is loaded from
+    pub fn location(&self) -> String {
+        match self {
+            CrateSource::Directory(path) => path.display().to_string(),
+            CrateSource::ZipFile { path, .. } => path.display().to_string(),
+            CrateSource::Url(url) => url.clone(),
+            CrateSource::ZipSubcrate {
+                zip_path, subpath, ..
+            } => format!("{}!{}", zip_path.display(), subpath),
+            CrateSource::UrlSubcrate { metadata_url, .. } => metadata_url.clone(),
+        }
+    }
+
     /// Get the base URL for resolving relative paths in subcrates
     pub fn base_url(&self) -> Option<String> {
         match self {
@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use redb::{
+    Database, MultimapTableDefinition, ReadableTable, ReadableTableMetadata, TableDefinition,
+    WriteTransaction,
+};
+
+use crate::config::{CrateEntry, Manifest};
+use crate::error::IndexError;
+
+/// crate_id -> JSON-encoded CrateEntry
+const CRATES: TableDefinition<&str, &str> = TableDefinition::new("crates");
+/// parent crate_id ("" for root crates) -> crate_id
+const BY_PARENT: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("by_parent");
+/// source location -> crate_id
+const BY_SOURCE: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("by_source");
+/// crate name -> crate_id
+const BY_NAME: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("by_name");
+
+/// Secondary manifest index to look crates up by
+#[derive(Debug, Clone, Copy)]
+pub enum ManifestKey<'a> {
+    /// Direct children of a crate ID, or root crates for `None`
+    Parent(Option<&'a str>),
+    /// Crates loaded from a source location
+    Source(&'a str),
+    /// Crates with an exact name
+    Name(&'a str),
+}
+
+impl ManifestKey<'_> {
+    /// Check whether an entry matches this key
+    pub fn matches(&self, entry: &CrateEntry) -> bool {
+        match self {
+            ManifestKey::Parent(parent) => entry.parent_id() == *parent,
+            ManifestKey::Source(source) => entry.source.as_deref() == Some(*source),
+            ManifestKey::Name(name) => entry.name.as_deref() == Some(*name),
+        }
+    }
+}
+
+/// Transactional manifest store backed by redb
+///
+/// redb locks the database file while it is open, so it is opened for each
+/// operation only. Other processes can open the same index in between.
+pub struct ManifestDb {
+    path: PathBuf,
+    /// Serialises the operations of this process, which open the file in turn
+    lock: Mutex<()>,
+}
+
+impl ManifestDb {
+    /// Open or create the manifest database at the given path
+    pub fn open(path: &Path) -> Result<Self, IndexError> {
+        let manifest_db = Self {
+            path: path.to_path_buf(),
+            lock: Mutex::new(()),
+        };
+
+        // Make sure all tables exist so read transactions can open them
+        manifest_db.with_db(|db| {
+            let txn = db.begin_write().map_err(db_error)?;
+            {
+                txn.open_table(CRATES).map_err(db_error)?;
+                txn.open_multimap_table(BY_PARENT).map_err(db_error)?;
+                txn.open_multimap_table(BY_SOURCE).map_err(db_error)?;
+                txn.open_multimap_table(BY_NAME).map_err(db_error)?;
+            }
+            txn.commit().map_err(db_error)
+        })?;
+
+        Ok(manifest_db)
+    }
+
+    /// Run `f` on the database, opened for the duration of the call
+    fn with_db<T>(
+        &self,
+        f: impl FnOnce(&Database) -> Result<T, IndexError>,
+    ) -> Result<T, IndexError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let db = Database::create(&self.path).map_err(db_error)?;
+        f(&db)
+    }
+
+    /// Check if the database holds no entries
+    pub fn is_empty(&self) -> Result<bool, IndexError> {
+        self.with_db(|db| {
+            let txn = db.begin_read().map_err(db_error)?;
+            let table = txn.open_table(CRATES).map_err(db_error)?;
+            table.is_empty().map_err(db_error)
+        })
+    }
+
+    /// Load all entries into a manifest
+    pub fn load(&self) -> Result<Manifest, IndexError> {
+        self.with_db(|db| {
+            let txn = db.begin_read().map_err(db_error)?;
+            let table = txn.open_table(CRATES).map_err(db_error)?;
+
+            let mut manifest = Manifest::default();
+            for item in table.iter().map_err(db_error)? {
+                let (_, value) = item.map_err(db_error)?;
+                let entry: CrateEntry = serde_json::from_str(value.value())?;
+                manifest.crates.insert(entry.crate_id.clone(), entry);
+            }
+            Ok(manifest)
+        })
+    }
+
+    /// Write the given crate IDs from the manifest in one transaction.
+    /// IDs missing from the manifest are deleted.
+    pub fn apply<'a>(
+        &self,
+        manifest: &Manifest,
+        crate_ids: impl IntoIterator<Item = &'a String>,
+    ) -> Result<(), IndexError> {
+        self.with_db(|db| {
+            let txn = db.begin_write().map_err(db_error)?;
+            for crate_id in crate_ids {
+                remove_entry(&txn, crate_id)?;
+                if let Some(entry) = manifest.get(crate_id) {
+                    insert_entry(&txn, entry)?;
+                }
+            }
+            txn.commit().map_err(db_error)
+        })
+    }
+
+    /// Replace the whole database content with the manifest
+    pub fn replace_all(&self, manifest: &Manifest) -> Result<(), IndexError> {
+        let mut crate_ids: HashSet<String> = self.load()?.crates.into_keys().collect();
+        crate_ids.extend(manifest.crates.keys().cloned());
+        self.apply(manifest, &crate_ids)
+    }
+
+    /// Look up crate IDs through a secondary index
+    pub fn lookup(&self, key: ManifestKey<'_>) -> Result<Vec<String>, IndexError> {
+        let (table, value) = match key {
+            ManifestKey::Parent(parent) => (BY_PARENT, parent.unwrap_or("")),
+            ManifestKey::Source(source) => (BY_SOURCE, source),
+            ManifestKey::Name(name) => (BY_NAME, name),
+        };
+
+        self.with_db(|db| {
+            let txn = db.begin_read().map_err(db_error)?;
+            let table = txn.open_multimap_table(table).map_err(db_error)?;
+            let mut crate_ids = Vec::new();
+            for item in table.get(value).map_err(db_error)? {
+                crate_ids.push(item.map_err(db_error)?.value().to_string());
+            }
+            Ok(crate_ids)
+        })
+    }
+}
+
+fn insert_entry(txn: &WriteTransaction, entry: &CrateEntry) -> Result<(), IndexError> {
+    let id = entry.crate_id.as_str();
+    let json = serde_json::to_string(entry)?;
+
+    txn.open_table(CRATES)
+        .map_err(db_error)?
+        .insert(id, json.as_str())
+        .map_err(db_error)?;
+    txn.open_multimap_table(BY_PARENT)
+        .map_err(db_error)?
+        .insert(entry.parent_id().unwrap_or(""), id)
+        .map_err(db_error)?;
+    if let Some(source) = &entry.source {
+        txn.open_multimap_table(BY_SOURCE)
+            .map_err(db_error)?
+            .insert(source.as_str(), id)
+            .map_err(db_error)?;
+    }
+    if let Some(name) = &entry.name {
+        txn.open_multimap_table(BY_NAME)
+            .map_err(db_error)?
+            .insert(name.as_str(), id)
+            .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+fn remove_entry(txn: &WriteTransaction, crate_id: &str) -> Result<(), IndexError> {
+    let old = {
+        let mut table = txn.open_table(CRATES).map_err(db_error)?;
+        let removed = table.remove(crate_id).map_err(db_error)?;
+        match removed {
+            Some(value) => serde_json::from_str::<CrateEntry>(value.value())?,
+            None => return Ok(()),
+        }
+    };
+
+    txn.open_multimap_table(BY_PARENT)
+        .map_err(db_error)?
+        .remove(old.parent_id().unwrap_or(""), crate_id)
+        .map_err(db_error)?;
+    if let Some(source) = &old.source {
+        txn.open_multimap_table(BY_SOURCE)
+            .map_err(db_error)?
+            .remove(source.as_str(), crate_id)
+            .map_err(db_error)?;
+    }
+    if let Some(name) = &old.name {
+        txn.open_multimap_table(BY_NAME)
+            .map_err(db_error)?
+            .remove(name.as_str(), crate_id)
+            .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+fn db_error(e: impl Into<redb::Error>) -> IndexError {
+    IndexError::Database(Box::new(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_lookup() {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = ManifestDb::open(&dir.join("manifest.redb")).unwrap();
+        assert!(db.is_empty().unwrap());
+
+        let mut manifest = Manifest::default();
+        manifest.add_crate(
+            CrateEntry::new("root".to_string())
+                .with_name(Some("Root".to_string()))
+                .with_source(Some("https://example.org/root".to_string())),
+        );
+        manifest.add_crate(
+            CrateEntry::with_parent("child".to_string(), vec!["root".to_string()])
+                .with_name(Some("Child".to_string())),
+        );
+        db.apply(&manifest, manifest.changes()).unwrap();
+
+        assert_eq!(db.load().unwrap().len(), 2);
+        assert_eq!(db.lookup(ManifestKey::Parent(None)).unwrap(), vec!["root"]);
+        assert_eq!(
+            db.lookup(ManifestKey::Parent(Some("root"))).unwrap(),
+            vec!["child"]
+        );
+        assert_eq!(
+            db.lookup(ManifestKey::Source("https://example.org/root"))
+                .unwrap(),
+            vec!["root"]
+        );
+        assert_eq!(
+            db.lookup(ManifestKey::Name("Child")).unwrap(),
+            vec!["child"]
+        );
+
+        // Renaming updates the secondary index, removal cleans it up
+        manifest.clear_changes();
+        manifest.add_crate(
+            CrateEntry::with_parent("child".to_string(), vec!["root".to_string()])
+                .with_name(Some("Renamed".to_string())),
+        );
+        manifest.remove_crate("root");
+        db.apply(&manifest, manifest.changes()).unwrap();
+
+        assert!(db.lookup(ManifestKey::Name("Child")).unwrap().is_empty());
+        assert_eq!(
+            db.lookup(ManifestKey::Name("Renamed")).unwrap(),
+            vec!["child"]
+        );
+        assert!(db.lookup(ManifestKey::Parent(None)).unwrap().is_empty());
+        assert_eq!(db.load().unwrap().len(), 1);
+
+        // The file isn't held open between operations
+        let other = ManifestDb::open(&dir.join("manifest.redb")).unwrap();
+        assert_eq!(other.load().unwrap().len(), 1);
+
+        drop(db);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,495 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::columnar::StrColumn;
+use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+use crate::error::IndexError;
+
+/// Position of the last hit of a page, used to fetch the following page.
+///
+/// Only valid for the reader generation it was issued for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchCursor {
+    pub generation: u64,
+    pub score: Score,
+    pub doc: DocAddress,
+}
+
+impl SearchCursor {
+    /// Encode as an opaque string for clients
+    pub fn encode(&self) -> String {
+        format!(
+            "{:x}.{:08x}.{:x}.{:x}",
+            self.generation,
+            self.score.to_bits(),
+            self.doc.segment_ord,
+            self.doc.doc_id
+        )
+    }
+
+    /// Decode a string produced by `encode`
+    pub fn decode(cursor: &str) -> Result<Self, IndexError> {
+        let invalid = || IndexError::InvalidCursor(format!("Malformed cursor: {}", cursor));
+        let parts: Vec<&str> = cursor.split('.').collect();
+        if parts.len() != 4 {
+            return Err(invalid());
+        }
+
+        let generation = u64::from_str_radix(parts[0], 16).map_err(|_| invalid())?;
+        let score_bits = u32::from_str_radix(parts[1], 16).map_err(|_| invalid())?;
+        let segment_ord = u32::from_str_radix(parts[2], 16).map_err(|_| invalid())?;
+        let doc_id = u32::from_str_radix(parts[3], 16).map_err(|_| invalid())?;
+
+        Ok(Self {
+            generation,
+            score: Score::from_bits(score_bits),
+            doc: DocAddress::new(segment_ord, doc_id),
+        })
+    }
+}
+
+/// A scored document, ordered by descending score then ascending address
+#[derive(Debug, Clone, Copy)]
+struct Ranked {
+    score: Score,
+    doc: DocAddress,
+}
+
+impl Ranked {
+    /// `Less` means `self` is ranked before `other`
+    fn rank_cmp(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .total_cmp(&self.score)
+            .then(self.doc.cmp(&other.doc))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank_cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank_cmp(other)
+    }
+}
+
+/// Result of a `PageCollector` run
+#[derive(Debug)]
+pub struct PageFruit {
+    /// Hits of the requested page, best first
+    pub docs: Vec<(Score, DocAddress)>,
+    /// Number of documents matching the query
+    pub total: usize,
+    /// Number of matching documents ranked after the cursor
+    pub remaining: usize,
+}
+
+/// Collects one page of hits with a total count in a single pass.
+///
+/// Ties in score are broken by document address, so the order is stable
+/// for a given reader generation and pages never overlap.
+pub struct PageCollector {
+    offset: usize,
+    limit: usize,
+    after: Option<Ranked>,
+}
+
+impl PageCollector {
+    pub fn new(offset: usize, limit: usize, after: Option<&SearchCursor>) -> Self {
+        Self {
+            offset,
+            limit,
+            after: after.map(|c| Ranked {
+                score: c.score,
+                doc: c.doc,
+            }),
+        }
+    }
+}
+
+pub struct PageSegmentCollector {
+    segment_ord: SegmentOrdinal,
+    capacity: usize,
+    after: Option<Ranked>,
+    heap: BinaryHeap<Ranked>,
+    total: usize,
+    remaining: usize,
+}
+
+impl SegmentCollector for PageSegmentCollector {
+    type Fruit = PageFruit;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        self.total += 1;
+
+        let ranked = Ranked {
+            score,
+            doc: DocAddress::new(self.segment_ord, doc),
+        };
+        if let Some(after) = &self.after
+            && ranked <= *after
+        {
+            return;
+        }
+        self.remaining += 1;
+
+        if self.capacity == 0 {
+            return;
+        }
+        if self.heap.len() < self.capacity {
+            self.heap.push(ranked);
+        } else if let Some(mut worst) = self.heap.peek_mut()
+            && ranked < *worst
+        {
+            *worst = ranked;
+        }
+    }
+
+    fn harvest(self) -> PageFruit {
+        PageFruit {
+            docs: self.heap.into_iter().map(|r| (r.score, r.doc)).collect(),
+            total: self.total,
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl Collector for PageCollector {
+    type Fruit = PageFruit;
+    type Child = PageSegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        _segment: &SegmentReader,
+    ) -> tantivy::Result<PageSegmentCollector> {
+        Ok(PageSegmentCollector {
+            segment_ord: segment_local_id,
+            capacity: self.offset.saturating_add(self.limit),
+            after: self.after,
+            heap: BinaryHeap::new(),
+            total: 0,
+            remaining: 0,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        true
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<PageFruit>) -> tantivy::Result<PageFruit> {
+        let mut ranked = Vec::new();
+        let mut total = 0;
+        let mut remaining = 0;
+
+        for fruit in segment_fruits {
+            total += fruit.total;
+            remaining += fruit.remaining;
+            ranked.extend(
+                fruit
+                    .docs
+                    .into_iter()
+                    .map(|(score, doc)| Ranked { score, doc }),
+            );
+        }
+
+        ranked.sort();
+        let docs = ranked
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .map(|r| (r.score, r.doc))
+            .collect();
+
+        Ok(PageFruit {
+            docs,
+            total,
+            remaining,
+        })
+    }
+}
+
+/// Weight of the non-best entity scores in a crate's score
+const CRATE_SCORE_TAIL_WEIGHT: Score = 0.1;
+
+/// Matching entities of one crate, collected per segment
+#[derive(Debug, Default)]
+pub struct CrateAgg {
+    /// Best entities, best first
+    top: Vec<Ranked>,
+    sum: Score,
+    count: usize,
+}
+
+impl CrateAgg {
+    fn push(&mut self, ranked: Ranked, capacity: usize) {
+        self.sum += ranked.score;
+        self.count += 1;
+
+        let pos = self.top.partition_point(|r| *r < ranked);
+        if pos < capacity {
+            self.top.insert(pos, ranked);
+            self.top.truncate(capacity);
+        }
+    }
+
+    fn merge(&mut self, other: CrateAgg, capacity: usize) {
+        self.sum += other.sum;
+        self.count += other.count;
+        self.top.extend(other.top);
+        self.top.sort();
+        self.top.truncate(capacity);
+    }
+
+    /// Best entity score plus a small bonus for every further match
+    fn score(&self) -> Score {
+        let best = self.top.first().map(|r| r.score).unwrap_or(0.0);
+        best + CRATE_SCORE_TAIL_WEIGHT * (self.sum - best)
+    }
+}
+
+/// A crate with its aggregated score and best matching entities
+#[derive(Debug, Clone)]
+pub struct CrateMatches {
+    pub crate_id: String,
+    pub score: Score,
+    /// Number of matching entities in the crate
+    pub matches: usize,
+    /// Best matching entities, best first
+    pub top: Vec<(Score, DocAddress)>,
+}
+
+/// Result of a `CrateCollector` run
+#[derive(Debug)]
+pub struct CrateFruit {
+    /// Crates of the requested page, best first
+    pub crates: Vec<CrateMatches>,
+    /// Number of crates with at least one matching entity
+    pub total: usize,
+    /// Number of matching crates ranked after the cursor
+    pub remaining: usize,
+}
+
+/// Collapses entity hits by `occurs_in` and collects one page of crates.
+///
+/// Crates are ranked by score, ties by the address of their best entity,
+/// which is unique per crate, so `SearchCursor` works for crate pages too.
+pub struct CrateCollector {
+    field: String,
+    offset: usize,
+    limit: usize,
+    top_n: usize,
+    after: Option<Ranked>,
+}
+
+impl CrateCollector {
+    pub fn new(
+        field: &str,
+        offset: usize,
+        limit: usize,
+        top_n: usize,
+        after: Option<&SearchCursor>,
+    ) -> Self {
+        Self {
+            field: field.to_string(),
+            offset,
+            limit,
+            top_n,
+            after: after.map(|c| Ranked {
+                score: c.score,
+                doc: c.doc,
+            }),
+        }
+    }
+
+    /// The best entity is always kept, it ranks the crate
+    fn capacity(&self) -> usize {
+        self.top_n.max(1)
+    }
+}
+
+pub struct CrateSegmentCollector {
+    segment_ord: SegmentOrdinal,
+    capacity: usize,
+    column: Option<StrColumn>,
+    crates: HashMap<u64, CrateAgg>,
+}
+
+impl SegmentCollector for CrateSegmentCollector {
+    type Fruit = HashMap<String, CrateAgg>;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        let Some(column) = &self.column else {
+            return;
+        };
+        let ranked = Ranked {
+            score,
+            doc: DocAddress::new(self.segment_ord, doc),
+        };
+        for ord in column.term_ords(doc) {
+            self.crates
+                .entry(ord)
+                .or_default()
+                .push(ranked, self.capacity);
+        }
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        let Some(column) = &self.column else {
+            return HashMap::new();
+        };
+        let mut crates = HashMap::with_capacity(self.crates.len());
+        for (ord, agg) in self.crates {
+            let mut crate_id = String::new();
+            if let Ok(true) = column.ord_to_str(ord, &mut crate_id) {
+                crates.insert(crate_id, agg);
+            }
+        }
+        crates
+    }
+}
+
+impl Collector for CrateCollector {
+    type Fruit = CrateFruit;
+    type Child = CrateSegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<CrateSegmentCollector> {
+        Ok(CrateSegmentCollector {
+            segment_ord: segment_local_id,
+            capacity: self.capacity(),
+            column: segment.fast_fields().str(&self.field)?,
+            crates: HashMap::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        true
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<HashMap<String, CrateAgg>>,
+    ) -> tantivy::Result<CrateFruit> {
+        let mut crates: HashMap<String, CrateAgg> = HashMap::new();
+        for fruit in segment_fruits {
+            for (crate_id, agg) in fruit {
+                match crates.get_mut(&crate_id) {
+                    Some(existing) => existing.merge(agg, self.capacity()),
+                    None => {
+                        crates.insert(crate_id, agg);
+                    }
+                }
+            }
+        }
+
+        let total = crates.len();
+        let mut ranked: Vec<(Ranked, String, CrateAgg)> = crates
+            .into_iter()
+            .filter_map(|(crate_id, agg)| {
+                let best = agg.top.first()?;
+                let rank = Ranked {
+                    score: agg.score(),
+                    doc: best.doc,
+                };
+                Some((rank, crate_id, agg))
+            })
+            .filter(|(rank, _, _)| self.after.is_none_or(|after| *rank > after))
+            .collect();
+        let remaining = ranked.len();
+
+        ranked.sort_by_key(|(rank, _, _)| *rank);
+        let crates = ranked
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .map(|(rank, crate_id, agg)| CrateMatches {
+                crate_id,
+                score: rank.score,
+                matches: agg.count,
+                top: agg
+                    .top
+                    .into_iter()
+                    .take(self.top_n)
+                    .map(|r| (r.score, r.doc))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(CrateFruit {
+            crates,
+            total,
+            remaining,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = SearchCursor {
+            generation: 42,
+            score: 1.25,
+            doc: DocAddress::new(3, 17),
+        };
+        assert_eq!(SearchCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(SearchCursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_rank_order() {
+        let a = Ranked {
+            score: 2.0,
+            doc: DocAddress::new(0, 5),
+        };
+        let b = Ranked {
+            score: 1.0,
+            doc: DocAddress::new(0, 1),
+        };
+        let c = Ranked {
+            score: 1.0,
+            doc: DocAddress::new(0, 2),
+        };
+        let mut ranked = vec![c, a, b];
+        ranked.sort();
+        assert_eq!(ranked, vec![a, b, c]);
+    }
+
+    #[test]
+    fn test_crate_agg_score() {
+        let mut agg = CrateAgg::default();
+        for (doc, score) in [(1, 1.0), (2, 3.0), (3, 2.0)] {
+            agg.push(
+                Ranked {
+                    score,
+                    doc: DocAddress::new(0, doc),
+                },
+                2,
+            );
+        }
+        assert_eq!(agg.count, 3);
+        assert_eq!(agg.top.len(), 2);
+        assert_eq!(agg.top[0].score, 3.0);
+        assert!((agg.score() - 3.3).abs() < 1e-5);
+    }
+}
@@ -1,13 +1,45 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use tantivy::{
-    Term,
-    collector::TopDocs,
-    query::{BooleanQuery, Occur, QueryParser, TermQuery},
-    schema::{IndexRecordOption, Value},
+    DocAddress, Term,
+    collector::{Count, DocSetCollector, TopDocs},
+    columnar::StrColumn,
+    query::{
+        AllQuery, BooleanQuery, ConstScoreQuery, MoreLikeThisQuery, Occur, Query, QueryParser,
+        TermQuery,
+    },
+    schema::{IndexRecordOption, OwnedValue, Value},
+    snippet::SnippetGenerator,
 };
 
+use crate::aggregate::{self, Aggregates, Aggregation};
+use crate::dsl::QueryDsl;
 use crate::error::IndexError;
+use crate::explain::{self, ScoreExplanation};
+use crate::extract::EntitySummary;
 use crate::index::SearchIndex;
+use crate::paging::{CrateCollector, CrateMatches, PageCollector, SearchCursor};
+use crate::sort::{SortCollector, SortKey};
+use crate::spelling;
+use crate::suggest::{self, SuggestField, Suggestion};
+use crate::vectors::VectorMatch;
+
+/// Minimum number of keyword and of vector candidates fused in hybrid search
+const HYBRID_CANDIDATES: usize = 100;
+
+/// Maximum number of keyword and of vector candidates fused in hybrid search
+const MAX_HYBRID_CANDIDATES: usize = 10_000;
+
+/// Vector matches less similar than this are noise, not candidates
+const MIN_SEMANTIC_SIMILARITY: f32 = 0.2;
+
+/// Most source entities read to build a more-like-this query
+const MAX_SIMILAR_SOURCE_DOCS: usize = 1000;
+
+/// Common words never used as more-like-this terms
+const STOP_WORDS: &[&str] = &[
+    "and", "are", "but", "for", "from", "has", "have", "into", "its", "not", "our", "that", "the",
+    "their", "these", "this", "those", "using", "was", "were", "which", "with",
+];
 
 /// A single search result
 #[derive(Debug, Clone)]
@@ -15,16 +47,256 @@ pub struct SearchHit {
     pub entity_id: String,
     pub crate_id: String,
     pub score: f32,
+    /// Compact projection of the entity (name, types, description)
+    pub entity: Option<EntitySummary>,
+    /// Highlighted HTML fragment of the matching content, if requested
+    pub snippet: Option<String>,
+}
+
+/// Pagination options for a search
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Maximum number of hits to return
+    pub limit: usize,
+    /// Number of hits to skip (applied after the cursor, if any)
+    pub offset: usize,
+    /// Continue after the last hit of a previous page
+    pub cursor: Option<String>,
+    /// Generate highlighted snippets for each hit
+    pub snippets: bool,
+    /// Number of matching entities returned per crate in crate-level search
+    pub entities_per_crate: usize,
+    /// Only search inside this crate (and, optionally, its subcrates)
+    pub scope: Option<CrateScope>,
+    /// Sort keys, most significant first; empty ranks by score
+    pub sort: Vec<SortKey>,
+    /// Maximum edits per content term for fuzzy matching (0 to 2; 0 is exact)
+    pub fuzziness: u8,
+    /// Offer a spelling correction when fewer hits than this match
+    /// (default 0, which disables it; see `SearchOptions::SPELLCHECK_BELOW`)
+    pub spellcheck_below: usize,
+    /// Share of vector similarity in the hit score, 0 (keyword only) to 1
+    ///
+    /// Above 0, hits are ranked by the fused score and paged by offset only;
+    /// needs an index with an embedder.
+    pub semantic_weight: f32,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            offset: 0,
+            cursor: None,
+            snippets: false,
+            entities_per_crate: 3,
+            scope: None,
+            sort: Vec::new(),
+            fuzziness: 0,
+            spellcheck_below: 0,
+            semantic_weight: 0.0,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Hit count below which interactive searches offer a spelling correction
+    pub const SPELLCHECK_BELOW: usize = 3;
+
+    /// Set the page size
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set the number of hits to skip
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set the search-after cursor
+    pub fn with_cursor(mut self, cursor: Option<String>) -> Self {
+        self.cursor = cursor;
+        self
+    }
+
+    /// Enable or disable highlighted snippets
+    pub fn with_snippets(mut self, snippets: bool) -> Self {
+        self.snippets = snippets;
+        self
+    }
+
+    /// Set how many matching entities are returned per crate
+    pub fn with_entities_per_crate(mut self, entities_per_crate: usize) -> Self {
+        self.entities_per_crate = entities_per_crate;
+        self
+    }
+
+    /// Restrict the search to a crate subtree
+    pub fn with_scope(mut self, scope: Option<CrateScope>) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Sort hits by fast fields instead of by score alone
+    pub fn with_sort(mut self, sort: Vec<SortKey>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Match content terms within this many edits of the query terms
+    pub fn with_fuzziness(mut self, fuzziness: u8) -> Self {
+        self.fuzziness = fuzziness;
+        self
+    }
+
+    /// Set the hit count below which a spelling correction is offered
+    ///
+    /// Finding a correction scans the term dictionary, so it is off by default.
+    pub fn with_spellcheck_below(mut self, spellcheck_below: usize) -> Self {
+        self.spellcheck_below = spellcheck_below;
+        self
+    }
+
+    /// Blend vector similarity into the ranking with this weight (0 to 1)
+    pub fn with_semantic_weight(mut self, semantic_weight: f32) -> Self {
+        self.semantic_weight = semantic_weight;
+        self
+    }
+}
+
+/// Restricts a search to the entities of one crate
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrateScope {
+    pub crate_id: String,
+    /// Also search the crate's subcrates, at any depth
+    pub include_descendants: bool,
+}
+
+impl CrateScope {
+    /// Scope to a crate and all of its descendants
+    pub fn new(crate_id: impl Into<String>) -> Self {
+        Self {
+            crate_id: crate_id.into(),
+            include_descendants: true,
+        }
+    }
+
+    /// Include or exclude the crate's descendants
+    pub fn with_descendants(mut self, include_descendants: bool) -> Self {
+        self.include_descendants = include_descendants;
+        self
+    }
+}
+
+/// One page of search results
+#[derive(Debug, Clone)]
+pub struct SearchPage {
+    /// Hits on this page
+    pub hits: Vec<SearchHit>,
+    /// Exact number of entities matching the query
+    pub total: usize,
+    /// Cursor for the next page, if there are more hits
+    pub next_cursor: Option<String>,
+    /// Spelling correction of the query, if it would match more entities
+    pub did_you_mean: Option<String>,
+}
+
+/// A crate matching a search, with its best matching entities
+#[derive(Debug, Clone)]
+pub struct CrateHit {
+    pub crate_id: String,
+    /// Best entity score plus a small bonus for further matches
+    pub score: f32,
+    /// Number of matching entities in the crate
+    pub matches: usize,
+    /// Best matching entities, best first
+    pub entities: Vec<SearchHit>,
+    /// Crate name from the manifest
+    pub name: Option<String>,
+    /// Crate description from the manifest
+    pub description: Option<String>,
+}
+
+/// One page of crate-level search results
+#[derive(Debug, Clone)]
+pub struct CrateSearchPage {
+    /// Crates on this page
+    pub hits: Vec<CrateHit>,
+    /// Number of crates matching the query
+    pub total: usize,
+    /// Cursor for the next page, if there are more crates
+    pub next_cursor: Option<String>,
+    /// Spelling correction of the query, if it would match more entities
+    pub did_you_mean: Option<String>,
 }
 
 /// Query builder and executor
 pub struct QueryEngine<'a> {
     index: &'a SearchIndex,
+    scope: Option<CrateScope>,
+    fuzziness: u8,
 }
 
 impl<'a> QueryEngine<'a> {
     pub fn new(index: &'a SearchIndex) -> Self {
-        Self { index }
+        Self {
+            index,
+            scope: None,
+            fuzziness: 0,
+        }
+    }
+
+    /// Restrict every search of this engine to a crate subtree
+    pub fn with_scope(mut self, scope: Option<CrateScope>) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Let query string terms match content terms within this many edits
+    pub fn with_fuzziness(mut self, fuzziness: u8) -> Self {
+        self.fuzziness = fuzziness;
+        self
+    }
+
+    /// Apply the engine's crate scope as a non-scoring filter
+    fn scoped(&self, query: Box<dyn Query>) -> Box<dyn Query> {
+        let Some(scope) = &self.scope else {
+            return query;
+        };
+
+        let filter = self.subtree_query(&scope.crate_id, scope.include_descendants);
+        Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Must, Box::new(ConstScoreQuery::new(filter, 0.0))),
+        ]))
+    }
+
+    /// Entities of a crate and, optionally, of its descendants
+    fn subtree_query(&self, crate_id: &str, include_descendants: bool) -> Box<dyn Query> {
+        let term_query = |field| -> Box<dyn Query> {
+            Box::new(TermQuery::new(
+                Term::from_field_text(field, crate_id),
+                IndexRecordOption::Basic,
+            ))
+        };
+        if include_descendants {
+            Box::new(BooleanQuery::new(vec![
+                (Occur::Should, term_query(self.index.occurs_in_field)),
+                (Occur::Should, term_query(self.index.ancestors_field)),
+            ]))
+        } else {
+            term_query(self.index.occurs_in_field)
+        }
+    }
+
+    /// Run a scoped query and collect the best hits
+    fn top_hits(&self, query: Box<dyn Query>, limit: usize) -> Result<Vec<SearchHit>, IndexError> {
+        let query = self.scoped(query);
+        let searcher = self.index.searcher();
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        self.collect_hits(&searcher, top_docs)
     }
 
     /// Preprocess a query - currently just passes through
@@ -48,49 +320,577 @@ impl<'a> QueryEngine<'a> {
         query_str.to_string()
     }
 
-    /// Full-text search across content
-    pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<SearchHit>, IndexError> {
+    /// Parse a query string against the default fields
+    fn parse_query(&self, query_str: &str) -> Result<Box<dyn Query>, IndexError> {
+        self.parse_query_with(query_str, self.fuzziness)
+    }
+
+    /// Parse a query string, matching content terms within `fuzziness` edits
+    ///
+    /// Fuzzy term queries score every match alike, so the exact query is
+    /// added as an optional clause to keep exact matches ranked first.
+    fn parse_query_with(
+        &self,
+        query_str: &str,
+        fuzziness: u8,
+    ) -> Result<Box<dyn Query>, IndexError> {
+        if fuzziness > 2 {
+            return Err(IndexError::InvalidQuery {
+                path: "fuzzy".to_string(),
+                reason: format!("fuzziness must be 0, 1 or 2, got {}", fuzziness),
+            });
+        }
         let processed_query = self.preprocess_query(query_str);
 
-        let parser = QueryParser::for_index(
+        let mut parser = QueryParser::for_index(
             &self.index.index,
             vec![self.index.content_field, self.index.properties_field],
         );
-        let query = parser.parse_query(&processed_query)?;
+        let exact = parser
+            .parse_query(&processed_query)
+            .map_err(|e| IndexError::query_parse(e, &processed_query))?;
+        if fuzziness == 0 {
+            return Ok(exact);
+        }
+        parser.set_field_fuzzy(self.index.content_field, false, fuzziness, true);
+        let fuzzy = parser
+            .parse_query(&processed_query)
+            .map_err(|e| IndexError::query_parse(e, &processed_query))?;
+        Ok(Box::new(BooleanQuery::new(vec![
+            (Occur::Should, exact),
+            (Occur::Should, fuzzy),
+        ])))
+    }
+
+    /// Spelling correction for a query with `total` hits, if one is due
+    ///
+    /// A correction is only offered when its exact query has hits, and at least
+    /// as many as the original, as counted by `count` within the engine's scope.
+    fn did_you_mean(
+        &self,
+        query_str: &str,
+        total: usize,
+        options: &SearchOptions,
+        count: impl Fn(&dyn Query) -> Result<usize, IndexError>,
+    ) -> Result<Option<String>, IndexError> {
+        if total >= options.spellcheck_below {
+            return Ok(None);
+        }
+        let Some(corrected) = spelling::correct_query(self.index, query_str)? else {
+            return Ok(None);
+        };
+        let query = self.parse_query_with(&corrected, 0)?;
+        let corrected_total = count(query.as_ref())?;
+        Ok((corrected_total > 0 && corrected_total >= total).then_some(corrected))
+    }
+
+    /// Full-text search across content
+    pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<SearchHit>, IndexError> {
+        let query = self.parse_query(query_str)?;
+        self.top_hits(query, limit)
+    }
+
+    /// Full-text search returning one page of hits with the total hit count
+    pub fn search_page(
+        &self,
+        query_str: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchPage, IndexError> {
+        if !(0.0..=1.0).contains(&options.semantic_weight) {
+            return Err(IndexError::InvalidQuery {
+                path: "semantic".to_string(),
+                reason: "the semantic weight must be between 0 and 1".to_string(),
+            });
+        }
+        let query = self.parse_query(query_str)?;
+        let mut page = if options.semantic_weight > 0.0 {
+            self.execute_hybrid(query_str, query.as_ref(), options)?
+        } else {
+            self.execute_page(query.as_ref(), options)?
+        };
+        page.did_you_mean = self.did_you_mean(query_str, page.total, options, |query| {
+            let query = self.scoped(query.box_clone());
+            Ok(self.index.searcher().search(query.as_ref(), &Count)?)
+        })?;
+        Ok(page)
+    }
 
+    /// Structured query search returning one page of hits
+    pub fn search_dsl(
+        &self,
+        query: &QueryDsl,
+        options: &SearchOptions,
+    ) -> Result<SearchPage, IndexError> {
+        let query = query.compile(self.index)?;
+        self.execute_page(query.as_ref(), options)
+    }
+
+    /// Run a query and collect the requested page
+    ///
+    /// Hits are ordered by score, ties by document address, so pages are
+    /// stable while the reader generation is unchanged.
+    fn execute_page(
+        &self,
+        query: &dyn Query,
+        options: &SearchOptions,
+    ) -> Result<SearchPage, IndexError> {
         let searcher = self.index.searcher();
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let generation = searcher.generation().generation_id();
+        let cursor = Self::decode_cursor(options, generation)?;
 
-        self.collect_hits(&searcher, top_docs)
+        let scoped = self.scoped(query.box_clone());
+        let fruit = if options.sort.is_empty() {
+            let collector = PageCollector::new(options.offset, options.limit, cursor.as_ref());
+            searcher.search(scoped.as_ref(), &collector)?
+        } else {
+            let mut collector =
+                SortCollector::new(options.sort.clone(), options.offset, options.limit);
+            if let Some(cursor) = &cursor {
+                collector = collector.with_after(cursor, &searcher)?;
+            }
+            searcher.search(scoped.as_ref(), &collector)?
+        };
+
+        let has_more = fruit.remaining > options.offset.saturating_add(fruit.docs.len());
+        let next_cursor = match fruit.docs.last() {
+            Some(&(score, doc)) if has_more => Some(
+                SearchCursor {
+                    generation,
+                    score,
+                    doc,
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+
+        let mut hits = self.collect_hits(&searcher, fruit.docs.clone())?;
+        if options.snippets {
+            let generator = SnippetGenerator::create(&searcher, query, self.index.content_field)?;
+            self.add_snippets(&searcher, &generator, &mut hits, &fruit.docs)?;
+        }
+
+        Ok(SearchPage {
+            hits,
+            total: fruit.total,
+            next_cursor,
+            did_you_mean: None,
+        })
     }
 
-    /// Search entities by @type
-    pub fn search_by_type(
+    /// Rank the union of keyword and nearest-vector hits by a fused score
+    ///
+    /// Each hit scores `(1 - w) * bm25 / best_bm25 + w * cosine`, `w` being
+    /// the semantic weight. Both candidate lists are cut to `offset + limit`
+    /// (at least `HYBRID_CANDIDATES`, at most `MAX_HYBRID_CANDIDATES`), and
+    /// `total` counts the fused candidates.
+    fn execute_hybrid(
         &self,
-        type_name: &str,
+        query_str: &str,
+        query: &dyn Query,
+        options: &SearchOptions,
+    ) -> Result<SearchPage, IndexError> {
+        let weight = options.semantic_weight;
+        let invalid = |reason: &str| IndexError::InvalidQuery {
+            path: "semantic".to_string(),
+            reason: reason.to_string(),
+        };
+        if options.cursor.is_some() || !options.sort.is_empty() {
+            return Err(invalid(
+                "semantic search is ranked by fused score and paged by offset",
+            ));
+        }
+        let Some(vectors) = self.index.vectors() else {
+            return Err(invalid("semantic search needs an index with an embedder"));
+        };
+
+        let candidates = options
+            .offset
+            .saturating_add(options.limit)
+            .clamp(HYBRID_CANDIDATES, MAX_HYBRID_CANDIDATES);
+        let searcher = self.index.searcher();
+        let mut fused: HashMap<DocAddress, f32> = HashMap::new();
+
+        let keyword = searcher.search(
+            self.scoped(query.box_clone()).as_ref(),
+            &TopDocs::with_limit(candidates),
+        )?;
+        let best = keyword.first().map_or(0.0, |&(score, _)| score);
+        if best > 0.0 {
+            for (score, doc) in keyword {
+                *fused.entry(doc).or_default() += (1.0 - weight) * score / best;
+            }
+        }
+
+        let in_scope = match &self.scope {
+            Some(scope) => Some(
+                self.matching_crates(
+                    self.subtree_query(&scope.crate_id, scope.include_descendants)
+                        .as_ref(),
+                )?,
+            ),
+            None => None,
+        };
+        let embedding = vectors.embedder().embed(query_str);
+        let nearest = vectors.search(&embedding, candidates, MIN_SEMANTIC_SIMILARITY, |id| {
+            in_scope.as_ref().is_none_or(|crates| crates.contains(id))
+        });
+        for (doc, similarity) in self.resolve_neighbours(&searcher, &nearest)? {
+            *fused.entry(doc).or_default() += weight * similarity;
+        }
+
+        let mut ranked: Vec<(f32, DocAddress)> =
+            fused.into_iter().map(|(doc, score)| (score, doc)).collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let total = ranked.len();
+        let docs: Vec<(f32, DocAddress)> = ranked
+            .into_iter()
+            .skip(options.offset)
+            .take(options.limit)
+            .collect();
+
+        let mut hits = self.collect_hits(&searcher, docs.clone())?;
+        if options.snippets {
+            let generator = SnippetGenerator::create(&searcher, query, self.index.content_field)?;
+            self.add_snippets(&searcher, &generator, &mut hits, &docs)?;
+        }
+
+        Ok(SearchPage {
+            hits,
+            total,
+            next_cursor: None,
+            did_you_mean: None,
+        })
+    }
+
+    /// Type-ahead suggestions starting with `prefix`, most frequent first
+    ///
+    /// With a scope, only values of entities in the crate subtree are
+    /// suggested and counted.
+    pub fn suggest(
+        &self,
+        prefix: &str,
+        fields: &[SuggestField],
+        limit: usize,
+    ) -> Result<Vec<Suggestion>, IndexError> {
+        let filter = self.scope.as_ref().map(|_| self.scoped(Box::new(AllQuery)));
+        suggest::suggest(self.index, prefix, fields, limit, filter.as_deref())
+    }
+
+    /// Aggregate over the entities matching a query string (all entities if `None`)
+    pub fn aggregate(
+        &self,
+        query_str: Option<&str>,
+        aggs: &BTreeMap<String, Aggregation>,
+    ) -> Result<Aggregates, IndexError> {
+        let query = match query_str {
+            Some(q) => self.parse_query(q)?,
+            None => Box::new(AllQuery),
+        };
+        aggregate::run(self.index, self.scoped(query).as_ref(), aggs)
+    }
+
+    /// Aggregate over the entities matching a structured query
+    pub fn aggregate_dsl(
+        &self,
+        query: &QueryDsl,
+        aggs: &BTreeMap<String, Aggregation>,
+    ) -> Result<Aggregates, IndexError> {
+        let query = query.compile(self.index)?;
+        aggregate::run(self.index, self.scoped(query).as_ref(), aggs)
+    }
+
+    /// Crate-level search: entity hits are collapsed by crate and ranked
+    pub fn search_crates(
+        &self,
+        query_str: &str,
+        options: &SearchOptions,
+    ) -> Result<CrateSearchPage, IndexError> {
+        if !options.sort.is_empty() {
+            return Err(IndexError::InvalidQuery {
+                path: "sort".to_string(),
+                reason: "crate search is always ranked by score".to_string(),
+            });
+        }
+        let query = self.parse_query(query_str)?;
+
+        let searcher = self.index.searcher();
+        let generation = searcher.generation().generation_id();
+        let cursor = Self::decode_cursor(options, generation)?;
+
+        let collector = CrateCollector::new(
+            "occurs_in",
+            options.offset,
+            options.limit,
+            options.entities_per_crate,
+            cursor.as_ref(),
+        );
+        let fruit = searcher.search(self.scoped(query.box_clone()).as_ref(), &collector)?;
+
+        let has_more = fruit.remaining > options.offset.saturating_add(fruit.crates.len());
+        let next_cursor = match fruit.crates.last() {
+            Some(last) if has_more => last.top.first().map(|&(_, doc)| {
+                SearchCursor {
+                    generation,
+                    score: last.score,
+                    doc,
+                }
+                .encode()
+            }),
+            _ => None,
+        };
+
+        let generator = if options.snippets {
+            Some(SnippetGenerator::create(
+                &searcher,
+                &query,
+                self.index.content_field,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(CrateSearchPage {
+            hits: self.crate_hits(&searcher, fruit.crates, generator.as_ref())?,
+            total: fruit.total,
+            next_cursor,
+            did_you_mean: self.did_you_mean(query_str, fruit.total, options, |query| {
+                Ok(self.matching_crates(query)?.len())
+            })?,
+        })
+    }
+
+    /// Explain the score of one entity occurrence for a query string
+    ///
+    /// The engine's scope and fuzziness apply as in a search; an entity
+    /// outside the scope is reported as not matching.
+    pub fn explain(
+        &self,
+        query_str: &str,
+        entity_id: &str,
+        crate_id: &str,
+    ) -> Result<ScoreExplanation, IndexError> {
+        let query = self.parse_query(query_str)?;
+        let searcher = self.index.searcher();
+        let occurrence = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.index.id_field, entity_id),
+                    IndexRecordOption::Basic,
+                )) as Box<dyn Query>,
+            ),
+            (Occur::Must, self.subtree_query(crate_id, false)),
+        ]);
+        let Some((_, doc)) = searcher
+            .search(&occurrence, &TopDocs::with_limit(1))?
+            .into_iter()
+            .next()
+        else {
+            return Err(IndexError::EntityNotFound(format!(
+                "{} in crate {}",
+                entity_id, crate_id
+            )));
+        };
+        let scoped = self.scoped(query.box_clone());
+        explain::explain(
+            &searcher,
+            scoped.as_ref(),
+            query.as_ref(),
+            doc,
+            entity_id,
+            crate_id,
+        )
+    }
+
+    /// Crates most similar to a crate, by the content of their entities
+    ///
+    /// The crate's own subtree is excluded from the results.
+    pub fn similar_crates(
+        &self,
+        crate_id: &str,
+        limit: usize,
+    ) -> Result<Vec<CrateHit>, IndexError> {
+        let source = TermQuery::new(
+            Term::from_field_text(self.index.occurs_in_field, crate_id),
+            IndexRecordOption::Basic,
+        );
+        let Some(query) = self.more_like_this(&source, self.subtree_query(crate_id, true))? else {
+            return Err(IndexError::CrateNotFound(crate_id.to_string()));
+        };
+
+        let searcher = self.index.searcher();
+        let collector = CrateCollector::new(
+            "occurs_in",
+            0,
+            limit,
+            SearchOptions::default().entities_per_crate,
+            None,
+        );
+        let fruit = searcher.search(self.scoped(query).as_ref(), &collector)?;
+        self.crate_hits(&searcher, fruit.crates, None)
+    }
+
+    /// Entities most similar to an entity, by content
+    ///
+    /// All occurrences of the entity are used as the source; they and the
+    /// subtrees of the crates they occur in are excluded from the results.
+    pub fn similar_entities(
+        &self,
+        entity_id: &str,
         limit: usize,
     ) -> Result<Vec<SearchHit>, IndexError> {
-        let term = Term::from_field_text(self.index.entity_type_field, type_name);
-        let query = TermQuery::new(term, IndexRecordOption::Basic);
+        let source = TermQuery::new(
+            Term::from_field_text(self.index.id_field, entity_id),
+            IndexRecordOption::Basic,
+        );
+        let mut exclude: Vec<(Occur, Box<dyn Query>)> =
+            vec![(Occur::Should, Box::new(source.clone()))];
+        for crate_id in self.matching_crates(&source)? {
+            exclude.push((Occur::Should, self.subtree_query(&crate_id, true)));
+        }
+        let Some(query) = self.more_like_this(&source, Box::new(BooleanQuery::new(exclude)))?
+        else {
+            return Err(IndexError::EntityNotFound(entity_id.to_string()));
+        };
+        self.top_hits(query, limit)
+    }
 
+    /// More-like-this query over the content of the entities matching `source`
+    ///
+    /// Returns `None` when nothing matches `source`.
+    fn more_like_this(
+        &self,
+        source: &dyn Query,
+        exclude: Box<dyn Query>,
+    ) -> Result<Option<Box<dyn Query>>, IndexError> {
         let searcher = self.index.searcher();
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let docs = searcher.search(source, &TopDocs::with_limit(MAX_SIMILAR_SOURCE_DOCS))?;
+        if docs.is_empty() {
+            return Ok(None);
+        }
 
-        self.collect_hits(&searcher, top_docs)
+        let mut content = Vec::with_capacity(docs.len());
+        for (_, doc_addr) in docs {
+            let doc: tantivy::TantivyDocument = searcher.doc(doc_addr)?;
+            if let Some(text) = doc
+                .get_first(self.index.content_field)
+                .and_then(|v| v.as_str())
+            {
+                content.push(OwnedValue::Str(text.to_string()));
+            }
+        }
+
+        let similar = MoreLikeThisQuery::builder()
+            .with_min_doc_frequency(2)
+            .with_min_term_frequency(1)
+            .with_min_word_length(3)
+            .with_max_query_terms(25)
+            .with_stop_words(STOP_WORDS.iter().map(|w| w.to_string()).collect())
+            .with_document_fields(vec![(self.index.content_field, content)]);
+        Ok(Some(Box::new(BooleanQuery::new(vec![
+            (Occur::Must, Box::new(similar)),
+            (Occur::MustNot, exclude),
+        ]))))
+    }
+
+    /// Build crate hits from collapsed matches, with snippets if a generator is given
+    fn crate_hits(
+        &self,
+        searcher: &tantivy::Searcher,
+        crates: Vec<CrateMatches>,
+        generator: Option<&SnippetGenerator>,
+    ) -> Result<Vec<CrateHit>, IndexError> {
+        let mut hits = Vec::with_capacity(crates.len());
+        for matches in crates {
+            let mut entities = self.collect_hits(searcher, matches.top.clone())?;
+            if let Some(generator) = generator {
+                self.add_snippets(searcher, generator, &mut entities, &matches.top)?;
+            }
+            hits.push(CrateHit {
+                crate_id: matches.crate_id,
+                score: matches.score,
+                matches: matches.matches,
+                entities,
+                name: None,
+                description: None,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Decode the cursor of a request, checking it belongs to this reader generation
+    fn decode_cursor(
+        options: &SearchOptions,
+        generation: u64,
+    ) -> Result<Option<SearchCursor>, IndexError> {
+        let Some(cursor) = &options.cursor else {
+            return Ok(None);
+        };
+        let cursor = SearchCursor::decode(cursor)?;
+        if cursor.generation != generation {
+            return Err(IndexError::InvalidCursor(
+                "the index changed since the cursor was issued".to_string(),
+            ));
+        }
+        Ok(Some(cursor))
+    }
+
+    /// Attach highlighted snippets to hits collected from `docs`
+    fn add_snippets(
+        &self,
+        searcher: &tantivy::Searcher,
+        generator: &SnippetGenerator,
+        hits: &mut [SearchHit],
+        docs: &[(f32, tantivy::DocAddress)],
+    ) -> Result<(), IndexError> {
+        for (hit, (_, doc_addr)) in hits.iter_mut().zip(docs) {
+            let doc: tantivy::TantivyDocument = searcher.doc(*doc_addr)?;
+            let snippet = generator.snippet_from_doc(&doc);
+            if !snippet.is_empty() {
+                hit.snippet = Some(snippet.to_html());
+            }
+        }
+        Ok(())
+    }
+
+    /// Query for a @type, optionally ignoring subtypes
+    fn type_query(&self, type_name: &str, exact: bool) -> TermQuery {
+        let field = if exact {
+            self.index.entity_type_exact_field
+        } else {
+            self.index.entity_type_field
+        };
+        let term = Term::from_field_text(field, type_name);
+        TermQuery::new(term, IndexRecordOption::Basic)
+    }
+
+    /// Search entities by @type, including subtypes (e.g. `CreativeWork` matches `Dataset`)
+    pub fn search_by_type(
+        &self,
+        type_name: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.top_hits(Box::new(self.type_query(type_name, false)), limit)
+    }
+
+    /// Search entities whose literal @type matches, ignoring subtypes
+    pub fn search_by_type_exact(
+        &self,
+        type_name: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.top_hits(Box::new(self.type_query(type_name, true)), limit)
     }
 
     /// Find all occurrences of an entity by @id
     pub fn search_by_id(&self, entity_id: &str) -> Result<Vec<SearchHit>, IndexError> {
         let term = Term::from_field_text(self.index.id_field, entity_id);
         let query = TermQuery::new(term, IndexRecordOption::Basic);
-
-        let searcher = self.index.searcher();
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(1000))?;
-
-        self.collect_hits(&searcher, top_docs)
+        self.top_hits(Box::new(query), 1000)
     }
 
-    /// Combined search: type + content
+    /// Combined search: type (or a subtype) + content
     /// E.g., find all Person entities matching "Smith"
     pub fn search_typed_content(
         &self,
@@ -98,43 +898,130 @@ impl<'a> QueryEngine<'a> {
         content_query: &str,
         limit: usize,
     ) -> Result<Vec<SearchHit>, IndexError> {
-        let type_term = Term::from_field_text(self.index.entity_type_field, type_name);
-        let type_query = TermQuery::new(type_term, IndexRecordOption::Basic);
+        self.typed_content(type_name, false, content_query, limit)
+    }
 
-        let processed_query = self.preprocess_query(content_query);
-        let content_parser = QueryParser::for_index(
-            &self.index.index,
-            vec![self.index.content_field, self.index.properties_field],
-        );
-        let content_query = content_parser.parse_query(&processed_query)?;
+    /// Combined search: literal type + content
+    pub fn search_typed_content_exact(
+        &self,
+        type_name: &str,
+        content_query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        self.typed_content(type_name, true, content_query, limit)
+    }
+
+    fn typed_content(
+        &self,
+        type_name: &str,
+        exact: bool,
+        content_query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, IndexError> {
+        let type_query = self.type_query(type_name, exact);
+
+        let content_query = self.parse_query(content_query)?;
 
         let combined = BooleanQuery::new(vec![
             (Occur::Must, Box::new(type_query)),
             (Occur::Must, content_query),
         ]);
-
-        let searcher = self.index.searcher();
-        let top_docs = searcher.search(&combined, &TopDocs::with_limit(limit))?;
-
-        self.collect_hits(&searcher, top_docs)
+        self.top_hits(Box::new(combined), limit)
     }
 
     /// Get unique crate IDs matching a content query
     pub fn find_crates(&self, query_str: &str) -> Result<HashSet<String>, IndexError> {
-        let hits = self.search(query_str, 10_000)?;
-        Ok(hits.into_iter().map(|h| h.crate_id).collect())
+        let query = self.parse_query(query_str)?;
+        self.matching_crates(query.as_ref())
     }
 
-    /// Get all crates containing entities of a specific type
+    /// Get all crates containing entities of a specific type (or a subtype)
     pub fn find_crates_by_type(&self, type_name: &str) -> Result<HashSet<String>, IndexError> {
-        let hits = self.search_by_type(type_name, 10_000)?;
-        Ok(hits.into_iter().map(|h| h.crate_id).collect())
+        self.matching_crates(&self.type_query(type_name, false))
+    }
+
+    /// All crate IDs with at least one entity matching the query
+    fn matching_crates(&self, query: &dyn Query) -> Result<HashSet<String>, IndexError> {
+        let collector = CrateCollector::new("occurs_in", 0, usize::MAX, 0, None);
+        let query = self.scoped(query.box_clone());
+        let fruit = self.index.searcher().search(query.as_ref(), &collector)?;
+        Ok(fruit.crates.into_iter().map(|c| c.crate_id).collect())
     }
 
     /// Get all crates referencing a specific entity ID (e.g., an ORCID)
     pub fn find_crates_by_entity(&self, entity_id: &str) -> Result<HashSet<String>, IndexError> {
-        let hits = self.search_by_id(entity_id)?;
-        Ok(hits.into_iter().map(|h| h.crate_id).collect())
+        let term = Term::from_field_text(self.index.id_field, entity_id);
+        let query = TermQuery::new(term, IndexRecordOption::Basic);
+        self.matching_crates(&query)
+    }
+
+    /// Find the documents of vector neighbours with one boolean query,
+    /// reading their IDs back from the fast columns
+    fn resolve_neighbours(
+        &self,
+        searcher: &tantivy::Searcher,
+        nearest: &[VectorMatch],
+    ) -> Result<Vec<(DocAddress, f32)>, IndexError> {
+        if nearest.is_empty() {
+            return Ok(Vec::new());
+        }
+        let term_query = |field, text: &str| -> Box<dyn Query> {
+            Box::new(TermQuery::new(
+                Term::from_field_text(field, text),
+                IndexRecordOption::Basic,
+            ))
+        };
+        let clauses: Vec<(Occur, Box<dyn Query>)> = nearest
+            .iter()
+            .map(|n| {
+                let entity = BooleanQuery::new(vec![
+                    (Occur::Must, term_query(self.index.id_field, &n.entity_id)),
+                    (
+                        Occur::Must,
+                        term_query(self.index.occurs_in_field, &n.crate_id),
+                    ),
+                ]);
+                (Occur::Should, Box::new(entity) as Box<dyn Query>)
+            })
+            .collect();
+        let similarity: HashMap<(&str, &str), f32> = nearest
+            .iter()
+            .map(|n| ((n.crate_id.as_str(), n.entity_id.as_str()), n.similarity))
+            .collect();
+
+        let mut docs: Vec<DocAddress> = searcher
+            .search(&BooleanQuery::new(clauses), &DocSetCollector)?
+            .into_iter()
+            .collect();
+        docs.sort();
+
+        let mut resolved = Vec::with_capacity(docs.len());
+        let mut columns = None;
+        for doc in docs {
+            if columns
+                .as_ref()
+                .is_none_or(|(ord, _, _)| *ord != doc.segment_ord)
+            {
+                let fast_fields = searcher.segment_reader(doc.segment_ord).fast_fields();
+                columns = Some((
+                    doc.segment_ord,
+                    fast_fields.str("id")?,
+                    fast_fields.str("occurs_in")?,
+                ));
+            }
+            let Some((_, Some(ids), Some(crates))) = &columns else {
+                continue;
+            };
+            let (Some(entity_id), Some(crate_id)) =
+                (first_str(ids, doc.doc_id), first_str(crates, doc.doc_id))
+            else {
+                continue;
+            };
+            if let Some(&sim) = similarity.get(&(crate_id.as_str(), entity_id.as_str())) {
+                resolved.push((doc, sim));
+            }
+        }
+        Ok(resolved)
     }
 
     fn collect_hits(
@@ -159,10 +1046,17 @@ impl<'a> QueryEngine<'a> {
                 .unwrap_or("")
                 .to_string();
 
+            let entity = doc
+                .get_first(self.index.summary_field)
+                .and_then(|v| v.as_str())
+                .and_then(|s| serde_json::from_str(s).ok());
+
             hits.push(SearchHit {
                 entity_id,
                 crate_id,
                 score,
+                entity,
+                snippet: None,
             });
         }
 
@@ -170,6 +1064,13 @@ impl<'a> QueryEngine<'a> {
     }
 }
 
+/// First value of a string fast column for a document
+fn first_str(column: &StrColumn, doc: tantivy::DocId) -> Option<String> {
+    let ord = column.term_ords(doc).next()?;
+    let mut value = String::new();
+    matches!(column.ord_to_str(ord, &mut value), Ok(true)).then_some(value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,6 +1109,23 @@ mod tests {
         assert_eq!(engine.preprocess_query("name:Test"), "name:Test");
     }
 
+    #[test]
+    fn test_parse_error_position() {
+        let index = SearchIndex::new_in_memory().unwrap();
+        let engine = QueryEngine::new(&index);
+
+        // The unclosed group is reported where the query ends
+        let error = engine.parse_query("coli AND name:(growth").unwrap_err();
+        assert_eq!(error.code(), "query_parse");
+        assert!(matches!(
+            error,
+            IndexError::QueryParse {
+                position: Some(21),
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_preprocess_query_mixed() {
         let index = SearchIndex::new_in_memory().unwrap();
@@ -0,0 +1,530 @@
+//! RDF view of the indexed crates, queried with SPARQL.
+//!
+//! Each crate's JSON-LD metadata is loaded into an in-memory quad store as a
+//! named graph (see `crate_iri`). Queries without `FROM` clauses see the union
+//! of all crates as their default graph. The metadata of a single crate can
+//! also be converted to other RDF serialisations (see `CrateFormat`).
+//! RO-Crate contexts are bundled, other remote contexts are not fetched.
+
+use std::collections::BTreeMap;
+
+use oxigraph::io::RdfSerializer;
+use oxigraph::io::{JsonLdProfile, JsonLdProfileSet, LoadedDocument, RdfFormat, RdfParser};
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{GraphName, NamedNode, NamedOrBlankNode, Quad, Term};
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
+use oxigraph::sparql::{QueryResults, SparqlEvaluator};
+use oxigraph::store::Store;
+
+use crate::error::IndexError;
+
+/// Published RO-Crate context documents, by the path they are served under
+const RO_CRATE_CONTEXTS: &[(&str, &str)] = &[
+    (
+        "w3id.org/ro/crate/1.0/context",
+        include_str!("contexts/ro-crate-1.0.jsonld"),
+    ),
+    (
+        "w3id.org/ro/crate/1.1/context",
+        include_str!("contexts/ro-crate-1.1.jsonld"),
+    ),
+    (
+        "w3id.org/ro/crate/1.2/context",
+        include_str!("contexts/ro-crate-1.2.jsonld"),
+    ),
+    (
+        "w3id.org/ro/crate/1.2-DRAFT/context",
+        include_str!("contexts/ro-crate-1.2-DRAFT.jsonld"),
+    ),
+];
+
+/// Base IRI of crates that are not identified by an http(s) URL
+const LOCAL_BASE: &str = "arcp://name,rocrate-idx/";
+
+/// JSON-LD profile of expanded documents, as used in `Accept` headers
+const EXPANDED_PROFILE: &str = "http://www.w3.org/ns/json-ld#expanded";
+
+/// Results of a SPARQL query, serialised
+#[derive(Debug, Clone)]
+pub struct SparqlResults {
+    /// `application/sparql-results+json` for SELECT and ASK queries,
+    /// `application/n-triples` for CONSTRUCT and DESCRIBE queries
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+/// Serialisation of a crate's metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrateFormat {
+    /// The JSON-LD as it was stored
+    JsonLd,
+    /// Expanded JSON-LD: full IRIs, no context
+    ExpandedJsonLd,
+    Turtle,
+    NTriples,
+    /// N-Quads, in the crate's named graph
+    NQuads,
+}
+
+impl CrateFormat {
+    /// Parse a format name (`json`, `expanded`, `turtle`, `ntriples` or `nquads`)
+    pub fn parse(name: &str) -> Result<Self, IndexError> {
+        match name {
+            "json" | "jsonld" | "json-ld" => Ok(CrateFormat::JsonLd),
+            "expanded" => Ok(CrateFormat::ExpandedJsonLd),
+            "turtle" | "ttl" => Ok(CrateFormat::Turtle),
+            "ntriples" | "nt" => Ok(CrateFormat::NTriples),
+            "nquads" | "nq" => Ok(CrateFormat::NQuads),
+            other => Err(IndexError::InvalidQuery {
+                path: "format".to_string(),
+                reason: format!(
+                    "unknown format '{}' (expected json, expanded, turtle, ntriples or nquads)",
+                    other
+                ),
+            }),
+        }
+    }
+
+    /// Best format for an HTTP `Accept` header, `None` if none is acceptable
+    ///
+    /// Media ranges are ranked by their `q` parameter, earlier ones win ties.
+    /// `application/json` and wildcards select the stored JSON-LD, the
+    /// expanded profile of `application/ld+json` selects expanded JSON-LD.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut best: Option<(f32, CrateFormat)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+            let mut quality = 1.0;
+            let mut expanded = false;
+            for (name, value) in params.filter_map(|param| param.split_once('=')) {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "q" => quality = value.trim().parse().unwrap_or(0.0),
+                    "profile" => {
+                        expanded = value
+                            .trim()
+                            .trim_matches('"')
+                            .split_whitespace()
+                            .any(|profile| profile == EXPANDED_PROFILE)
+                    }
+                    _ => {}
+                }
+            }
+            let format = match media_type.as_str() {
+                "application/ld+json" if expanded => CrateFormat::ExpandedJsonLd,
+                "application/ld+json" | "application/json" | "application/*" | "*/*" => {
+                    CrateFormat::JsonLd
+                }
+                "text/turtle" | "text/*" => CrateFormat::Turtle,
+                "application/n-triples" => CrateFormat::NTriples,
+                "application/n-quads" => CrateFormat::NQuads,
+                _ => continue,
+            };
+            if quality > 0.0 && best.is_none_or(|(q, _)| quality > q) {
+                best = Some((quality, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+
+    /// Media type of the serialisation, e.g. `text/turtle`
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            CrateFormat::JsonLd => "application/ld+json",
+            CrateFormat::ExpandedJsonLd => {
+                r#"application/ld+json; profile="http://www.w3.org/ns/json-ld#expanded""#
+            }
+            CrateFormat::Turtle => "text/turtle",
+            CrateFormat::NTriples => "application/n-triples",
+            CrateFormat::NQuads => "application/n-quads",
+        }
+    }
+}
+
+/// IRI of a crate's root, used as its named graph and to resolve relative `@id`s
+///
+/// Crates added from a URL keep that URL, e.g. `https://example.org/crate/`;
+/// others get an `arcp://name,rocrate-idx/` IRI with the crate ID as path.
+pub fn crate_iri(crate_id: &str) -> String {
+    if crate_id.starts_with("http://") || crate_id.starts_with("https://") {
+        return format!("{}/", crate_id.trim_end_matches('/'));
+    }
+    let mut iri = url::Url::parse(LOCAL_BASE).expect("valid base IRI");
+    iri.path_segments_mut()
+        .expect("hierarchical base IRI")
+        .pop_if_empty()
+        .extend(crate_id.split('/'))
+        .push("");
+    iri.to_string()
+}
+
+/// Quad store holding one named graph per crate
+pub(crate) struct RdfStore {
+    store: Store,
+}
+
+impl RdfStore {
+    pub fn new() -> Result<Self, IndexError> {
+        Ok(Self {
+            store: Store::new().map_err(rdf_error)?,
+        })
+    }
+
+    /// Load a crate's metadata as its named graph, replacing what it had
+    ///
+    /// The old graph is dropped first, so metadata that fails to parse leaves
+    /// the crate without a graph rather than with stale triples. Returns the
+    /// number of triples loaded.
+    pub fn insert_crate(&self, crate_id: &str, json: &str) -> Result<usize, IndexError> {
+        self.remove_crate(crate_id)?;
+        let graph = NamedNode::new(crate_iri(crate_id)).map_err(rdf_error)?;
+        let quads = parse_crate(crate_id, json, graph.into())?;
+        let count = quads.len();
+        self.store.extend(quads).map_err(rdf_error)?;
+        Ok(count)
+    }
+
+    /// Drop a crate's named graph
+    pub fn remove_crate(&self, crate_id: &str) -> Result<(), IndexError> {
+        let graph = NamedNode::new(crate_iri(crate_id)).map_err(rdf_error)?;
+        self.store.remove_named_graph(&graph).map_err(rdf_error)?;
+        Ok(())
+    }
+
+    /// Run a SPARQL 1.1 query
+    pub fn query(&self, sparql: &str) -> Result<SparqlResults, IndexError> {
+        let mut query =
+            SparqlEvaluator::new()
+                .parse_query(sparql)
+                .map_err(|e| IndexError::InvalidQuery {
+                    path: "query".to_string(),
+                    reason: e.to_string(),
+                })?;
+        if query.dataset().is_default_dataset() {
+            query.dataset_mut().set_default_graph_as_union();
+        }
+
+        match query.on_store(&self.store).execute().map_err(rdf_error)? {
+            QueryResults::Solutions(solutions) => {
+                let mut serializer = QueryResultsSerializer::from_format(QueryResultsFormat::Json)
+                    .serialize_solutions_to_writer(Vec::new(), solutions.variables().to_vec())?;
+                for solution in solutions {
+                    serializer.serialize(&solution.map_err(rdf_error)?)?;
+                }
+                sparql_json(serializer.finish()?)
+            }
+            QueryResults::Boolean(value) => sparql_json(
+                QueryResultsSerializer::from_format(QueryResultsFormat::Json)
+                    .serialize_boolean_to_writer(Vec::new(), value)?,
+            ),
+            QueryResults::Graph(triples) => {
+                let mut serializer =
+                    RdfSerializer::from_format(RdfFormat::NTriples).for_writer(Vec::new());
+                for triple in triples {
+                    serializer.serialize_triple(&triple.map_err(rdf_error)?)?;
+                }
+                Ok(SparqlResults {
+                    content_type: "application/n-triples",
+                    body: utf8(serializer.finish()?)?,
+                })
+            }
+        }
+    }
+}
+
+/// Parse a crate's JSON-LD metadata into quads in `graph`
+///
+/// Metadata that isn't valid JSON-LD (e.g. with an unknown remote context)
+/// is an invalid crate format. Properties the context doesn't define are
+/// dropped, as JSON-LD expansion does.
+fn parse_crate(crate_id: &str, json: &str, graph: GraphName) -> Result<Vec<Quad>, IndexError> {
+    RdfParser::from_format(RdfFormat::JsonLd {
+        profile: JsonLdProfileSet::empty(),
+    })
+    .with_base_iri(crate_iri(crate_id))
+    .map_err(rdf_error)?
+    .with_default_graph(graph)
+    .rename_blank_nodes()
+    .lenient()
+    .for_slice(json)
+    .with_document_loader(load_context)
+    .filter(|quad| !matches!(quad, Ok(quad) if !quad.predicate.as_str().contains(':')))
+    .collect::<Result<_, _>>()
+    .map_err(|e| IndexError::InvalidCrateFormat(format!("not convertible to RDF: {}", e)))
+}
+
+/// Convert a crate's JSON-LD metadata to `format`
+///
+/// Relative `@id`s are resolved against the crate's IRI (see `crate_iri`).
+pub fn convert_crate(
+    crate_id: &str,
+    json: &str,
+    format: CrateFormat,
+) -> Result<String, IndexError> {
+    let rdf_format = match format {
+        CrateFormat::JsonLd => return Ok(json.to_string()),
+        CrateFormat::ExpandedJsonLd => {
+            let quads = parse_crate(crate_id, json, GraphName::DefaultGraph)?;
+            return Ok(serde_json::to_string_pretty(&expand(&quads))?);
+        }
+        CrateFormat::Turtle => RdfFormat::Turtle,
+        CrateFormat::NTriples => RdfFormat::NTriples,
+        CrateFormat::NQuads => RdfFormat::NQuads,
+    };
+    let graph = match format {
+        CrateFormat::NQuads => NamedNode::new(crate_iri(crate_id))
+            .map_err(rdf_error)?
+            .into(),
+        _ => GraphName::DefaultGraph,
+    };
+    let mut serializer = RdfSerializer::from_format(rdf_format)
+        .with_prefix("schema", "http://schema.org/")
+        .map_err(rdf_error)?
+        .for_writer(Vec::new());
+    for quad in &parse_crate(crate_id, json, graph)? {
+        serializer.serialize_quad(quad)?;
+    }
+    utf8(serializer.finish()?)
+}
+
+/// Expanded JSON-LD of triples: one node object per subject, sorted by `@id`
+fn expand(quads: &[Quad]) -> serde_json::Value {
+    use serde_json::{Map, Value, json};
+
+    let mut nodes: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+    for quad in quads {
+        let id = match &quad.subject {
+            NamedOrBlankNode::NamedNode(node) => node.as_str().to_string(),
+            NamedOrBlankNode::BlankNode(node) => node.to_string(),
+        };
+        let (key, value) = match &quad.object {
+            Term::NamedNode(node) if quad.predicate == rdf::TYPE => ("@type", json!(node.as_str())),
+            Term::BlankNode(node) if quad.predicate == rdf::TYPE => {
+                ("@type", json!(node.to_string()))
+            }
+            Term::NamedNode(node) => (quad.predicate.as_str(), json!({"@id": node.as_str()})),
+            Term::BlankNode(node) => (quad.predicate.as_str(), json!({"@id": node.to_string()})),
+            Term::Literal(literal) => {
+                let value = match literal.language() {
+                    Some(language) => json!({"@value": literal.value(), "@language": language}),
+                    None if literal.datatype() == xsd::STRING => json!({"@value": literal.value()}),
+                    None => {
+                        json!({"@value": literal.value(), "@type": literal.datatype().as_str()})
+                    }
+                };
+                (quad.predicate.as_str(), value)
+            }
+        };
+        let node = nodes
+            .entry(id.clone())
+            .or_insert_with(|| Map::from_iter([("@id".to_string(), json!(id))]));
+        if let Value::Array(values) = node.entry(key).or_insert_with(|| json!([])) {
+            values.push(value);
+        }
+    }
+    Value::Array(nodes.into_values().map(Value::Object).collect())
+}
+
+/// Resolve a remote `@context`, only from the bundled contexts
+fn load_context(url: &str) -> Result<LoadedDocument, Box<dyn std::error::Error + Send + Sync>> {
+    let path = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+    let Some((_, content)) = RO_CRATE_CONTEXTS.iter().find(|(known, _)| *known == path) else {
+        return Err(format!("remote context {} is not bundled", url).into());
+    };
+    Ok(LoadedDocument {
+        url: url.to_string(),
+        content: content.as_bytes().to_vec(),
+        format: RdfFormat::JsonLd {
+            profile: JsonLdProfile::Context.into(),
+        },
+    })
+}
+
+fn sparql_json(body: Vec<u8>) -> Result<SparqlResults, IndexError> {
+    Ok(SparqlResults {
+        content_type: "application/sparql-results+json",
+        body: utf8(body)?,
+    })
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String, IndexError> {
+    String::from_utf8(bytes).map_err(rdf_error)
+}
+
+fn rdf_error(error: impl std::fmt::Display) -> IndexError {
+    IndexError::Rdf(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRATE: &str = r#"{
+        "@context": "https://w3id.org/ro/crate/1.1/context",
+        "@graph": [
+            {"@id": "ro-crate-metadata.json", "@type": "CreativeWork", "about": {"@id": "./"}},
+            {
+                "@id": "./",
+                "@type": "Dataset",
+                "name": "Yeast growth",
+                "author": {"@id": "https://orcid.org/0000-0002-1825-0097"},
+                "hasPart": {"@id": "data.csv"}
+            },
+            {"@id": "data.csv", "@type": "File", "path": "data.csv"},
+            {
+                "@id": "https://orcid.org/0000-0002-1825-0097",
+                "@type": "Person",
+                "name": "Josiah Carberry",
+                "affiliation": {"@id": "https://ror.org/05gq02987"}
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_crate_iri() {
+        assert_eq!(
+            crate_iri("https://example.org/crates/yeast"),
+            "https://example.org/crates/yeast/"
+        );
+        assert_eq!(
+            crate_iri("01HZX/my crate"),
+            "arcp://name,rocrate-idx/01HZX/my%20crate/"
+        );
+    }
+
+    #[test]
+    fn test_format_negotiation() {
+        assert_eq!(CrateFormat::from_accept("*/*"), Some(CrateFormat::JsonLd));
+        assert_eq!(
+            CrateFormat::from_accept("application/json;q=0.5, text/turtle"),
+            Some(CrateFormat::Turtle)
+        );
+        assert_eq!(
+            CrateFormat::from_accept(
+                r#"application/ld+json;profile="http://www.w3.org/ns/json-ld#expanded""#
+            ),
+            Some(CrateFormat::ExpandedJsonLd)
+        );
+        assert_eq!(
+            CrateFormat::from_accept("text/html, application/n-quads;q=0.1"),
+            Some(CrateFormat::NQuads)
+        );
+        assert_eq!(CrateFormat::from_accept("text/html, text/turtle;q=0"), None);
+        assert!(CrateFormat::parse("rdfxml").is_err());
+    }
+
+    #[test]
+    fn test_convert_crate() {
+        let turtle = convert_crate("01HZX/yeast", CRATE, CrateFormat::Turtle).unwrap();
+        assert!(turtle.contains("@prefix schema: <http://schema.org/>"));
+        assert!(turtle.contains("schema:MediaObject"));
+
+        let triples = convert_crate("01HZX/yeast", CRATE, CrateFormat::NTriples).unwrap();
+        assert_eq!(triples.lines().count(), 11);
+        assert!(triples.contains(
+            "<arcp://name,rocrate-idx/01HZX/yeast/data.csv> <http://schema.org/contentUrl> \"data.csv\" ."
+        ));
+        let quads = convert_crate("01HZX/yeast", CRATE, CrateFormat::NQuads).unwrap();
+        assert!(
+            quads
+                .lines()
+                .all(|line| line.ends_with("<arcp://name,rocrate-idx/01HZX/yeast/> ."))
+        );
+
+        let expanded = convert_crate("01HZX/yeast", CRATE, CrateFormat::ExpandedJsonLd).unwrap();
+        let expanded: serde_json::Value = serde_json::from_str(&expanded).unwrap();
+        let person = expanded
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node| node["@id"] == "https://orcid.org/0000-0002-1825-0097")
+            .unwrap();
+        assert_eq!(
+            person["@type"],
+            serde_json::json!(["http://schema.org/Person"])
+        );
+        assert_eq!(
+            person["http://schema.org/name"],
+            serde_json::json!([{"@value": "Josiah Carberry"}])
+        );
+
+        assert_eq!(
+            convert_crate("01HZX/yeast", CRATE, CrateFormat::JsonLd).unwrap(),
+            CRATE
+        );
+    }
+
+    #[test]
+    fn test_versioned_contexts() {
+        for version in ["1.0", "1.1", "1.2", "1.2-DRAFT"] {
+            let json = CRATE.replace("crate/1.1/context", &format!("crate/{}/context", version));
+            let triples = convert_crate("01HZX/yeast", &json, CrateFormat::NTriples).unwrap();
+            assert_eq!(triples.lines().count(), 11, "context {}", version);
+        }
+
+        // Terms the context doesn't define are dropped, not mapped to schema.org
+        let json = CRATE.replace(r#""name": "Yeast growth","#, r#""growthMedium": "YPD","#);
+        let triples = convert_crate("01HZX/yeast", &json, CrateFormat::NTriples).unwrap();
+        assert_eq!(triples.lines().count(), 10);
+        assert!(!triples.contains("growthMedium"));
+    }
+
+    #[test]
+    fn test_query_crate_graphs() {
+        let store = RdfStore::new().unwrap();
+        assert_eq!(store.insert_crate("01HZX/yeast", CRATE).unwrap(), 11);
+        // Reloading replaces the graph
+        assert_eq!(store.insert_crate("01HZX/yeast", CRATE).unwrap(), 11);
+
+        let results = store
+            .query(
+                "PREFIX schema: <http://schema.org/>
+                 SELECT ?dataset WHERE {
+                     ?dataset a schema:Dataset ;
+                              schema:author/schema:affiliation <https://ror.org/05gq02987> .
+                 }",
+            )
+            .unwrap();
+        assert_eq!(results.content_type, "application/sparql-results+json");
+        let json: serde_json::Value = serde_json::from_str(&results.body).unwrap();
+        assert_eq!(
+            json["results"]["bindings"][0]["dataset"]["value"],
+            "arcp://name,rocrate-idx/01HZX/yeast/"
+        );
+
+        // Named graphs, terms mapped outside schema.org, and CONSTRUCT
+        let ask = "ASK { GRAPH <arcp://name,rocrate-idx/01HZX/yeast/> {
+                        ?file a <http://schema.org/MediaObject> ;
+                              <http://schema.org/contentUrl> \"data.csv\" } }";
+        assert!(store.query(ask).unwrap().body.contains("true"));
+        let graph = store
+            .query("CONSTRUCT WHERE { ?s <http://schema.org/name> ?name }")
+            .unwrap();
+        assert_eq!(graph.content_type, "application/n-triples");
+        assert_eq!(graph.body.lines().count(), 2);
+
+        store.remove_crate("01HZX/yeast").unwrap();
+        assert!(store.query(ask).unwrap().body.contains("false"));
+
+        assert!(matches!(
+            store.query("SELECT WHERE"),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+        let unknown = CRATE.replace(
+            "https://w3id.org/ro/crate/1.1/context",
+            "https://example.org/ctx",
+        );
+        assert!(matches!(
+            store.insert_crate("other", &unknown),
+            Err(IndexError::InvalidCrateFormat(_))
+        ));
+
+        // A failed reload drops the previous graph instead of keeping it
+        store.insert_crate("01HZX/yeast", CRATE).unwrap();
+        assert!(store.insert_crate("01HZX/yeast", &unknown).is_err());
+        assert!(store.query(ask).unwrap().body.contains("false"));
+    }
+}
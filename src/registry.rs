@@ -0,0 +1,196 @@
+//! Cross-crate registry of entities with absolute `@id`s.
+//!
+//! People, organisations and identifiers such as ORCIDs, RORs and DOIs recur
+//! across many crates. The registry collects, per absolute `@id`, the crates it
+//! occurs in, the properties that reference it there (its roles, e.g.
+//! `author` or `funder`) and the names and types it is described with.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::extract::{extract_id, extract_references, extract_types};
+
+/// Everything known about an entity across the indexed crates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegisteredEntity {
+    pub entity_id: String,
+    /// Names it is given in any crate, merged and sorted
+    pub names: Vec<String>,
+    /// `@type` values it has in any crate, merged and sorted
+    pub types: Vec<String>,
+    /// Crates it occurs in, sorted by crate ID
+    pub occurrences: Vec<EntityOccurrence>,
+}
+
+/// Occurrence of an entity in one crate
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EntityOccurrence {
+    pub crate_id: String,
+    /// Properties referencing the entity in this crate, e.g. `author`
+    pub roles: BTreeSet<String>,
+    /// Names the crate gives it
+    pub names: BTreeSet<String>,
+    /// `@type` values the crate gives it
+    pub types: BTreeSet<String>,
+}
+
+/// Entity occurrences keyed by absolute `@id`
+#[derive(Debug, Default)]
+pub(crate) struct EntityRegistry {
+    /// Occurrences per entity, keyed by crate ID
+    entities: HashMap<String, BTreeMap<String, EntityOccurrence>>,
+    /// Registered entity IDs per crate, for removal
+    crates: HashMap<String, Vec<String>>,
+}
+
+impl EntityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the entities of a crate, replacing what it had registered
+    pub fn insert_crate(&mut self, crate_id: &str, entities: &[Value]) {
+        self.remove_crate(crate_id);
+
+        let mut found: BTreeMap<String, EntityOccurrence> = BTreeMap::new();
+        for entity in entities {
+            if let Some(id) = extract_id(entity).filter(|id| is_absolute(id)) {
+                let described = occurrence(&mut found, crate_id, id);
+                described.types.extend(extract_types(entity));
+                described.names.extend(names(entity));
+            }
+            let Some(properties) = entity.as_object() else {
+                continue;
+            };
+            for (property, value) in properties.iter().filter(|(p, _)| !p.starts_with('@')) {
+                for id in extract_references(value).filter(|id| is_absolute(id)) {
+                    occurrence(&mut found, crate_id, id)
+                        .roles
+                        .insert(property.clone());
+                }
+            }
+        }
+
+        let entity_ids = found.keys().cloned().collect();
+        for (entity_id, occurrence) in found {
+            self.entities
+                .entry(entity_id)
+                .or_default()
+                .insert(crate_id.to_string(), occurrence);
+        }
+        self.crates.insert(crate_id.to_string(), entity_ids);
+    }
+
+    /// Drop the occurrences of a crate
+    pub fn remove_crate(&mut self, crate_id: &str) {
+        for entity_id in self.crates.remove(crate_id).unwrap_or_default() {
+            if let Some(occurrences) = self.entities.get_mut(&entity_id) {
+                occurrences.remove(crate_id);
+                if occurrences.is_empty() {
+                    self.entities.remove(&entity_id);
+                }
+            }
+        }
+    }
+
+    /// All occurrences of an entity, `None` if no crate mentions it
+    pub fn get(&self, entity_id: &str) -> Option<RegisteredEntity> {
+        let occurrences = self.entities.get(entity_id)?;
+        let merge = |field: fn(&EntityOccurrence) -> &BTreeSet<String>| {
+            occurrences
+                .values()
+                .flat_map(field)
+                .cloned()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        };
+        Some(RegisteredEntity {
+            entity_id: entity_id.to_string(),
+            names: merge(|o| &o.names),
+            types: merge(|o| &o.types),
+            occurrences: occurrences.values().cloned().collect(),
+        })
+    }
+}
+
+fn occurrence<'a>(
+    found: &'a mut BTreeMap<String, EntityOccurrence>,
+    crate_id: &str,
+    entity_id: &str,
+) -> &'a mut EntityOccurrence {
+    found
+        .entry(entity_id.to_string())
+        .or_insert_with(|| EntityOccurrence {
+            crate_id: crate_id.to_string(),
+            ..Default::default()
+        })
+}
+
+/// Whether an `@id` is an absolute IRI, not one relative to its crate
+fn is_absolute(id: &str) -> bool {
+    url::Url::parse(id).is_ok()
+}
+
+fn names(entity: &Value) -> Vec<String> {
+    match entity.get("name") {
+        Some(Value::String(name)) => vec![name.clone()],
+        Some(Value::Array(names)) => names
+            .iter()
+            .filter_map(|n| n.as_str().map(String::from))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ORCID: &str = "https://orcid.org/0000-0002-1825-0097";
+
+    #[test]
+    fn test_registry_merges_occurrences() {
+        let mut registry = EntityRegistry::new();
+        registry.insert_crate(
+            "crate-a",
+            &[
+                json!({"@id": "./", "@type": "Dataset", "author": {"@id": ORCID}}),
+                json!({"@id": ORCID, "@type": "Person", "name": "Josiah Carberry"}),
+                json!({"@id": "#local", "@type": "Person", "name": "Nobody"}),
+            ],
+        );
+        registry.insert_crate(
+            "crate-b",
+            &[
+                json!({
+                    "@id": "./",
+                    "@type": "Dataset",
+                    "creator": [{"@id": ORCID}, {"@id": "#local"}],
+                    "funder": {"@id": ORCID}
+                }),
+                json!({"@id": ORCID, "@type": "Person", "name": "J. Carberry"}),
+            ],
+        );
+
+        let entity = registry.get(ORCID).unwrap();
+        assert_eq!(entity.names, vec!["J. Carberry", "Josiah Carberry"]);
+        assert_eq!(entity.types, vec!["Person"]);
+        let crates: Vec<_> = entity.occurrences.iter().map(|o| &o.crate_id).collect();
+        assert_eq!(crates, vec!["crate-a", "crate-b"]);
+        assert_eq!(
+            entity.occurrences[1].roles,
+            BTreeSet::from(["creator".to_string(), "funder".to_string()])
+        );
+        assert!(registry.get("#local").is_none());
+
+        // Re-registering replaces a crate's occurrences, removal drops them
+        registry.insert_crate("crate-b", &[json!({"@id": "./", "@type": "Dataset"})]);
+        assert_eq!(registry.get(ORCID).unwrap().occurrences.len(), 1);
+        registry.remove_crate("crate-a");
+        assert!(registry.get(ORCID).is_none());
+    }
+}
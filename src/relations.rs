@@ -0,0 +1,457 @@
+//! Links between crates beyond subcrate ancestry.
+//!
+//! Crates point at each other through properties such as `isBasedOn` or
+//! `citation`, and mention the same DOIs. The links of each crate are
+//! extracted when it is indexed and resolved against the identifiers of the
+//! indexed crates (their URL, root `@id` and `identifier`s) when queried, so a
+//! link resolves whichever of the two crates is indexed first.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::IndexError;
+use crate::extract::{extract_id, extract_references, find_root_entity};
+
+/// DOIs are normalised to lowercase IRIs with this prefix
+const DOI_PREFIX: &str = "https://doi.org/";
+
+/// Kind of link between two crates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RelationKind {
+    IsBasedOn,
+    Citation,
+    IsPartOf,
+    Mentions,
+    /// `hasPart` pointing at another crate
+    HasPart,
+    /// Both crates mention the same DOI, undirected
+    SharedDoi,
+}
+
+/// Properties whose references to other crates are links
+const LINK_PROPERTIES: [(&str, RelationKind); 5] = [
+    ("isBasedOn", RelationKind::IsBasedOn),
+    ("citation", RelationKind::Citation),
+    ("isPartOf", RelationKind::IsPartOf),
+    ("mentions", RelationKind::Mentions),
+    ("hasPart", RelationKind::HasPart),
+];
+
+impl RelationKind {
+    /// Name of the kind, e.g. `isBasedOn`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RelationKind::IsBasedOn => "isBasedOn",
+            RelationKind::Citation => "citation",
+            RelationKind::IsPartOf => "isPartOf",
+            RelationKind::Mentions => "mentions",
+            RelationKind::HasPart => "hasPart",
+            RelationKind::SharedDoi => "sharedDoi",
+        }
+    }
+}
+
+/// A typed edge between two indexed crates
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct CrateRelation {
+    /// Crate holding the link
+    pub source: String,
+    /// Crate it resolves to
+    pub target: String,
+    pub kind: RelationKind,
+    /// IRI or DOI the link goes through
+    pub via: String,
+}
+
+/// Output format of the relationship graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    GraphMl,
+}
+
+impl GraphFormat {
+    /// Parse a format name (`dot` or `graphml`)
+    pub fn parse(name: &str) -> Result<Self, IndexError> {
+        match name {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            other => Err(IndexError::InvalidQuery {
+                path: "format".to_string(),
+                reason: format!("unknown graph format '{}' (expected dot or graphml)", other),
+            }),
+        }
+    }
+}
+
+/// Keys per crate and crates per key
+#[derive(Debug, Default)]
+struct Postings {
+    keys: HashMap<String, BTreeSet<String>>,
+    crates: HashMap<String, BTreeSet<String>>,
+}
+
+impl Postings {
+    fn insert(&mut self, crate_id: &str, keys: BTreeSet<String>) {
+        for key in &keys {
+            self.crates
+                .entry(key.clone())
+                .or_default()
+                .insert(crate_id.to_string());
+        }
+        self.keys.insert(crate_id.to_string(), keys);
+    }
+
+    fn remove(&mut self, crate_id: &str) {
+        for key in self.keys.remove(crate_id).unwrap_or_default() {
+            if let Some(crates) = self.crates.get_mut(&key) {
+                crates.remove(crate_id);
+                if crates.is_empty() {
+                    self.crates.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn keys_of(&self, crate_id: &str) -> impl Iterator<Item = &String> {
+        self.keys.get(crate_id).into_iter().flatten()
+    }
+
+    fn crates_with(&self, key: &str) -> impl Iterator<Item = &String> {
+        self.crates.get(key).into_iter().flatten()
+    }
+}
+
+/// Links of the indexed crates and the identifiers they resolve against
+#[derive(Debug, Default)]
+pub(crate) struct RelationGraph {
+    /// Links per crate, as (kind, normalised target IRI)
+    links: HashMap<String, BTreeSet<(RelationKind, String)>>,
+    /// IRIs each crate links to
+    targets: Postings,
+    /// Normalised identifiers of each crate
+    identifiers: Postings,
+    /// DOIs each crate mentions, other than its own
+    dois: Postings,
+}
+
+impl RelationGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extract the links of a crate, replacing those it had
+    pub fn insert_crate(&mut self, crate_id: &str, entities: &[Value]) {
+        self.remove_crate(crate_id);
+
+        let mut identifiers: BTreeSet<String> = normalize(crate_id).into_iter().collect();
+        if let Some(root) = find_root_entity(entities) {
+            identifiers.extend(extract_id(root).and_then(normalize));
+            identifiers.extend(identifier_values(root).filter_map(normalize));
+        }
+
+        let mut links = BTreeSet::new();
+        let mut dois = BTreeSet::new();
+        for entity in entities {
+            let mentioned = extract_id(entity)
+                .into_iter()
+                .chain(identifier_values(entity))
+                .filter_map(normalize);
+            dois.extend(mentioned.filter(|id| id.starts_with(DOI_PREFIX)));
+            for (property, kind) in LINK_PROPERTIES {
+                let Some(value) = entity.get(property) else {
+                    continue;
+                };
+                for target in extract_references(value).filter_map(normalize) {
+                    if !identifiers.contains(&target) {
+                        links.insert((kind, target));
+                    }
+                }
+            }
+        }
+        dois.retain(|doi| !identifiers.contains(doi));
+
+        self.targets.insert(
+            crate_id,
+            links.iter().map(|(_, target)| target.clone()).collect(),
+        );
+        self.links.insert(crate_id.to_string(), links);
+        self.identifiers.insert(crate_id, identifiers);
+        self.dois.insert(crate_id, dois);
+    }
+
+    /// Drop the links and identifiers of a crate
+    pub fn remove_crate(&mut self, crate_id: &str) {
+        self.links.remove(crate_id);
+        self.targets.remove(crate_id);
+        self.identifiers.remove(crate_id);
+        self.dois.remove(crate_id);
+    }
+
+    /// Relations of a crate to others, both ways, sorted
+    ///
+    /// Shared DOIs are reported with the crate as source.
+    pub fn related(&self, crate_id: &str) -> Vec<CrateRelation> {
+        let mut relations = BTreeSet::new();
+        self.outgoing(crate_id, &mut relations);
+        self.shared_dois(crate_id, &mut relations);
+        for identifier in self.identifiers.keys_of(crate_id) {
+            for source in self.targets.crates_with(identifier) {
+                let links = self.links.get(source).into_iter().flatten();
+                for (kind, _) in links.filter(|(_, target)| target == identifier) {
+                    relations.insert(relation(source, crate_id, *kind, identifier));
+                }
+            }
+        }
+        relations.retain(|r| r.source != r.target);
+        relations.into_iter().collect()
+    }
+
+    /// Relations between all indexed crates, sorted, shared DOIs once
+    pub fn all(&self) -> Vec<CrateRelation> {
+        let mut relations = BTreeSet::new();
+        for crate_id in self.identifiers.keys.keys() {
+            self.outgoing(crate_id, &mut relations);
+            self.shared_dois(crate_id, &mut relations);
+        }
+        relations.retain(|r| {
+            r.source != r.target && (r.kind != RelationKind::SharedDoi || r.source < r.target)
+        });
+        relations.into_iter().collect()
+    }
+
+    fn outgoing(&self, crate_id: &str, relations: &mut BTreeSet<CrateRelation>) {
+        for (kind, target) in self.links.get(crate_id).into_iter().flatten() {
+            for other in self.identifiers.crates_with(target) {
+                relations.insert(relation(crate_id, other, *kind, target));
+            }
+        }
+    }
+
+    fn shared_dois(&self, crate_id: &str, relations: &mut BTreeSet<CrateRelation>) {
+        for doi in self.dois.keys_of(crate_id) {
+            for other in self.dois.crates_with(doi) {
+                relations.insert(relation(crate_id, other, RelationKind::SharedDoi, doi));
+            }
+        }
+    }
+}
+
+fn relation(source: &str, target: &str, kind: RelationKind, via: &str) -> CrateRelation {
+    CrateRelation {
+        source: source.to_string(),
+        target: target.to_string(),
+        kind,
+        via: via.to_string(),
+    }
+}
+
+/// String and `{"@id": ...}` values of an entity's `identifier`
+fn identifier_values(entity: &Value) -> impl Iterator<Item = &str> {
+    let values = match entity.get("identifier") {
+        Some(Value::Array(values)) => values.as_slice(),
+        Some(value) => std::slice::from_ref(value),
+        None => &[],
+    };
+    values
+        .iter()
+        .filter_map(|v| v.as_str().or_else(|| v.get("@id")?.as_str()))
+}
+
+/// Canonical form of an absolute IRI or DOI, `None` for crate-relative ids
+///
+/// DOIs in any common notation become lowercase `https://doi.org/` IRIs;
+/// http(s) IRIs lose a trailing slash and `ro-crate-metadata.json`, as crate
+/// IDs of crates loaded from URLs do.
+fn normalize(id: &str) -> Option<String> {
+    let id = id.trim();
+    let doi = [
+        "doi:",
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "http://dx.doi.org/",
+    ]
+    .iter()
+    .find_map(|prefix| id.strip_prefix(prefix))
+    .unwrap_or(id);
+    if doi.starts_with("10.") && doi.contains('/') {
+        return Some(format!("{}{}", DOI_PREFIX, doi.to_lowercase()));
+    }
+    let url = url::Url::parse(id).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some(id.to_string());
+    }
+    let id = id.trim_end_matches('/');
+    let id = id.strip_suffix("ro-crate-metadata.json").unwrap_or(id);
+    Some(id.trim_end_matches('/').to_string())
+}
+
+/// Render relations as a graph of the crates they connect
+///
+/// Crates are labelled with `label`, e.g. their name.
+pub fn render_graph(
+    relations: &[CrateRelation],
+    format: GraphFormat,
+    label: impl Fn(&str) -> String,
+) -> String {
+    let crates: BTreeSet<&str> = relations
+        .iter()
+        .flat_map(|r| [r.source.as_str(), r.target.as_str()])
+        .collect();
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            out.push_str("digraph crates {\n");
+            for crate_id in &crates {
+                let _ = writeln!(
+                    out,
+                    "  \"{}\" [label=\"{}\"];",
+                    escape_dot(crate_id),
+                    escape_dot(&label(crate_id))
+                );
+            }
+            for r in relations {
+                let undirected = if r.kind == RelationKind::SharedDoi {
+                    ", dir=none"
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    out,
+                    "  \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                    escape_dot(&r.source),
+                    escape_dot(&r.target),
+                    r.kind.as_str(),
+                    undirected
+                );
+            }
+            out.push_str("}\n");
+        }
+        GraphFormat::GraphMl => {
+            out.push_str(concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+                "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+                "  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+                "  <key id=\"via\" for=\"edge\" attr.name=\"via\" attr.type=\"string\"/>\n",
+                "  <graph id=\"crates\" edgedefault=\"directed\">\n",
+            ));
+            for crate_id in &crates {
+                let _ = writeln!(
+                    out,
+                    "    <node id=\"{}\"><data key=\"label\">{}</data></node>",
+                    escape_xml(crate_id),
+                    escape_xml(&label(crate_id))
+                );
+            }
+            for r in relations {
+                let undirected = if r.kind == RelationKind::SharedDoi {
+                    " directed=\"false\""
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    out,
+                    "    <edge source=\"{}\" target=\"{}\"{}><data key=\"kind\">{}</data><data key=\"via\">{}</data></edge>",
+                    escape_xml(&r.source),
+                    escape_xml(&r.target),
+                    undirected,
+                    r.kind.as_str(),
+                    escape_xml(&r.via)
+                );
+            }
+            out.push_str("  </graph>\n</graphml>\n");
+        }
+    }
+    out
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PAPER: &str = "https://example.org/crates/paper";
+
+    fn graph() -> RelationGraph {
+        let mut graph = RelationGraph::new();
+        // Loaded from a URL, so identified by its crate ID, and with a DOI
+        graph.insert_crate(
+            PAPER,
+            &[
+                json!({"@id": "./", "@type": "Dataset", "identifier": "doi:10.5281/Zenodo.1"}),
+                json!({"@id": "https://doi.org/10.1000/shared", "@type": "ScholarlyArticle"}),
+            ],
+        );
+        graph.insert_crate(
+            "01J0000000000000000000000A/derived",
+            &[
+                json!({
+                    "@id": "./",
+                    "@type": "Dataset",
+                    "isBasedOn": {"@id": "https://example.org/crates/paper/ro-crate-metadata.json"},
+                    "citation": [{"@id": "https://doi.org/10.5281/zenodo.1"}, {"@id": "#local"}],
+                    "mentions": {"@id": "https://example.org/not-indexed"}
+                }),
+                json!({"@id": "#local", "identifier": "https://doi.org/10.1000/SHARED"}),
+            ],
+        );
+        graph
+    }
+
+    #[test]
+    fn test_links_resolve_to_indexed_crates() {
+        let mut graph = graph();
+        let derived = "01J0000000000000000000000A/derived";
+        let related = graph.related(PAPER);
+        let kinds: Vec<_> = related
+            .iter()
+            .map(|r| (r.kind, r.source.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (RelationKind::IsBasedOn, derived),
+                (RelationKind::Citation, derived),
+                (RelationKind::SharedDoi, PAPER),
+            ]
+        );
+        assert_eq!(related[1].via, "https://doi.org/10.5281/zenodo.1");
+        assert_eq!(related[2].target, derived);
+        assert_eq!(graph.all().len(), 3);
+
+        graph.remove_crate(PAPER);
+        assert!(graph.related(derived).is_empty());
+    }
+
+    #[test]
+    fn test_render_graph() {
+        let relations = graph().all();
+        let dot = render_graph(&relations, GraphFormat::Dot, |id| format!("\"{}\"", id));
+        assert!(dot.starts_with("digraph crates {\n"));
+        assert!(dot.contains("[label=\"\\\"https://example.org/crates/paper\\\"\"];"));
+        assert!(dot.contains("[label=\"sharedDoi\", dir=none];"));
+
+        let graphml = render_graph(&relations, GraphFormat::GraphMl, |id| id.to_string());
+        assert_eq!(graphml.matches("<node ").count(), 2);
+        assert_eq!(graphml.matches("<edge ").count(), 3);
+        assert!(GraphFormat::parse("svg").is_err());
+    }
+}
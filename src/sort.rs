@@ -0,0 +1,440 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::columnar::{Column, StrColumn};
+use tantivy::{DateTime, DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+use crate::error::IndexError;
+use crate::paging::{PageFruit, SearchCursor};
+
+/// Field search hits can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    /// BM25 relevance
+    Score,
+    /// Entity `datePublished`
+    DatePublished,
+    /// Entity `name` (case-insensitive)
+    Name,
+    /// Time the entity's crate was indexed
+    IndexedAt,
+    /// Number of entities in the entity's crate
+    EntityCount,
+}
+
+impl SortField {
+    /// Name used in sort specifications
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortField::Score => "score",
+            SortField::DatePublished => "datePublished",
+            SortField::Name => "name",
+            SortField::IndexedAt => "indexedAt",
+            SortField::EntityCount => "entityCount",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "score" | "_score" => Some(SortField::Score),
+            "datePublished" => Some(SortField::DatePublished),
+            "name" => Some(SortField::Name),
+            "indexedAt" => Some(SortField::IndexedAt),
+            "entityCount" => Some(SortField::EntityCount),
+            _ => None,
+        }
+    }
+
+    /// Fast field holding the values, if not the score
+    fn column(&self) -> Option<&'static str> {
+        match self {
+            SortField::Score => None,
+            SortField::DatePublished => Some("date_published"),
+            SortField::Name => Some("sort_name"),
+            SortField::IndexedAt => Some("indexed_at"),
+            SortField::EntityCount => Some("entity_count"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// One key of a (possibly multi-key) sort
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub order: SortOrder,
+}
+
+impl SortKey {
+    pub fn asc(field: SortField) -> Self {
+        Self {
+            field,
+            order: SortOrder::Asc,
+        }
+    }
+
+    pub fn desc(field: SortField) -> Self {
+        Self {
+            field,
+            order: SortOrder::Desc,
+        }
+    }
+
+    /// Parse a comma-separated sort specification, e.g. `datePublished:desc,name`
+    ///
+    /// The order defaults to descending for `score` and ascending otherwise.
+    pub fn parse_list(spec: &str) -> Result<Vec<SortKey>, IndexError> {
+        let invalid = |reason: String| IndexError::InvalidQuery {
+            path: "sort".to_string(),
+            reason,
+        };
+
+        let mut keys = Vec::new();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, order) = match part.split_once(':') {
+                Some((name, order)) => (name.trim(), Some(order.trim())),
+                None => (part, None),
+            };
+            let field = SortField::from_name(name).ok_or_else(|| {
+                invalid(format!(
+                    "unknown sort field '{}' (expected score, datePublished, name, indexedAt or entityCount)",
+                    name
+                ))
+            })?;
+            let order = match order {
+                None if field == SortField::Score => SortOrder::Desc,
+                None => SortOrder::Asc,
+                Some("asc") => SortOrder::Asc,
+                Some("desc") => SortOrder::Desc,
+                Some(other) => {
+                    return Err(invalid(format!(
+                        "unknown sort order '{}' (expected asc or desc)",
+                        other
+                    )));
+                }
+            };
+            keys.push(SortKey { field, order });
+        }
+        Ok(keys)
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let order = match self.order {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        };
+        write!(f, "{}:{}", self.field.as_str(), order)
+    }
+}
+
+/// Sort value of one document for one key
+#[derive(Debug, Clone, PartialEq)]
+enum SortValue {
+    Missing,
+    Int(i64),
+    Str(String),
+    Score(Score),
+}
+
+impl SortValue {
+    /// Compare in the key's order; missing values always sort last
+    fn cmp_with(&self, other: &Self, order: SortOrder) -> Ordering {
+        let ordering = match (self, other) {
+            (SortValue::Missing, SortValue::Missing) => return Ordering::Equal,
+            (SortValue::Missing, _) => return Ordering::Greater,
+            (_, SortValue::Missing) => return Ordering::Less,
+            (SortValue::Int(a), SortValue::Int(b)) => a.cmp(b),
+            (SortValue::Str(a), SortValue::Str(b)) => a.cmp(b),
+            (SortValue::Score(a), SortValue::Score(b)) => a.total_cmp(b),
+            _ => Ordering::Equal,
+        };
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+}
+
+/// A document with its sort values
+#[derive(Debug, Clone)]
+pub struct Sorted {
+    values: Vec<SortValue>,
+    score: Score,
+    doc: DocAddress,
+}
+
+/// Order by the sort keys, then by descending score and ascending address
+fn sorted_cmp(keys: &[SortKey], a: &Sorted, b: &Sorted) -> Ordering {
+    keys.iter()
+        .zip(a.values.iter().zip(&b.values))
+        .map(|(key, (va, vb))| va.cmp_with(vb, key.order))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| b.score.total_cmp(&a.score))
+        .then_with(|| a.doc.cmp(&b.doc))
+}
+
+/// Fast field reader for one sort key in one segment
+enum KeyReader {
+    Score,
+    Date(Option<Column<DateTime>>),
+    U64(Option<Column<u64>>),
+    Str(Option<StrColumn>),
+}
+
+impl KeyReader {
+    fn open(field: SortField, segment: &SegmentReader) -> tantivy::Result<Self> {
+        let fast_fields = segment.fast_fields();
+        Ok(match (field, field.column()) {
+            (SortField::Score, _) | (_, None) => KeyReader::Score,
+            (SortField::Name, Some(name)) => KeyReader::Str(fast_fields.str(name)?),
+            (SortField::EntityCount, Some(name)) => KeyReader::U64(fast_fields.column_opt(name)?),
+            (_, Some(name)) => KeyReader::Date(fast_fields.column_opt(name)?),
+        })
+    }
+
+    fn value(&self, doc: DocId, score: Score) -> SortValue {
+        match self {
+            KeyReader::Score => SortValue::Score(score),
+            KeyReader::Date(column) => column
+                .as_ref()
+                .and_then(|c| c.first(doc))
+                .map(|d| SortValue::Int(d.into_timestamp_secs()))
+                .unwrap_or(SortValue::Missing),
+            KeyReader::U64(column) => column
+                .as_ref()
+                .and_then(|c| c.first(doc))
+                .map(|v| SortValue::Int(v as i64))
+                .unwrap_or(SortValue::Missing),
+            KeyReader::Str(column) => {
+                let Some(column) = column else {
+                    return SortValue::Missing;
+                };
+                let mut value = String::new();
+                match column.term_ords(doc).next() {
+                    Some(ord) if matches!(column.ord_to_str(ord, &mut value), Ok(true)) => {
+                        SortValue::Str(value)
+                    }
+                    _ => SortValue::Missing,
+                }
+            }
+        }
+    }
+}
+
+/// Collects one page of hits ordered by fast-field sort keys.
+///
+/// Like `PageCollector`, but ties are broken by score and then by document
+/// address, so pages are stable for a reader generation.
+pub struct SortCollector {
+    keys: Vec<SortKey>,
+    offset: usize,
+    limit: usize,
+    after: Option<Sorted>,
+}
+
+impl SortCollector {
+    pub fn new(keys: Vec<SortKey>, offset: usize, limit: usize) -> Self {
+        Self {
+            keys,
+            offset,
+            limit,
+            after: None,
+        }
+    }
+
+    /// Only collect hits sorted after the cursor's document
+    pub fn with_after(
+        mut self,
+        cursor: &SearchCursor,
+        searcher: &tantivy::Searcher,
+    ) -> Result<Self, IndexError> {
+        let segment = searcher
+            .segment_readers()
+            .get(cursor.doc.segment_ord as usize)
+            .ok_or_else(|| IndexError::InvalidCursor("Cursor points past the index".into()))?;
+        let values = self
+            .keys
+            .iter()
+            .map(|key| {
+                Ok(KeyReader::open(key.field, segment)?.value(cursor.doc.doc_id, cursor.score))
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+        self.after = Some(Sorted {
+            values,
+            score: cursor.score,
+            doc: cursor.doc,
+        });
+        Ok(self)
+    }
+}
+
+pub struct SortSegmentCollector {
+    segment_ord: SegmentOrdinal,
+    keys: Vec<SortKey>,
+    readers: Vec<KeyReader>,
+    capacity: usize,
+    after: Option<Sorted>,
+    docs: Vec<Sorted>,
+    total: usize,
+    remaining: usize,
+}
+
+impl SortSegmentCollector {
+    /// Keep only the best `capacity` documents
+    fn truncate(&mut self) {
+        let keys = &self.keys;
+        if self.docs.len() > self.capacity {
+            self.docs
+                .select_nth_unstable_by(self.capacity, |a, b| sorted_cmp(keys, a, b));
+            self.docs.truncate(self.capacity);
+        }
+    }
+}
+
+/// Best documents of one segment with its hit counts
+#[derive(Debug)]
+pub struct SortedFruit {
+    docs: Vec<Sorted>,
+    total: usize,
+    remaining: usize,
+}
+
+impl SegmentCollector for SortSegmentCollector {
+    type Fruit = SortedFruit;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        self.total += 1;
+
+        let sorted = Sorted {
+            values: self.readers.iter().map(|r| r.value(doc, score)).collect(),
+            score,
+            doc: DocAddress::new(self.segment_ord, doc),
+        };
+        if let Some(after) = &self.after
+            && sorted_cmp(&self.keys, &sorted, after).is_le()
+        {
+            return;
+        }
+        self.remaining += 1;
+
+        if self.capacity == 0 {
+            return;
+        }
+        self.docs.push(sorted);
+        if self.docs.len() >= self.capacity.saturating_mul(2) {
+            self.truncate();
+        }
+    }
+
+    fn harvest(mut self) -> SortedFruit {
+        self.truncate();
+        SortedFruit {
+            docs: self.docs,
+            total: self.total,
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl Collector for SortCollector {
+    type Fruit = PageFruit;
+    type Child = SortSegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<SortSegmentCollector> {
+        let readers = self
+            .keys
+            .iter()
+            .map(|key| KeyReader::open(key.field, segment))
+            .collect::<tantivy::Result<Vec<_>>>()?;
+        Ok(SortSegmentCollector {
+            segment_ord: segment_local_id,
+            keys: self.keys.clone(),
+            readers,
+            capacity: self.offset.saturating_add(self.limit),
+            after: self.after.clone(),
+            docs: Vec::new(),
+            total: 0,
+            remaining: 0,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        true
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<SortedFruit>) -> tantivy::Result<PageFruit> {
+        let mut docs = Vec::new();
+        let mut total = 0;
+        let mut remaining = 0;
+
+        for fruit in segment_fruits {
+            total += fruit.total;
+            remaining += fruit.remaining;
+            docs.extend(fruit.docs);
+        }
+
+        docs.sort_by(|a, b| sorted_cmp(&self.keys, a, b));
+        let docs = docs
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .map(|s| (s.score, s.doc))
+            .collect();
+
+        Ok(PageFruit {
+            docs,
+            total,
+            remaining,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sort_spec() {
+        let keys = SortKey::parse_list("datePublished:desc, name,score").unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                SortKey::desc(SortField::DatePublished),
+                SortKey::asc(SortField::Name),
+                SortKey::desc(SortField::Score),
+            ]
+        );
+        assert_eq!(keys[0].to_string(), "datePublished:desc");
+
+        assert!(matches!(
+            SortKey::parse_list("size"),
+            Err(IndexError::InvalidQuery { .. })
+        ));
+        assert!(SortKey::parse_list("name:up").is_err());
+    }
+
+    #[test]
+    fn test_missing_values_sort_last() {
+        let missing = SortValue::Missing;
+        let value = SortValue::Int(1);
+        assert_eq!(value.cmp_with(&missing, SortOrder::Asc), Ordering::Less);
+        assert_eq!(value.cmp_with(&missing, SortOrder::Desc), Ordering::Less);
+        assert_eq!(
+            SortValue::Int(1).cmp_with(&SortValue::Int(2), SortOrder::Desc),
+            Ordering::Greater
+        );
+    }
+}
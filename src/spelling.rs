@@ -0,0 +1,323 @@
+//! "Did you mean" corrections built from the content term dictionary.
+//!
+//! Query words that occur neither in any entity's content nor in any of its
+//! properties are replaced by the most frequent content term within two edits,
+//! or split in two when both halves are indexed ("ecoli" becomes "e coli").
+//! Field-qualified clauses and boolean operators are left alone.
+//!
+//! Dictionaries are only searched with automata, as Tantivy's fuzzy queries
+//! do, so a correction never visits the whole vocabulary.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use levenshtein_automata::{DFA, Distance, LevenshteinAutomatonBuilder, SINK_STATE};
+use tantivy::Term;
+use tantivy::schema::Type;
+use tantivy_fst::Automaton;
+
+use crate::error::IndexError;
+use crate::index::SearchIndex;
+
+/// Maximum edits between a query word and its correction
+const MAX_EDITS: usize = 2;
+
+/// Words shorter than this are too ambiguous to correct
+const MIN_WORD_CHARS: usize = 3;
+
+/// Builds automata accepting terms within `MAX_EDITS`, transpositions included
+static AUTOMATON_BUILDER: LazyLock<LevenshteinAutomatonBuilder> =
+    LazyLock::new(|| LevenshteinAutomatonBuilder::new(MAX_EDITS as u8, true));
+
+/// Levenshtein DFA as an automaton over term dictionary bytes
+struct Within(DFA);
+
+impl Automaton for Within {
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.0.initial_state()
+    }
+
+    fn is_match(&self, state: &u32) -> bool {
+        matches!(self.0.distance(*state), Distance::Exact(_))
+    }
+
+    fn can_match(&self, state: &u32) -> bool {
+        *state != SINK_STATE
+    }
+
+    fn accept(&self, state: &u32, byte: u8) -> u32 {
+        self.0.transition(*state, byte)
+    }
+}
+
+/// Accepts JSON terms with a given string value under any path
+///
+/// JSON terms are `<path>\0<type code><value>`; the automaton passes any path,
+/// then follows `value` only.
+struct AnyPath(Vec<u8>);
+
+impl AnyPath {
+    fn new(value: &str) -> Self {
+        let mut suffix = vec![Type::Str.to_code()];
+        suffix.extend_from_slice(value.as_bytes());
+        Self(suffix)
+    }
+}
+
+impl Automaton for AnyPath {
+    /// `None` inside the path, then the number of suffix bytes matched
+    type State = Option<Option<usize>>;
+
+    fn start(&self) -> Self::State {
+        Some(None)
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        *state == Some(Some(self.0.len()))
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        state.is_some()
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        match *state {
+            Some(None) if byte == 0 => Some(Some(0)),
+            Some(None) => Some(None),
+            Some(Some(matched)) if self.0.get(matched) == Some(&byte) => Some(Some(matched + 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Correct the misspelled words of a query string
+///
+/// Returns `None` when every word is indexed or no correction was found.
+pub(crate) fn correct_query(
+    index: &SearchIndex,
+    query: &str,
+) -> Result<Option<String>, IndexError> {
+    let searcher = index.searcher();
+    let doc_freq = |word: &str| -> Result<u64, IndexError> {
+        Ok(searcher.doc_freq(&Term::from_field_text(index.content_field, word))?)
+    };
+    let in_properties = |word: &str| -> Result<bool, IndexError> {
+        for segment in searcher.segment_readers() {
+            let inverted_index = segment.inverted_index(index.properties_field)?;
+            let automaton = AnyPath::new(word);
+            if inverted_index
+                .terms()
+                .search(automaton)
+                .into_stream()?
+                .advance()
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    };
+
+    let mut misspelled: Vec<String> = Vec::new();
+    for (start, end) in clauses(query) {
+        for word in words(&query[start..end]) {
+            let word = word.to_lowercase();
+            if word.chars().count() >= MIN_WORD_CHARS
+                && !misspelled.contains(&word)
+                && doc_freq(&word)? == 0
+                && !in_properties(&word)?
+            {
+                misspelled.push(word);
+            }
+        }
+    }
+    if misspelled.is_empty() {
+        return Ok(None);
+    }
+
+    // Content terms within reach of each misspelled word, with their frequency
+    let mut candidates: Vec<HashMap<String, (usize, u64)>> = Vec::new();
+    for word in &misspelled {
+        let target: Vec<char> = word.chars().collect();
+        let mut found: HashMap<String, (usize, u64)> = HashMap::new();
+        for segment in searcher.segment_readers() {
+            let inverted_index = segment.inverted_index(index.content_field)?;
+            let automaton = Within(AUTOMATON_BUILDER.build_dfa(word));
+            let mut stream = inverted_index.terms().search(automaton).into_stream()?;
+            while stream.advance() {
+                let Ok(term) = std::str::from_utf8(stream.key()) else {
+                    continue;
+                };
+                let entry = found.entry(term.to_string()).or_insert_with(|| {
+                    let chars: Vec<char> = term.chars().collect();
+                    (edit_distance(&target, &chars), 0)
+                });
+                entry.1 += stream.value().doc_freq as u64;
+            }
+        }
+        candidates.push(found);
+    }
+
+    let mut corrections: HashMap<String, String> = HashMap::new();
+    for (word, found) in misspelled.iter().zip(candidates) {
+        // Ranked by distance, then frequency; a split wins ties with an edit
+        let mut best: Option<(usize, Reverse<u64>, bool, String)> = found
+            .into_iter()
+            .map(|(term, (distance, freq))| (distance, Reverse(freq), true, term))
+            .min();
+        for (i, _) in word.char_indices().skip(1) {
+            let (head, tail) = word.split_at(i);
+            let freq = doc_freq(head)?.min(doc_freq(tail)?);
+            let candidate = (1, Reverse(freq), false, format!("{} {}", head, tail));
+            if freq > 0 && best.as_ref().is_none_or(|b| candidate < *b) {
+                best = Some(candidate);
+            }
+        }
+        if let Some((_, _, _, correction)) = best {
+            corrections.insert(word.clone(), correction);
+        }
+    }
+    if corrections.is_empty() {
+        return Ok(None);
+    }
+
+    // Rebuild the query, replacing corrected words in unqualified clauses
+    let mut corrected = String::with_capacity(query.len());
+    let mut last = 0;
+    for (start, end) in clauses(query) {
+        let clause = &query[start..end];
+        let mut offset = 0;
+        for word in words(clause) {
+            let word_start = offset + clause[offset..].find(word).unwrap_or(0);
+            if let Some(correction) = corrections.get(&word.to_lowercase()) {
+                corrected.push_str(&query[last..start + word_start]);
+                corrected.push_str(correction);
+                last = start + word_start + word.len();
+            }
+            offset = word_start + word.len();
+        }
+    }
+    corrected.push_str(&query[last..]);
+    Ok(Some(corrected))
+}
+
+/// Byte ranges of the unqualified clauses of a query
+///
+/// Clauses are separated by whitespace outside quotes; clauses with a field
+/// prefix (`name:...`) and boolean operators are skipped.
+fn clauses(query: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+    for (i, c) in query.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        if c.is_whitespace() && !in_quotes {
+            if let Some(s) = start.take() {
+                ranges.push((s, i));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        ranges.push((s, query.len()));
+    }
+
+    ranges
+        .into_iter()
+        .filter(|&(s, e)| {
+            let clause = &query[s..e];
+            let unquoted = clause.split('"').next().unwrap_or_default();
+            !unquoted.contains(':') && !matches!(clause, "AND" | "OR" | "NOT")
+        })
+        .collect()
+}
+
+/// Words of a clause as the default tokenizer splits them
+fn words(clause: &str) -> impl Iterator<Item = &str> {
+    clause
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+}
+
+/// Edit distance counting adjacent transpositions as one edit
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test_index() -> SearchIndex {
+        SearchIndex::with_entities(&[(
+            "crate-a",
+            vec![
+                json!({"@id": "./", "@type": "Dataset", "name": "E. coli growth curves"}),
+                json!({"@id": "#alice", "@type": "Person", "name": "Alice Smith"}),
+                json!({"@id": "#bob", "@type": "Person", "name": "Bob Smith"}),
+                json!({"@id": "#carol", "@type": "Person", "name": "Carol Smyth"}),
+                json!({"@id": "#od", "@type": "PropertyValue", "measurementTechnique": "spectrophotometry"}),
+            ],
+        )])
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let d = |a: &str, b: &str| {
+            edit_distance(
+                &a.chars().collect::<Vec<_>>(),
+                &b.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(d("smith", "smith"), 0);
+        assert_eq!(d("smiht", "smith"), 1);
+        assert_eq!(d("smth", "smith"), 1);
+        assert_eq!(d("growht", "growth"), 1);
+        assert_eq!(d("kitten", "sitting"), 3);
+        assert_eq!(d("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_correct_query() {
+        let index = test_index();
+        let correct = |q: &str| correct_query(&index, q).unwrap();
+
+        assert_eq!(correct("ecoli"), Some("e coli".to_string()));
+        // The more frequent of two equally close terms wins
+        assert_eq!(correct("smiht"), Some("smith".to_string()));
+        assert_eq!(
+            correct("Smiht AND grwoth curves"),
+            Some("smith AND growth curves".to_string())
+        );
+        // Field-qualified clauses are left alone
+        assert_eq!(
+            correct("name:grwoth \"coli grwoth\""),
+            Some("name:grwoth \"coli growth\"".to_string())
+        );
+        assert_eq!(correct("growth smith"), None);
+        // Words only found in other properties are not misspelled
+        assert_eq!(correct("spectrophotometry"), None);
+        assert_eq!(correct("xyzzyq"), None);
+    }
+}
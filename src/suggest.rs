@@ -0,0 +1,349 @@
+//! Type-ahead suggestions for entity names, keywords, authors and types.
+//!
+//! Every suggestable value is indexed in the `suggest` field as
+//! `<field>\u{1}<lowercased text>\u{0}<original text>`, once for the full value
+//! and once from each later word start, so a prefix lookup in the term
+//! dictionary (an FST) finds "E. coli growth" for both "e. c" and "gro".
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+use tantivy::query::{EnableScoring, Query};
+use tantivy::schema::IndexRecordOption;
+use tantivy::{DocId, DocSet, TERMINATED};
+
+use crate::error::IndexError;
+use crate::extract::{extract_keywords, extract_person_names, extract_summary, extract_types};
+use crate::index::SearchIndex;
+
+const FIELD_SEP: char = '\u{1}';
+const VALUE_SEP: char = '\u{0}';
+
+/// Word starts indexed per value, beyond the first word
+const MAX_WORD_STARTS: usize = 8;
+
+/// Values longer than this are not worth completing
+const MAX_VALUE_CHARS: usize = 200;
+
+/// Suggester a value belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SuggestField {
+    /// Entity `name`
+    Name,
+    /// Entries of `keywords`
+    Keyword,
+    /// Names of people and organisations, inline or as entities
+    Author,
+    /// Literal `@type` values
+    Type,
+}
+
+impl SuggestField {
+    pub const ALL: [SuggestField; 4] = [
+        SuggestField::Name,
+        SuggestField::Keyword,
+        SuggestField::Author,
+        SuggestField::Type,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuggestField::Name => "name",
+            SuggestField::Keyword => "keyword",
+            SuggestField::Author => "author",
+            SuggestField::Type => "type",
+        }
+    }
+
+    /// Parse a suggester name (`name`, `keyword`, `author` or `type`)
+    pub fn parse(name: &str) -> Result<Self, IndexError> {
+        match name {
+            "name" => Ok(SuggestField::Name),
+            "keyword" | "keywords" => Ok(SuggestField::Keyword),
+            "author" | "authors" => Ok(SuggestField::Author),
+            "type" | "@type" => Ok(SuggestField::Type),
+            other => Err(IndexError::InvalidQuery {
+                path: "field".to_string(),
+                reason: format!(
+                    "unknown suggestion field '{}' (expected name, keyword, author or type)",
+                    other
+                ),
+            }),
+        }
+    }
+}
+
+/// A completion with the number of entities it occurs in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub text: String,
+    pub field: SuggestField,
+    pub count: u64,
+}
+
+/// Terms to index in the `suggest` field for an entity
+pub(crate) fn suggestion_terms(entity: &Value) -> Vec<String> {
+    let types = extract_types(entity);
+    let name = extract_summary(entity).name;
+
+    let mut terms = Vec::new();
+    if let Some(name) = &name {
+        push_terms(&mut terms, SuggestField::Name, name, true);
+        if types.iter().any(|t| t == "Person" || t == "Organization") {
+            push_terms(&mut terms, SuggestField::Author, name, true);
+        }
+    }
+    for keyword in extract_keywords(entity) {
+        push_terms(&mut terms, SuggestField::Keyword, &keyword, true);
+    }
+    for person in extract_person_names(entity) {
+        push_terms(&mut terms, SuggestField::Author, &person, true);
+    }
+    for t in &types {
+        push_terms(&mut terms, SuggestField::Type, t, false);
+    }
+
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+fn push_terms(terms: &mut Vec<String>, field: SuggestField, value: &str, word_starts: bool) {
+    let value = value.trim();
+    if value.is_empty() || value.chars().count() > MAX_VALUE_CHARS {
+        return;
+    }
+
+    let term = |from: &str| {
+        format!(
+            "{}{}{}{}{}",
+            field.as_str(),
+            FIELD_SEP,
+            from.to_lowercase(),
+            VALUE_SEP,
+            value
+        )
+    };
+    terms.push(term(value));
+    if word_starts {
+        let starts = value
+            .char_indices()
+            .zip(value.chars().skip(1))
+            .filter(|((_, c), next)| c.is_whitespace() && !next.is_whitespace())
+            .map(|((i, c), _)| i + c.len_utf8())
+            .take(MAX_WORD_STARTS);
+        for start in starts {
+            terms.push(term(&value[start..]));
+        }
+    }
+}
+
+/// Most frequent values of the given suggesters starting with `prefix`
+///
+/// With a `filter`, only the entities matching it are counted.
+pub(crate) fn suggest(
+    index: &SearchIndex,
+    prefix: &str,
+    fields: &[SuggestField],
+    limit: usize,
+    filter: Option<&dyn Query>,
+) -> Result<Vec<Suggestion>, IndexError> {
+    let needle = prefix.trim_start().to_lowercase();
+    let searcher = index.searcher();
+    let filter = filter
+        .map(|filter| filter.weight(EnableScoring::disabled_from_searcher(&searcher)))
+        .transpose()?;
+
+    // Entity counts per exact value, summed over segments
+    let mut counts: HashMap<(SuggestField, String), u64> = HashMap::new();
+    for &field in fields {
+        let lower = format!("{}{}{}", field.as_str(), FIELD_SEP, needle);
+        let mut upper = lower.clone().into_bytes();
+        // Never part of UTF-8, so this bounds every term starting with `lower`
+        upper.push(0xFF);
+
+        for segment in searcher.segment_readers() {
+            // Live entities matching the filter, if any
+            let matching = match &filter {
+                Some(weight) => {
+                    let mut docs = HashSet::new();
+                    weight.for_each_no_score(segment, &mut |matched| {
+                        docs.extend(matched.iter().filter(|&&doc| !segment.is_deleted(doc)));
+                    })?;
+                    Some(docs)
+                }
+                None => None,
+            };
+            let inverted_index = segment.inverted_index(index.suggest_field)?;
+            let mut stream = inverted_index
+                .terms()
+                .range()
+                .ge(lower.as_bytes())
+                .lt(&upper)
+                .into_stream()?;
+
+            // A value matched from several word starts counts once per entity
+            let mut segment_counts: HashMap<String, u64> = HashMap::new();
+            while stream.advance() {
+                let Some((_, value)) = std::str::from_utf8(stream.key())
+                    .ok()
+                    .and_then(|key| key.split_once(VALUE_SEP))
+                else {
+                    continue;
+                };
+                let term_info = stream.value();
+                let count = match (&matching, segment.alive_bitset()) {
+                    (Some(matching), _) => {
+                        let mut postings = inverted_index
+                            .read_postings_from_terminfo(term_info, IndexRecordOption::Basic)?;
+                        count_matching(&mut postings, matching)
+                    }
+                    (None, Some(alive)) => inverted_index
+                        .read_postings_from_terminfo(term_info, IndexRecordOption::Basic)?
+                        .doc_freq_given_deletes(alive),
+                    (None, None) => term_info.doc_freq,
+                } as u64;
+                let entry = segment_counts.entry(value.to_string()).or_default();
+                *entry = (*entry).max(count);
+            }
+            for (value, count) in segment_counts {
+                *counts.entry((field, value)).or_default() += count;
+            }
+        }
+    }
+
+    // Merge spellings that only differ in case, shown as the most common one
+    let mut merged: HashMap<(SuggestField, String), (Suggestion, u64)> = HashMap::new();
+    for ((field, text), count) in counts {
+        if count == 0 {
+            continue;
+        }
+        let entry = merged
+            .entry((field, text.to_lowercase()))
+            .or_insert_with(|| {
+                (
+                    Suggestion {
+                        text: text.clone(),
+                        field,
+                        count: 0,
+                    },
+                    0,
+                )
+            });
+        entry.0.count += count;
+        if count > entry.1 || (count == entry.1 && text < entry.0.text) {
+            entry.0.text = text;
+            entry.1 = count;
+        }
+    }
+
+    let mut suggestions: Vec<Suggestion> = merged.into_values().map(|(s, _)| s).collect();
+    suggestions.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.text.cmp(&b.text))
+            .then_with(|| a.field.cmp(&b.field))
+    });
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+/// Number of documents of `docs` that are in `matching`
+fn count_matching(docs: &mut impl DocSet, matching: &HashSet<DocId>) -> u32 {
+    let mut count = 0;
+    let mut doc = docs.doc();
+    while doc != TERMINATED {
+        if matching.contains(&doc) {
+            count += 1;
+        }
+        doc = docs.advance();
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{CrateScope, QueryEngine};
+    use serde_json::json;
+
+    fn test_index() -> SearchIndex {
+        let entities = vec![
+            json!({
+                "@id": "./",
+                "@type": "Dataset",
+                "name": "E. coli growth curves",
+                "keywords": ["Ecology", "growth"],
+                "author": {"@id": "#alice"}
+            }),
+            json!({"@id": "#alice", "@type": "Person", "name": "Alice Smith"}),
+        ];
+        SearchIndex::with_entities(&[
+            ("crate-a", entities.clone()),
+            ("crate-b", entities),
+            (
+                "crate-c",
+                vec![json!({"@id": "./", "@type": "Dataset", "keywords": "ecology, soil"})],
+            ),
+        ])
+    }
+
+    fn texts(suggestions: &[Suggestion]) -> Vec<(&str, u64)> {
+        suggestions
+            .iter()
+            .map(|s| (s.text.as_str(), s.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_prefix_and_word_start_matches() {
+        let index = test_index();
+        let names = suggest(&index, "E. C", &[SuggestField::Name], 10, None).unwrap();
+        assert_eq!(texts(&names), vec![("E. coli growth curves", 2)]);
+
+        let names = suggest(&index, "grow", &[SuggestField::Name], 10, None).unwrap();
+        assert_eq!(texts(&names), vec![("E. coli growth curves", 2)]);
+
+        let authors = suggest(&index, "smi", &[SuggestField::Author], 10, None).unwrap();
+        assert_eq!(texts(&authors), vec![("Alice Smith", 2)]);
+
+        let types = suggest(&index, "d", &[SuggestField::Type], 10, None).unwrap();
+        assert_eq!(texts(&types), vec![("Dataset", 3)]);
+    }
+
+    #[test]
+    fn test_ranked_by_frequency_across_fields() {
+        let mut index = test_index();
+        let all = suggest(&index, "", &SuggestField::ALL, 3, None).unwrap();
+        assert_eq!(all[0].text, "Dataset");
+
+        // "Ecology" and "ecology" merge, shown in the more common spelling
+        let keywords = suggest(&index, "eco", &[SuggestField::Keyword], 10, None).unwrap();
+        assert_eq!(texts(&keywords), vec![("Ecology", 3)]);
+
+        // Deleted entities no longer count
+        index.remove_crate("crate-a").unwrap();
+        index.commit().unwrap();
+        let keywords = suggest(&index, "eco", &[SuggestField::Keyword], 10, None).unwrap();
+        assert_eq!(keywords[0].count, 2);
+        assert!(
+            suggest(&index, "soil", &[SuggestField::Author], 10, None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_scoped_counts() {
+        let index = test_index();
+        let engine = QueryEngine::new(&index).with_scope(Some(CrateScope::new("crate-c")));
+        let keywords = engine.suggest("eco", &[SuggestField::Keyword], 10).unwrap();
+        assert_eq!(texts(&keywords), vec![("ecology", 1)]);
+        assert!(
+            engine
+                .suggest("smi", &[SuggestField::Author], 10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
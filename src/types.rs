@@ -0,0 +1,275 @@
+//! Bundled schema.org / RO-Crate type hierarchy used to index supertypes.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Direct supertypes of the schema.org and RO-Crate types commonly found in crates
+const SUPERTYPES: &[(&str, &[&str])] = &[
+    // Top level
+    ("Action", &["Thing"]),
+    ("BioChemEntity", &["Thing"]),
+    ("CreativeWork", &["Thing"]),
+    ("Event", &["Thing"]),
+    ("Intangible", &["Thing"]),
+    ("MedicalEntity", &["Thing"]),
+    ("Organization", &["Thing"]),
+    ("Person", &["Thing"]),
+    ("Place", &["Thing"]),
+    ("Product", &["Thing"]),
+    ("Taxon", &["Thing"]),
+    // CreativeWork
+    ("Article", &["CreativeWork"]),
+    ("Blog", &["CreativeWork"]),
+    ("Book", &["CreativeWork"]),
+    ("Chapter", &["CreativeWork"]),
+    ("Clip", &["CreativeWork"]),
+    ("Collection", &["CreativeWork"]),
+    ("Comment", &["CreativeWork"]),
+    ("ComputationalWorkflow", &["CreativeWork"]),
+    ("Course", &["CreativeWork"]),
+    ("CreativeWorkSeries", &["CreativeWork"]),
+    ("DataCatalog", &["CreativeWork"]),
+    ("Dataset", &["CreativeWork"]),
+    ("DefinedTermSet", &["CreativeWork"]),
+    ("DigitalDocument", &["CreativeWork"]),
+    ("Drawing", &["CreativeWork"]),
+    ("HowTo", &["CreativeWork"]),
+    ("HowToSection", &["CreativeWork", "ItemList", "ListItem"]),
+    ("HowToStep", &["CreativeWork", "ItemList", "ListItem"]),
+    ("Legislation", &["CreativeWork"]),
+    ("Manuscript", &["CreativeWork"]),
+    ("Map", &["CreativeWork"]),
+    ("MediaObject", &["CreativeWork"]),
+    ("Message", &["CreativeWork"]),
+    ("Movie", &["CreativeWork"]),
+    ("MusicComposition", &["CreativeWork"]),
+    ("Painting", &["CreativeWork"]),
+    ("Photograph", &["CreativeWork"]),
+    ("Poster", &["CreativeWork"]),
+    ("Profile", &["CreativeWork"]),
+    ("PublicationIssue", &["CreativeWork"]),
+    ("PublicationVolume", &["CreativeWork"]),
+    ("Quotation", &["CreativeWork"]),
+    ("Review", &["CreativeWork"]),
+    ("Sculpture", &["CreativeWork"]),
+    ("SoftwareApplication", &["CreativeWork"]),
+    ("SoftwareSourceCode", &["CreativeWork"]),
+    ("Thesis", &["CreativeWork"]),
+    ("WebContent", &["CreativeWork"]),
+    ("WebPage", &["CreativeWork"]),
+    ("WebPageElement", &["CreativeWork"]),
+    ("WebSite", &["CreativeWork"]),
+    // Article
+    ("NewsArticle", &["Article"]),
+    ("Report", &["Article"]),
+    ("ScholarlyArticle", &["Article"]),
+    ("SocialMediaPosting", &["Article"]),
+    ("TechArticle", &["Article"]),
+    ("BlogPosting", &["SocialMediaPosting"]),
+    // Other CreativeWork subtrees
+    ("Periodical", &["CreativeWorkSeries"]),
+    ("DataFeed", &["Dataset"]),
+    ("AudioObject", &["MediaObject"]),
+    ("DataDownload", &["MediaObject"]),
+    ("ImageObject", &["MediaObject"]),
+    ("TextObject", &["MediaObject"]),
+    ("VideoObject", &["MediaObject"]),
+    ("3DModel", &["MediaObject"]),
+    ("MobileApplication", &["SoftwareApplication"]),
+    ("WebApplication", &["SoftwareApplication"]),
+    // RO-Crate: `File` is an alias of schema:MediaObject
+    ("File", &["MediaObject"]),
+    // Organization
+    ("Consortium", &["Organization"]),
+    ("Corporation", &["Organization"]),
+    ("EducationalOrganization", &["Organization"]),
+    ("GovernmentOrganization", &["Organization"]),
+    ("LibrarySystem", &["Organization"]),
+    ("LocalBusiness", &["Organization", "Place"]),
+    ("MedicalOrganization", &["Organization"]),
+    ("NGO", &["Organization"]),
+    ("NewsMediaOrganization", &["Organization"]),
+    ("PerformingGroup", &["Organization"]),
+    ("Project", &["Organization"]),
+    ("ResearchOrganization", &["Organization"]),
+    ("SportsOrganization", &["Organization"]),
+    ("CollegeOrUniversity", &["EducationalOrganization"]),
+    ("School", &["EducationalOrganization"]),
+    ("FundingAgency", &["Project"]),
+    ("ResearchProject", &["Project"]),
+    // Place
+    ("Accommodation", &["Place"]),
+    ("AdministrativeArea", &["Place"]),
+    ("CivicStructure", &["Place"]),
+    ("Landform", &["Place"]),
+    ("LandmarksOrHistoricalBuildings", &["Place"]),
+    ("Residence", &["Place"]),
+    ("TouristAttraction", &["Place"]),
+    ("City", &["AdministrativeArea"]),
+    ("Country", &["AdministrativeArea"]),
+    ("State", &["AdministrativeArea"]),
+    // Event
+    ("CourseInstance", &["Event"]),
+    ("EducationEvent", &["Event"]),
+    ("EventSeries", &["Event"]),
+    ("ExhibitionEvent", &["Event"]),
+    ("Festival", &["Event"]),
+    ("Hackathon", &["Event"]),
+    ("PublicationEvent", &["Event"]),
+    ("SocialEvent", &["Event"]),
+    // Action (RO-Crate provenance)
+    ("AssessAction", &["Action"]),
+    ("ConsumeAction", &["Action"]),
+    ("ControlAction", &["Action"]),
+    ("CreateAction", &["Action"]),
+    ("FindAction", &["Action"]),
+    ("InteractAction", &["Action"]),
+    ("MoveAction", &["Action"]),
+    ("OrganizeAction", &["Action"]),
+    ("SearchAction", &["Action"]),
+    ("TransferAction", &["Action"]),
+    ("UpdateAction", &["Action"]),
+    ("ActivateAction", &["ControlAction"]),
+    ("DeactivateAction", &["ControlAction"]),
+    ("ResumeAction", &["ControlAction"]),
+    ("SuspendAction", &["ControlAction"]),
+    ("PhotographAction", &["CreateAction"]),
+    ("WriteAction", &["CreateAction"]),
+    ("AllocateAction", &["OrganizeAction"]),
+    ("ApplyAction", &["OrganizeAction"]),
+    ("PlanAction", &["OrganizeAction"]),
+    ("AddAction", &["UpdateAction"]),
+    ("DeleteAction", &["UpdateAction"]),
+    ("ReplaceAction", &["UpdateAction"]),
+    // Intangible
+    ("Audience", &["Intangible"]),
+    ("Brand", &["Intangible"]),
+    ("ComputerLanguage", &["Intangible"]),
+    ("DefinedTerm", &["Intangible"]),
+    ("EntryPoint", &["Intangible"]),
+    ("Enumeration", &["Intangible"]),
+    ("FormalParameter", &["Intangible"]),
+    ("Grant", &["Intangible"]),
+    ("ItemList", &["Intangible"]),
+    ("Language", &["Intangible"]),
+    ("ListItem", &["Intangible"]),
+    ("Occupation", &["Intangible"]),
+    ("Offer", &["Intangible"]),
+    ("Quantity", &["Intangible"]),
+    ("Rating", &["Intangible"]),
+    ("Role", &["Intangible"]),
+    ("Schedule", &["Intangible"]),
+    ("Service", &["Intangible"]),
+    ("StructuredValue", &["Intangible"]),
+    ("MonetaryGrant", &["Grant"]),
+    ("Duration", &["Quantity"]),
+    ("OrganizationRole", &["Role"]),
+    ("ContactPoint", &["StructuredValue"]),
+    ("GeoCoordinates", &["StructuredValue"]),
+    ("GeoShape", &["StructuredValue"]),
+    ("MonetaryAmount", &["StructuredValue"]),
+    ("PropertyValue", &["StructuredValue"]),
+    ("QuantitativeValue", &["StructuredValue"]),
+    ("PostalAddress", &["ContactPoint"]),
+    // BioChemEntity
+    ("ChemicalSubstance", &["BioChemEntity"]),
+    ("Gene", &["BioChemEntity"]),
+    ("MolecularEntity", &["BioChemEntity"]),
+    ("Protein", &["BioChemEntity"]),
+];
+
+const SCHEMA_PREFIXES: &[&str] = &["schema:", "http://schema.org/", "https://schema.org/"];
+
+fn hierarchy() -> &'static HashMap<&'static str, &'static [&'static str]> {
+    static HIERARCHY: OnceLock<HashMap<&'static str, &'static [&'static str]>> = OnceLock::new();
+    HIERARCHY.get_or_init(|| SUPERTYPES.iter().copied().collect())
+}
+
+/// Strip a schema.org prefix or namespace from a type name
+pub fn local_type_name(type_name: &str) -> &str {
+    SCHEMA_PREFIXES
+        .iter()
+        .find_map(|prefix| type_name.strip_prefix(prefix))
+        .unwrap_or(type_name)
+}
+
+/// All known supertypes of a type, nearest first (not including the type itself)
+pub fn supertypes(type_name: &str) -> Vec<&'static str> {
+    let mut result: Vec<&'static str> = Vec::new();
+    let mut queue = vec![local_type_name(type_name)];
+    while !queue.is_empty() {
+        let mut next = Vec::new();
+        for name in queue {
+            for parent in hierarchy().get(name).copied().unwrap_or_default() {
+                if !result.contains(parent) {
+                    result.push(parent);
+                    next.push(*parent);
+                }
+            }
+        }
+        queue = next;
+    }
+    result
+}
+
+/// The given types followed by all of their supertypes, without duplicates
+///
+/// Prefixed schema.org types are also expanded to their local name.
+pub fn expand_types(types: &[String]) -> Vec<String> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut expanded = Vec::new();
+    for t in types {
+        for name in [t.as_str(), local_type_name(t)] {
+            if seen.insert(name.to_string()) {
+                expanded.push(name.to_string());
+            }
+        }
+    }
+    for t in types {
+        for parent in supertypes(t) {
+            if seen.insert(parent.to_string()) {
+                expanded.push(parent.to_string());
+            }
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supertypes() {
+        assert_eq!(supertypes("Dataset"), vec!["CreativeWork", "Thing"]);
+        assert_eq!(
+            supertypes("File"),
+            vec!["MediaObject", "CreativeWork", "Thing"]
+        );
+        assert_eq!(
+            supertypes("https://schema.org/ScholarlyArticle"),
+            vec!["Article", "CreativeWork", "Thing"]
+        );
+        assert!(supertypes("LocalBusiness").contains(&"Place"));
+        assert!(supertypes("UnknownType").is_empty());
+    }
+
+    #[test]
+    fn test_expand_types() {
+        let types = vec!["File".to_string(), "SoftwareSourceCode".to_string()];
+        assert_eq!(
+            expand_types(&types),
+            vec![
+                "File",
+                "SoftwareSourceCode",
+                "MediaObject",
+                "CreativeWork",
+                "Thing"
+            ]
+        );
+        assert_eq!(
+            expand_types(&["schema:Person".to_string()]),
+            vec!["schema:Person", "Person", "Thing"]
+        );
+    }
+}
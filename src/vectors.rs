@@ -0,0 +1,542 @@
+//! Flat on-disk vector index stored next to the Tantivy index.
+//!
+//! Vectors are kept per crate and searched exhaustively. Changes are staged
+//! like index writes and only become visible (and are written to disk) on
+//! commit, so the vectors always describe the committed entities. The file
+//! is an append-only log of per-crate records: a commit appends the crates it
+//! touched, and the log is compacted once superseded records dominate it.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::embed::{Embedder, cosine};
+use crate::error::IndexError;
+
+const MAGIC: &[u8; 8] = b"RCVEC02\n";
+
+/// Log record replacing the vectors of a crate
+const RECORD_PUT: u8 = 1;
+/// Log record removing the vectors of a crate
+const RECORD_REMOVE: u8 = 2;
+
+/// Compact the log once it holds this many times the live vectors
+const COMPACT_RATIO: usize = 2;
+
+/// Vector of one entity occurrence
+#[derive(Debug, Clone)]
+pub(crate) struct EntityVector {
+    pub entity_id: String,
+    pub vector: Vec<f32>,
+}
+
+/// A nearest-neighbour match
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VectorMatch {
+    pub crate_id: String,
+    pub entity_id: String,
+    pub similarity: f32,
+}
+
+pub(crate) struct VectorIndex {
+    embedder: Arc<dyn Embedder>,
+    /// File the committed vectors are written to; `None` keeps them in memory
+    path: Option<PathBuf>,
+    /// Committed vectors per crate
+    crates: HashMap<String, Vec<EntityVector>>,
+    /// Staged replacements (`Some`) and removals (`None`), in order
+    pending: Vec<(String, Option<Vec<EntityVector>>)>,
+    /// Vectors in the log file including superseded ones; `None` if the file
+    /// has to be rewritten before appending
+    logged: Option<usize>,
+}
+
+/// Vectors replayed from a log file
+struct VectorLog {
+    crates: HashMap<String, Vec<EntityVector>>,
+    /// Vectors in all records, including superseded ones
+    logged: usize,
+    /// Length of the intact prefix of the file
+    valid_len: u64,
+}
+
+impl VectorIndex {
+    /// Open the vectors at `path`, or start empty
+    ///
+    /// Vectors written by a different embedder are dropped.
+    pub fn open(embedder: Arc<dyn Embedder>, path: Option<PathBuf>) -> Self {
+        let mut crates = HashMap::new();
+        let mut logged = None;
+        if let Some(path) = &path
+            && path.exists()
+        {
+            match read_vectors(path, &embedder.id(), embedder.dimensions()) {
+                Ok(Some(log)) => {
+                    // Drop a record cut short by a crash so appends stay readable
+                    if let Err(e) = truncate_to(path, log.valid_len) {
+                        eprintln!(
+                            "Warning: cannot repair vector index {}: {}",
+                            path.display(),
+                            e
+                        );
+                    } else {
+                        logged = Some(log.logged);
+                    }
+                    crates = log.crates;
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!(
+                    "Warning: ignoring unreadable vector index {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        Self {
+            embedder,
+            path,
+            crates,
+            pending: Vec::new(),
+            logged,
+        }
+    }
+
+    pub fn embedder(&self) -> &dyn Embedder {
+        self.embedder.as_ref()
+    }
+
+    /// Whether committed vectors exist for a crate
+    pub fn contains_crate(&self, crate_id: &str) -> bool {
+        self.crates.contains_key(crate_id)
+    }
+
+    /// Stage the vectors of a crate, replacing any it had
+    pub fn stage_crate(&mut self, crate_id: &str, vectors: Vec<EntityVector>) {
+        self.pending.push((crate_id.to_string(), Some(vectors)));
+    }
+
+    /// Stage the removal of a crate's vectors
+    pub fn stage_removal(&mut self, crate_id: &str) {
+        self.pending.push((crate_id.to_string(), None));
+    }
+
+    /// Stage the removal of crates `keep` rejects, returning how many
+    pub fn retain_crates(&mut self, keep: impl Fn(&str) -> bool) -> usize {
+        let stale: Vec<String> = self.crates.keys().filter(|id| !keep(id)).cloned().collect();
+        for crate_id in &stale {
+            self.stage_removal(crate_id);
+        }
+        stale.len()
+    }
+
+    /// Apply staged changes and write them to disk
+    ///
+    /// The final state of each touched crate is appended to the log; the
+    /// file is only rewritten when it is compacted. Nothing is written when
+    /// no changes are staged.
+    pub fn commit(&mut self) -> Result<(), IndexError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut touched = Vec::new();
+        let mut seen = HashSet::new();
+        for (crate_id, vectors) in self.pending.drain(..) {
+            match vectors {
+                Some(vectors) => self.crates.insert(crate_id.clone(), vectors),
+                None => self.crates.remove(&crate_id),
+            };
+            if seen.insert(crate_id.clone()) {
+                touched.push(crate_id);
+            }
+        }
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let live: usize = self.crates.values().map(Vec::len).sum();
+        let appended: usize = touched
+            .iter()
+            .filter_map(|crate_id| self.crates.get(crate_id))
+            .map(Vec::len)
+            .sum();
+        match self.logged {
+            Some(logged) if logged + appended <= COMPACT_RATIO * live => {
+                append_records(path, &touched, &self.crates)?;
+                self.logged = Some(logged + appended);
+            }
+            _ => {
+                write_vectors(
+                    path,
+                    &self.embedder.id(),
+                    self.embedder.dimensions(),
+                    &self.crates,
+                )?;
+                self.logged = Some(live);
+            }
+        }
+        Ok(())
+    }
+
+    /// Discard staged changes
+    pub fn rollback(&mut self) {
+        self.pending.clear();
+    }
+
+    /// The `limit` committed vectors most similar to `query`, best first
+    ///
+    /// Only crates accepted by `filter` are searched.
+    pub fn search(
+        &self,
+        query: &[f32],
+        limit: usize,
+        min_similarity: f32,
+        filter: impl Fn(&str) -> bool,
+    ) -> Vec<VectorMatch> {
+        let mut matches: Vec<VectorMatch> = self
+            .crates
+            .iter()
+            .filter(|(crate_id, _)| filter(crate_id))
+            .flat_map(|(crate_id, vectors)| {
+                vectors.iter().filter_map(move |v| {
+                    let similarity = cosine(query, &v.vector);
+                    (similarity >= min_similarity).then(|| VectorMatch {
+                        crate_id: crate_id.clone(),
+                        entity_id: v.entity_id.clone(),
+                        similarity,
+                    })
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| a.crate_id.cmp(&b.crate_id))
+                .then_with(|| a.entity_id.cmp(&b.entity_id))
+        });
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Replay the log written by `write_vectors` and `append_records`; `None`
+/// if another embedder wrote it
+///
+/// Lengths read from the file are checked against its size before anything
+/// is allocated for them. Reading stops at a truncated or corrupt record.
+fn read_vectors(
+    path: &Path,
+    embedder_id: &str,
+    dimensions: usize,
+) -> io::Result<Option<VectorLog>> {
+    let bytes = fs::read(path)?;
+    let file_len = bytes.len() as u64;
+    let mut reader = Cursor::new(bytes);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a vector index",
+        ));
+    }
+    if read_string(&mut reader)? != embedder_id || read_u32(&mut reader)? as usize != dimensions {
+        return Ok(None);
+    }
+
+    let mut log = VectorLog {
+        crates: HashMap::new(),
+        logged: 0,
+        valid_len: reader.position(),
+    };
+    while log.valid_len < file_len {
+        match read_record(&mut reader, file_len, dimensions) {
+            Ok((crate_id, Some(vectors))) => {
+                log.logged += vectors.len();
+                log.crates.insert(crate_id, vectors);
+            }
+            Ok((crate_id, None)) => {
+                log.crates.remove(&crate_id);
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: dropping vector records after byte {} of {}: {}",
+                    log.valid_len,
+                    path.display(),
+                    e
+                );
+                break;
+            }
+        }
+        log.valid_len = reader.position();
+    }
+    Ok(Some(log))
+}
+
+/// Read one log record: the crate ID and its vectors, `None` for a removal
+fn read_record(
+    reader: &mut impl Read,
+    file_len: u64,
+    dimensions: usize,
+) -> io::Result<(String, Option<Vec<EntityVector>>)> {
+    let mut tag = [0u8; 1];
+    reader.read_exact(&mut tag)?;
+    let crate_id = read_string(reader)?;
+    match tag[0] {
+        RECORD_REMOVE => Ok((crate_id, None)),
+        RECORD_PUT => {
+            let count = read_u32(reader)? as usize;
+            // Each vector takes at least its id length and its components
+            let entry_len = 4 + dimensions as u64 * 4;
+            if count as u64 > file_len / entry_len {
+                return Err(truncated());
+            }
+            let mut vectors = Vec::with_capacity(count);
+            for _ in 0..count {
+                let entity_id = read_string(reader)?;
+                let mut bytes = vec![0u8; dimensions * 4];
+                reader.read_exact(&mut bytes)?;
+                let vector = bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                vectors.push(EntityVector { entity_id, vector });
+            }
+            Ok((crate_id, Some(vectors)))
+        }
+        tag => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown record tag {}", tag),
+        )),
+    }
+}
+
+/// Write a compacted log with all vectors to a temporary file and move it
+/// over `path`
+fn write_vectors(
+    path: &Path,
+    embedder_id: &str,
+    dimensions: usize,
+    crates: &HashMap<String, Vec<EntityVector>>,
+) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+    writer.write_all(MAGIC)?;
+    write_string(&mut writer, embedder_id)?;
+    writer.write_all(&(dimensions as u32).to_le_bytes())?;
+    for (crate_id, vectors) in crates {
+        write_record(&mut writer, crate_id, Some(vectors))?;
+    }
+    writer.into_inner()?.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+/// Append the current state of the given crates to the log
+fn append_records(
+    path: &Path,
+    crate_ids: &[String],
+    crates: &HashMap<String, Vec<EntityVector>>,
+) -> io::Result<()> {
+    let file = fs::OpenOptions::new().append(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+    for crate_id in crate_ids {
+        write_record(&mut writer, crate_id, crates.get(crate_id))?;
+    }
+    writer.into_inner()?.sync_all()
+}
+
+fn write_record(
+    writer: &mut impl Write,
+    crate_id: &str,
+    vectors: Option<&Vec<EntityVector>>,
+) -> io::Result<()> {
+    let Some(vectors) = vectors else {
+        writer.write_all(&[RECORD_REMOVE])?;
+        return write_string(writer, crate_id);
+    };
+    writer.write_all(&[RECORD_PUT])?;
+    write_string(writer, crate_id)?;
+    writer.write_all(&(vectors.len() as u32).to_le_bytes())?;
+    for v in vectors {
+        write_string(writer, &v.entity_id)?;
+        for x in &v.vector {
+            writer.write_all(&x.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Cut a file back to its intact prefix
+fn truncate_to(path: &Path, len: u64) -> io::Result<()> {
+    let file = fs::OpenOptions::new().write(true).open(path)?;
+    if file.metadata()?.len() > len {
+        file.set_len(len)?;
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read a length-prefixed string without trusting the length for allocation
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(reader)? as u64;
+    let mut bytes = Vec::new();
+    if reader.take(len).read_to_end(&mut bytes)? as u64 != len {
+        return Err(truncated());
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated vector index")
+}
+
+fn write_string(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    writer.write_all(&(s.len() as u32).to_le_bytes())?;
+    writer.write_all(s.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed::HashingEmbedder;
+
+    fn entity(embedder: &dyn Embedder, id: &str, text: &str) -> EntityVector {
+        EntityVector {
+            entity_id: id.to_string(),
+            vector: embedder.embed(text),
+        }
+    }
+
+    #[test]
+    fn test_staged_vectors_persist_on_commit() {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vectors.bin");
+        let embedder: Arc<dyn Embedder> = Arc::new(HashingEmbedder::new(64));
+
+        let mut index = VectorIndex::open(embedder.clone(), Some(path.clone()));
+        index.stage_crate(
+            "crate-a",
+            vec![entity(embedder.as_ref(), "#a", "yeast transcriptome")],
+        );
+        index.stage_crate(
+            "crate-b",
+            vec![entity(embedder.as_ref(), "#b", "soil nitrogen")],
+        );
+        assert!(
+            index
+                .search(&embedder.embed("yeast"), 10, 0.0, |_| true)
+                .is_empty()
+        );
+        index.commit().unwrap();
+
+        let query = embedder.embed("yeast transcriptomics");
+        let matches = index.search(&query, 10, 0.3, |_| true);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].entity_id, "#a");
+        assert!(index.search(&query, 10, 0.3, |c| c != "crate-a").is_empty());
+
+        index.stage_removal("crate-a");
+        index.rollback();
+        index.commit().unwrap();
+
+        // Reopened with the same embedder, with another one the vectors are dropped
+        let reopened = VectorIndex::open(embedder.clone(), Some(path.clone()));
+        assert!(reopened.contains_crate("crate-a") && reopened.contains_crate("crate-b"));
+        let other = VectorIndex::open(Arc::new(HashingEmbedder::new(32)), Some(path));
+        assert!(!other.contains_crate("crate-a"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_commits_append_and_compact() {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vectors.bin");
+        let embedder: Arc<dyn Embedder> = Arc::new(HashingEmbedder::new(16));
+        let vectors = |text: &str| vec![entity(embedder.as_ref(), "#e", text)];
+
+        let mut index = VectorIndex::open(embedder.clone(), Some(path.clone()));
+        index.stage_crate("crate-a", vectors("yeast"));
+        index.commit().unwrap();
+        let first_len = fs::metadata(&path).unwrap().len();
+
+        // A second crate is appended, not rewritten
+        index.stage_crate("crate-b", vectors("soil"));
+        index.commit().unwrap();
+        let appended_len = fs::metadata(&path).unwrap().len();
+        assert!(appended_len > first_len);
+        assert_eq!(index.logged, Some(2));
+
+        // Replacing crate-a leaves a superseded record until compaction
+        index.stage_crate("crate-a", vectors("yeast cells"));
+        index.commit().unwrap();
+        assert_eq!(index.logged, Some(3));
+        index.stage_removal("crate-b");
+        index.commit().unwrap();
+        assert_eq!(index.logged, Some(1));
+        assert!(fs::metadata(&path).unwrap().len() < appended_len);
+
+        // A record cut short by a crash is dropped on open
+        index.stage_crate("crate-c", vectors("nitrogen"));
+        index.commit().unwrap();
+        let intact_len = fs::metadata(&path).unwrap().len();
+        let file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        let mut writer = BufWriter::new(file);
+        write_record(&mut writer, "crate-d", Some(&vectors("water"))).unwrap();
+        let torn = writer.into_inner().unwrap();
+        torn.set_len(intact_len + 10).unwrap();
+
+        let reopened = VectorIndex::open(embedder.clone(), Some(path.clone()));
+        assert!(reopened.contains_crate("crate-a") && reopened.contains_crate("crate-c"));
+        assert!(!reopened.contains_crate("crate-b") && !reopened.contains_crate("crate-d"));
+        assert_eq!(fs::metadata(&path).unwrap().len(), intact_len);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_lengths_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("rocrate-test-{}", ulid::Ulid::new()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vectors.bin");
+        let embedder = HashingEmbedder::new(64);
+        let header = |out: &mut Vec<u8>| {
+            out.extend_from_slice(MAGIC);
+            write_string(out, &embedder.id()).unwrap();
+            out.extend_from_slice(&64u32.to_le_bytes());
+        };
+        let mut header_bytes = Vec::new();
+        header(&mut header_bytes);
+
+        // A string claiming to be 4 GiB long
+        let mut bytes = Vec::new();
+        header(&mut bytes);
+        bytes.push(RECORD_PUT);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        let log = read_vectors(&path, &embedder.id(), 64).unwrap().unwrap();
+        assert!(log.crates.is_empty());
+        assert_eq!(log.valid_len, header_bytes.len() as u64);
+
+        // A crate claiming billions of vectors
+        let mut bytes = Vec::new();
+        header(&mut bytes);
+        bytes.push(RECORD_PUT);
+        write_string(&mut bytes, "crate-a").unwrap();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        let log = read_vectors(&path, &embedder.id(), 64).unwrap().unwrap();
+        assert!(log.crates.is_empty());
+        assert_eq!(log.valid_len, header_bytes.len() as u64);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}